@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::fs;
+use std::path::Path;
+
+use crate::Session;
+use crate::theme::{Paint, Role};
+
+struct VariantResult {
+    output: String,
+    tokens: u64,
+    ms: u64,
+    cached: bool,
+}
+
+#[derive(Default)]
+struct VariantTotals {
+    runs: u64,
+    tokens: u64,
+    ms: u64,
+    failures: u64,
+}
+
+impl VariantTotals {
+    fn add(&mut self, result: &Result<VariantResult>) {
+        match result {
+            Ok(result) => {
+                self.runs += 1;
+                self.tokens += result.tokens;
+                self.ms += result.ms;
+            }
+            Err(_) => self.failures += 1,
+        }
+    }
+
+    fn average_tokens(&self) -> u64 {
+        self.tokens.checked_div(self.runs).unwrap_or(0)
+    }
+
+    fn average_ms(&self) -> u64 {
+        self.ms.checked_div(self.runs).unwrap_or(0)
+    }
+}
+
+fn read_file(path: &Path, what: &str) -> Result<String> {
+    fs::read_to_string(path)
+        .context(format!("Failed to read {}: {}", what, path.display()))
+}
+
+pub fn apply_prompt(prompt: &str, input: &str) -> String {
+    if prompt.contains("{input}") {
+        prompt.replace("{input}", input)
+    } else {
+        format!("{}\n\n{}", prompt.trim_end(), input)
+    }
+}
+
+async fn run_variant(session: &mut Session, prompt: &str, input: &str) -> Result<VariantResult> {
+    let response = session.send_query(&apply_prompt(prompt, input)).await?;
+    Ok(VariantResult {
+        output: response.data.output,
+        tokens: response.data.tokens,
+        ms: response.meta.ms,
+        cached: response.cached_at.is_some(),
+    })
+}
+
+fn describe(name: &str, result: &Result<VariantResult>) -> String {
+    match result {
+        Ok(result) => format!(
+            "### {} ({} tokens, {}ms{})\n\n{}\n\n",
+            name,
+            result.tokens,
+            result.ms,
+            if result.cached { ", local cache" } else { "" },
+            result.output.trim()
+        ),
+        Err(e) => format!("### {} (failed)\n\n{}\n\n", name, e),
+    }
+}
+
+pub async fn run(session: &mut Session, prompt_a: &Path, prompt_b: &Path, inputs: &Path) -> Result<String> {
+    let prompt_a_text = read_file(prompt_a, "prompt A")?;
+    let prompt_b_text = read_file(prompt_b, "prompt B")?;
+    let inputs_text = read_file(inputs, "inputs file")?;
+
+    let input_lines: Vec<&str> = inputs_text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    if input_lines.is_empty() {
+        anyhow::bail!("No inputs found in {}", inputs.display());
+    }
+
+    let mut totals_a = VariantTotals::default();
+    let mut totals_b = VariantTotals::default();
+    let mut details = String::new();
+
+    for (i, input) in input_lines.iter().enumerate() {
+        eprintln!("{} {}", format!("[{}/{}]", i + 1, input_lines.len()).dimmed(), input.paint(Role::Query));
+
+        let result_a = run_variant(session, &prompt_a_text, input).await;
+        let result_b = run_variant(session, &prompt_b_text, input).await;
+        totals_a.add(&result_a);
+        totals_b.add(&result_b);
+
+        details.push_str(&format!("\n## Input {}: {}\n\n", i + 1, input));
+        details.push_str(&describe("A", &result_a));
+        details.push_str(&describe("B", &result_b));
+    }
+
+    let mut report = String::new();
+    report.push_str("# A/B prompt comparison\n\n");
+    report.push_str(&format!("- Prompt A: `{}`\n", prompt_a.display()));
+    report.push_str(&format!("- Prompt B: `{}`\n", prompt_b.display()));
+    report.push_str(&format!("- Inputs: {} from `{}`\n\n", input_lines.len(), inputs.display()));
+    report.push_str("| Variant | Runs | Failures | Total tokens | Avg tokens | Avg latency |\n");
+    report.push_str("|---|---|---|---|---|---|\n");
+    for (name, totals) in [("A", &totals_a), ("B", &totals_b)] {
+        report.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {}ms |\n",
+            name,
+            totals.runs,
+            totals.failures,
+            totals.tokens,
+            totals.average_tokens(),
+            totals.average_ms()
+        ));
+    }
+    report.push_str(&details);
+
+    Ok(report)
+}
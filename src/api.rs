@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{Error, Result};
+
+pub const API_URL: &str = "https://kagi.com/api/v0/fastgpt";
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Serialize)]
+pub struct FastGPTRequest {
+    pub query: String,
+    pub cache: bool,
+    pub web_search: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct FastGPTResponse {
+    pub meta: Meta,
+    pub data: Data,
+    #[serde(skip)]
+    pub cached_at: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Meta {
+    pub id: String,
+    pub node: String,
+    pub ms: u64,
+    pub api_balance: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Data {
+    pub output: String,
+    pub references: Vec<Reference>,
+    pub tokens: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Reference {
+    pub title: String,
+    pub snippet: String,
+    pub url: String,
+}
+
+#[derive(Clone, Default)]
+pub struct CallOptions {
+    pub timeout: Option<Duration>,
+    pub cancel: Option<CancellationToken>,
+    pub retries: u32,
+}
+
+impl CallOptions {
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn cancel_with(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Event {
+    RequestStarted { query: String, attempt: u32 },
+    RequestFinished { success: bool },
+    Retry { attempt: u32, delay: Duration, error: String },
+    Usage { tokens: u64, ms: u64, api_balance: Option<f64> },
+    CacheHit { query: String },
+}
+
+pub type Hook = Arc<dyn Fn(&Event) + Send + Sync>;
+
+fn request_error(error: reqwest::Error, options: &CallOptions) -> Error {
+    match options.timeout {
+        Some(after) if error.is_timeout() => Error::Timeout { after },
+        _ => Error::Network(error),
+    }
+}
+
+fn status_error(status: reqwest::StatusCode, retry_after: Option<Duration>, message: String) -> Error {
+    match status.as_u16() {
+        401 | 403 => Error::Auth { status: status.as_u16(), message },
+        429 => Error::RateLimited { retry_after },
+        status => Error::Api { status, message },
+    }
+}
+
+fn retry_delay(error: &Error, attempt: u32) -> Option<Duration> {
+    let backoff = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt)).min(RETRY_MAX_DELAY);
+    match error {
+        Error::RateLimited { retry_after } => Some(retry_after.unwrap_or(backoff)),
+        Error::Network(_) => Some(backoff),
+        Error::Api { status, .. } if *status >= 500 => Some(backoff),
+        _ => None,
+    }
+}
+
+async fn cancellable<T>(options: &CallOptions, call: impl Future<Output = Result<T>>) -> Result<T> {
+    match &options.cancel {
+        Some(token) => token.run_until_cancelled(call).await.unwrap_or(Err(Error::Cancelled)),
+        None => call.await,
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    api_key: String,
+    endpoint: String,
+    hooks: Vec<Hook>,
+}
+
+impl Client {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_key: api_key.into(),
+            endpoint: API_URL.to_string(),
+            hooks: Vec::new(),
+        }
+    }
+
+    pub fn on_event(mut self, hook: impl Fn(&Event) + Send + Sync + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    pub fn emit(&self, event: &Event) {
+        for hook in &self.hooks {
+            hook(event);
+        }
+    }
+
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    async fn post(&self, request: &FastGPTRequest, options: &CallOptions) -> Result<reqwest::Response> {
+        if self.api_key.trim().is_empty() {
+            return Err(Error::Config("the API key is empty".to_string()));
+        }
+        let mut builder = self.http
+            .post(&self.endpoint)
+            .header("Authorization", format!("Bot {}", self.api_key))
+            .header("Content-Type", "application/json")
+            .json(request);
+        if let Some(timeout) = options.timeout {
+            builder = builder.timeout(timeout);
+        }
+        builder
+            .send()
+            .await
+            .map_err(|e| request_error(e, options))
+    }
+
+    pub async fn query(&self, request: &FastGPTRequest) -> Result<FastGPTResponse> {
+        self.query_with(request, &CallOptions::default()).await
+    }
+
+    pub async fn query_with(&self, request: &FastGPTRequest, options: &CallOptions) -> Result<FastGPTResponse> {
+        cancellable(options, async {
+            let mut attempt = 0;
+            loop {
+                self.emit(&Event::RequestStarted { query: request.query.clone(), attempt });
+                let result = self.send_query(request, options).await;
+                self.emit(&Event::RequestFinished { success: result.is_ok() });
+
+                match result {
+                    Ok(response) => {
+                        self.emit(&Event::Usage {
+                            tokens: response.data.tokens,
+                            ms: response.meta.ms,
+                            api_balance: response.meta.api_balance,
+                        });
+                        return Ok(response);
+                    }
+                    Err(error) => match retry_delay(&error, attempt) {
+                        Some(delay) if attempt < options.retries => {
+                            attempt += 1;
+                            self.emit(&Event::Retry { attempt, delay, error: error.to_string() });
+                            futures_timer::Delay::new(delay).await;
+                        }
+                        _ => return Err(error),
+                    },
+                }
+            }
+        }).await
+    }
+
+    async fn send_query(&self, request: &FastGPTRequest, options: &CallOptions) -> Result<FastGPTResponse> {
+        let response = self.post(request, options).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = response.headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs);
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(status_error(status, retry_after, error_text));
+        }
+
+        let body = response.text().await.map_err(|e| request_error(e, options))?;
+        serde_json::from_str(&body).map_err(|e| Error::Parse(e.to_string()))
+    }
+
+    pub async fn balance(&self) -> Result<f64> {
+        self.balance_with(&CallOptions::default()).await
+    }
+
+    pub async fn balance_with(&self, options: &CallOptions) -> Result<f64> {
+        cancellable(options, self.fetch_balance(options)).await
+    }
+
+    async fn fetch_balance(&self, options: &CallOptions) -> Result<f64> {
+        let request = FastGPTRequest {
+            query: "ping".to_string(),
+            cache: true,
+            web_search: false,
+        };
+
+        let response = self.post(&request, options).await?;
+        let status = response.status();
+        let response_text = response.text().await.map_err(|e| request_error(e, options))?;
+
+        if let Ok(fastgpt_response) = serde_json::from_str::<FastGPTResponse>(&response_text) {
+            if let Some(balance) = fastgpt_response.meta.api_balance {
+                return Ok(balance);
+            }
+        }
+
+        if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&response_text) {
+            if let Some(balance) = json_value.pointer("/meta/api_balance").and_then(|balance| balance.as_f64()) {
+                return Ok(balance);
+            }
+        }
+
+        if !status.is_success() {
+            return Err(status_error(status, None, response_text));
+        }
+        Err(Error::Parse(format!("API balance not available in response: {}", response_text)))
+    }
+}
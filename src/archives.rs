@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+pub const MAX_ENTRY_BYTES: u64 = 10_000_000;
+
+pub enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+pub struct Entry {
+    pub name: PathBuf,
+    pub size: u64,
+}
+
+pub fn kind_of(path: &Path) -> Option<Kind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Kind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else {
+        None
+    }
+}
+
+fn is_visible(name: &Path) -> bool {
+    name.components().all(|component| match component {
+        Component::Normal(part) => {
+            let part = part.to_string_lossy();
+            !part.starts_with('.') && part != "__MACOSX"
+        }
+        Component::CurDir => true,
+        _ => false,
+    })
+}
+
+fn visit_tar(reader: impl Read, visit: &mut dyn FnMut(&Entry, &mut dyn Read) -> Result<bool>) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().context("Failed to read tar archive")? {
+        let mut entry = entry.context("Failed to read tar entry")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path().context("Invalid path in tar archive")?.into_owned();
+        if !is_visible(&name) {
+            continue;
+        }
+        let size = entry.header().size().unwrap_or(0);
+        if !visit(&Entry { name, size }, &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn visit_zip(file: File, visit: &mut dyn FnMut(&Entry, &mut dyn Read) -> Result<bool>) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(file).context("Failed to read zip archive")?;
+    let mut names: Vec<(usize, PathBuf)> = (0..archive.len())
+        .filter_map(|i| {
+            let entry = archive.by_index(i).ok()?;
+            let name = entry.enclosed_name()?;
+            (entry.is_file() && is_visible(&name)).then_some((i, name))
+        })
+        .collect();
+    names.sort_by(|a, b| a.1.cmp(&b.1));
+
+    for (i, name) in names {
+        let mut entry = archive.by_index(i).context("Failed to read zip entry")?;
+        let size = entry.size();
+        if !visit(&Entry { name, size }, &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub fn visit(path: &Path, kind: Kind, mut visit: impl FnMut(&Entry, &mut dyn Read) -> Result<bool>) -> Result<()> {
+    let file = File::open(path).context(format!("Failed to open archive: {}", path.display()))?;
+    match kind {
+        Kind::Zip => visit_zip(file, &mut visit),
+        Kind::Tar => visit_tar(file, &mut visit),
+        Kind::TarGz => visit_tar(GzDecoder::new(file), &mut visit),
+    }
+    .context(format!("Failed to read archive: {}", path.display()))
+}
@@ -0,0 +1,75 @@
+use anyhow::{Context, Result};
+use include_dir::{include_dir, Dir, DirEntry};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+static ASSETS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/assets");
+
+pub fn override_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("fastgpt").join("assets"))
+}
+
+pub fn read(path: &str) -> Option<String> {
+    if let Some(content) = override_dir().and_then(|dir| fs::read_to_string(dir.join(path)).ok()) {
+        return Some(content);
+    }
+    ASSETS.get_file(path).and_then(|file| file.contents_utf8()).map(str::to_string)
+}
+
+pub fn is_embedded(path: &str) -> bool {
+    ASSETS.get_file(path).is_some()
+}
+
+pub fn files(dir: &str) -> Vec<String> {
+    let mut paths: Vec<String> = ASSETS
+        .get_dir(dir)
+        .map(|dir| dir.files().map(|file| file.path().to_string_lossy().to_string()).collect())
+        .unwrap_or_default();
+
+    let overrides = override_dir().and_then(|root| fs::read_dir(root.join(dir)).ok());
+    for entry in overrides.into_iter().flatten().flatten() {
+        if entry.path().is_file() {
+            let path = format!("{}/{}", dir, entry.file_name().to_string_lossy());
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    paths.sort();
+    paths
+}
+
+fn collect_files<'a>(dir: &'a Dir<'a>, files: &mut Vec<&'a include_dir::File<'a>>) {
+    for entry in dir.entries() {
+        match entry {
+            DirEntry::Dir(dir) => collect_files(dir, files),
+            DirEntry::File(file) => files.push(file),
+        }
+    }
+}
+
+pub fn dump(target: &Path, force: bool) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut files = Vec::new();
+    collect_files(&ASSETS, &mut files);
+    files.sort_by_key(|file| file.path());
+
+    let mut written = Vec::new();
+    let mut skipped = Vec::new();
+    for file in files {
+        let path = target.join(file.path());
+        if path.exists() && !force {
+            skipped.push(path);
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create directory: {}", parent.display()))?;
+        }
+        fs::write(&path, file.contents())
+            .context(format!("Failed to write file: {}", path.display()))?;
+        written.push(path);
+    }
+
+    Ok((written, skipped))
+}
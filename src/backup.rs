@@ -0,0 +1,184 @@
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+
+use crate::{get_config_path, get_data_dir, lock, write_atomic};
+
+const CONFIG_PREFIX: &str = "config";
+const DATA_PREFIX: &str = "data";
+const CONFIG_FILE: &str = "config.toml";
+
+pub struct Summary {
+    pub files: usize,
+    pub skipped: Vec<PathBuf>,
+}
+
+fn config_dir() -> Result<PathBuf> {
+    let path = get_config_path()?;
+    Ok(path.parent().context("Could not determine config directory")?.to_path_buf())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir).context(format!("Failed to read directory: {}", dir.display()))? {
+        let path = entry.context(format!("Failed to read directory: {}", dir.display()))?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if !is_excluded(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_excluded(path: &Path) -> bool {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    name == lock::LOCK_FILE || name.ends_with(".lock") || name.contains(".tmp-")
+}
+
+fn config_without_key(content: &str) -> Result<String> {
+    let mut config: toml::Table = toml::from_str(content).context("Failed to parse config file")?;
+    config.remove("api_key");
+    toml::to_string_pretty(&config).context("Failed to serialize config")
+}
+
+fn create_private(path: &Path) -> std::io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let file = options.open(path)?;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        Ok(file)
+    }
+    #[cfg(not(unix))]
+    options.open(path)
+}
+
+pub fn create(target: &Path, include_key: bool) -> Result<usize> {
+    let config_dir = config_dir()?;
+    let data_dir = get_data_dir()?;
+
+    let file = create_private(target).context(format!("Failed to create backup: {}", target.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mut count = 0;
+
+    for (prefix, dir) in [(CONFIG_PREFIX, &config_dir), (DATA_PREFIX, &data_dir)] {
+        let mut files = Vec::new();
+        collect_files(dir, &mut files)?;
+        files.sort();
+
+        for path in files {
+            let relative = path.strip_prefix(dir).unwrap_or(&path);
+            let name = Path::new(prefix).join(relative);
+
+            if prefix == CONFIG_PREFIX && relative == Path::new(CONFIG_FILE) && !include_key {
+                let content = fs::read_to_string(&path)
+                    .context(format!("Failed to read file: {}", path.display()))?;
+                let content = config_without_key(&content)?;
+                let mut header = tar::Header::new_gnu();
+                header.set_size(content.len() as u64);
+                header.set_mode(0o600);
+                header.set_mtime(fs::metadata(&path).ok()
+                    .and_then(|metadata| metadata.modified().ok())
+                    .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|age| age.as_secs())
+                    .unwrap_or(0));
+                header.set_cksum();
+                archive.append_data(&mut header, &name, content.as_bytes())
+                    .context(format!("Failed to add {} to backup", path.display()))?;
+            } else {
+                archive.append_path_with_name(&path, &name)
+                    .context(format!("Failed to add {} to backup", path.display()))?;
+            }
+            count += 1;
+        }
+    }
+
+    archive.into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context(format!("Failed to write backup: {}", target.display()))?;
+    Ok(count)
+}
+
+fn restore_path(name: &Path, config_dir: &Path, data_dir: &Path) -> Option<PathBuf> {
+    if !name.components().all(|component| matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    let mut components = name.components();
+    let base = match components.next()?.as_os_str().to_str()? {
+        CONFIG_PREFIX => config_dir,
+        DATA_PREFIX => data_dir,
+        _ => return None,
+    };
+    let relative = components.as_path();
+    (!relative.as_os_str().is_empty()).then(|| base.join(relative))
+}
+
+fn merge_local_key(content: &str, path: &Path) -> Result<String> {
+    let mut config: toml::Table = toml::from_str(content).context("Failed to parse config in backup")?;
+    if config.contains_key("api_key") {
+        return Ok(content.to_string());
+    }
+    let local_key = fs::read_to_string(path).ok()
+        .and_then(|local| toml::from_str::<toml::Table>(&local).ok())
+        .and_then(|mut local| local.remove("api_key"));
+    match local_key {
+        Some(key) => {
+            config.insert("api_key".to_string(), key);
+            toml::to_string_pretty(&config).context("Failed to serialize config")
+        }
+        None => Ok(content.to_string()),
+    }
+}
+
+pub fn restore(source: &Path, force: bool) -> Result<Summary> {
+    let config_dir = config_dir()?;
+    let data_dir = get_data_dir()?;
+    let config_path = config_dir.join(CONFIG_FILE);
+
+    let file = File::open(source).context(format!("Failed to open backup: {}", source.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut summary = Summary { files: 0, skipped: Vec::new() };
+
+    for entry in archive.entries().context(format!("Failed to read backup: {}", source.display()))? {
+        let mut entry = entry.context(format!("Failed to read backup: {}", source.display()))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path().context("Invalid path in backup")?.to_path_buf();
+        let Some(path) = restore_path(&name, &config_dir, &data_dir) else {
+            anyhow::bail!("Refusing to restore unexpected path from backup: {}", name.display());
+        };
+
+        if path.exists() && !force {
+            summary.skipped.push(path);
+            continue;
+        }
+
+        let mut content = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut content)
+            .context(format!("Failed to read {} from backup", name.display()))?;
+
+        if path == config_path {
+            let text = String::from_utf8(content).context("Config in backup is not valid UTF-8")?;
+            content = merge_local_key(&text, &path)?.into_bytes();
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create directory: {}", parent.display()))?;
+        }
+        write_atomic(&path, &content)?;
+        summary.files += 1;
+    }
+
+    Ok(summary)
+}
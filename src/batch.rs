@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::theme::{Paint, Role};
+use crate::{write_atomic, BudgetExhausted, Session};
+
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub inputs: PathBuf,
+    pub output: Option<PathBuf>,
+    pub completed: Vec<String>,
+}
+
+pub enum Outcome {
+    Finished { answered: usize, failed: usize },
+    Stopped { checkpoint: PathBuf, remaining: usize, reason: String },
+}
+
+pub fn default_checkpoint_path(inputs: &Path) -> PathBuf {
+    let mut name = inputs.file_name().unwrap_or_default().to_os_string();
+    name.push(".checkpoint.json");
+    inputs.with_file_name(name)
+}
+
+pub fn load_checkpoint(path: &Path) -> Result<Checkpoint> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read checkpoint: {}", path.display()))?;
+    serde_json::from_str(&content)
+        .context(format!("Failed to parse checkpoint: {}", path.display()))
+}
+
+fn save_checkpoint(path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    let content = serde_json::to_string_pretty(checkpoint)
+        .context("Failed to serialize checkpoint")?;
+    write_atomic(path, content.as_bytes())
+        .context(format!("Failed to write checkpoint: {}", path.display()))
+}
+
+fn is_throttled(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<fastgpt::Error>(), Some(fastgpt::Error::RateLimited { .. }))
+        || error.downcast_ref::<BudgetExhausted>().is_some()
+}
+
+fn write_result(output: Option<&Path>, result: &serde_json::Value) -> Result<()> {
+    let line = serde_json::to_string(result).context("Failed to serialize batch result")?;
+    match output {
+        Some(path) => {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .context(format!("Failed to open batch output: {}", path.display()))?;
+            writeln!(file, "{}", line).context(format!("Failed to write batch output: {}", path.display()))
+        }
+        None => {
+            println!("{}", line);
+            Ok(())
+        }
+    }
+}
+
+pub async fn run(session: &mut Session, checkpoint_path: &Path, mut checkpoint: Checkpoint) -> Result<Outcome> {
+    let inputs_text = fs::read_to_string(&checkpoint.inputs)
+        .context(format!("Failed to read inputs file: {}", checkpoint.inputs.display()))?;
+
+    let completed: HashSet<String> = checkpoint.completed.iter().cloned().collect();
+    let queries: Vec<&str> = inputs_text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if queries.is_empty() {
+        anyhow::bail!("No queries found in {}", checkpoint.inputs.display());
+    }
+    let pending: Vec<&str> = queries.iter().copied().filter(|query| !completed.contains(*query)).collect();
+    if !completed.is_empty() {
+        eprintln!("{} {} of {} queries already answered", "Resuming:".bright_green(), queries.len() - pending.len(), queries.len());
+    }
+
+    let mut answered = 0;
+    let mut failed = 0;
+    for (i, query) in pending.iter().enumerate() {
+        eprintln!("{} {}", format!("[{}/{}]", i + 1, pending.len()).dimmed(), query.paint(Role::Query));
+
+        let result = tokio::select! {
+            result = session.send_query(query) => result,
+            _ = tokio::signal::ctrl_c() => {
+                save_checkpoint(checkpoint_path, &checkpoint)?;
+                return Ok(Outcome::Stopped {
+                    checkpoint: checkpoint_path.to_path_buf(),
+                    remaining: pending.len() - i,
+                    reason: "Interrupted".to_string(),
+                });
+            }
+        };
+
+        match result {
+            Ok(response) => {
+                write_result(checkpoint.output.as_deref(), &serde_json::json!({
+                    "query": query,
+                    "output": response.data.output,
+                    "references": response.data.references,
+                    "tokens": response.data.tokens,
+                }))?;
+                checkpoint.completed.push(query.to_string());
+                answered += 1;
+            }
+            Err(e) if is_throttled(&e) => {
+                save_checkpoint(checkpoint_path, &checkpoint)?;
+                return Ok(Outcome::Stopped {
+                    checkpoint: checkpoint_path.to_path_buf(),
+                    remaining: pending.len() - i,
+                    reason: e.to_string(),
+                });
+            }
+            Err(e) => {
+                eprintln!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                write_result(checkpoint.output.as_deref(), &serde_json::json!({
+                    "query": query,
+                    "error": e.to_string(),
+                }))?;
+                failed += 1;
+            }
+        }
+        save_checkpoint(checkpoint_path, &checkpoint)?;
+    }
+
+    if failed == 0 {
+        fs::remove_file(checkpoint_path)
+            .context(format!("Failed to remove checkpoint: {}", checkpoint_path.display()))?;
+    }
+    Ok(Outcome::Finished { answered, failed })
+}
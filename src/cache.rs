@@ -0,0 +1,259 @@
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use dirs::cache_dir;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::lock::{self, StoreLock};
+use crate::retention::{self, StoredFile};
+use crate::FastGPTResponse;
+
+pub const DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    cached_at: u64,
+    query: String,
+    response: FastGPTResponse,
+}
+
+pub fn get_cache_dir() -> Result<PathBuf> {
+    let cache_dir = cache_dir()
+        .context("Could not determine cache directory")?;
+
+    let app_cache_dir = cache_dir.join("fastgpt");
+    fs::create_dir_all(&app_cache_dir)
+        .context("Failed to create cache directory")?;
+
+    Ok(app_cache_dir)
+}
+
+pub fn cache_key(query: &str, web_search: bool) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(if web_search { b"1" } else { b"0" });
+    hasher.update(query.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn entry_path(key: &str) -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join(format!("{}.json", key)))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub struct CacheStats {
+    pub entries: usize,
+    pub expired: usize,
+    pub total_size: u64,
+}
+
+pub fn parse_duration(input: &str) -> Result<u64> {
+    let input = input.trim();
+    let split_at = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split_at);
+
+    let amount: u64 = amount
+        .parse()
+        .context(format!("Invalid duration: {}", input))?;
+
+    let multiplier = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => anyhow::bail!("Invalid duration unit in '{}' (use s, m, h, d or w)", input),
+    };
+
+    amount
+        .checked_mul(multiplier)
+        .context(format!("Duration is too long: {}", input))
+}
+
+pub fn lock_exclusive() -> Result<StoreLock> {
+    lock::exclusive(&get_cache_dir()?.join(lock::LOCK_FILE))
+}
+
+fn lock_shared() -> Result<StoreLock> {
+    lock::shared(&get_cache_dir()?.join(lock::LOCK_FILE))
+}
+
+fn read_entry(path: &Path) -> Option<CacheEntry> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn cache_files() -> Result<Vec<PathBuf>> {
+    let dir = get_cache_dir()?;
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(&dir).context("Failed to read cache directory")? {
+        let path = entry.context("Failed to read cache entry")?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+pub fn format_timestamp(secs: u64) -> String {
+    match Local.timestamp_opt(secs as i64, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => secs.to_string(),
+    }
+}
+
+pub fn lookup(key: &str, ttl: Option<u64>) -> Option<FastGPTResponse> {
+    let _lock = lock_shared().ok()?;
+    let path = entry_path(key).ok()?;
+    let entry = read_entry(&path)?;
+
+    if let Some(ttl) = ttl {
+        if now_secs().saturating_sub(entry.cached_at) > ttl {
+            return None;
+        }
+    }
+
+    let mut response = entry.response;
+    response.cached_at = Some(entry.cached_at);
+    Some(response)
+}
+
+pub fn store(key: &str, query: &str, response: &FastGPTResponse) -> Result<()> {
+    let _lock = lock_exclusive()?;
+    let path = entry_path(key)?;
+    let entry = CacheEntry {
+        cached_at: now_secs(),
+        query: query.to_string(),
+        response: response.clone(),
+    };
+
+    let content = serde_json::to_string(&entry)
+        .context("Failed to serialize cache entry")?;
+
+    fs::write(&path, content)
+        .context(format!("Failed to write cache entry: {}", path.display()))?;
+
+    Ok(())
+}
+
+pub fn clear() -> Result<usize> {
+    let _lock = lock_exclusive()?;
+    let files = cache_files()?;
+
+    for path in &files {
+        fs::remove_file(path)
+            .context(format!("Failed to remove cache entry: {}", path.display()))?;
+    }
+
+    Ok(files.len())
+}
+
+pub fn stats(ttl: Option<u64>) -> Result<CacheStats> {
+    let _lock = lock_shared()?;
+    let now = now_secs();
+    let mut stats = CacheStats {
+        entries: 0,
+        expired: 0,
+        total_size: 0,
+    };
+
+    for path in cache_files()? {
+        stats.entries += 1;
+        stats.total_size += fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+        let expired = match (read_entry(&path), ttl) {
+            (Some(entry), Some(ttl)) => now.saturating_sub(entry.cached_at) > ttl,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        if expired {
+            stats.expired += 1;
+        }
+    }
+
+    Ok(stats)
+}
+
+pub fn purge(max_age: u64) -> Result<usize> {
+    let _lock = lock_exclusive()?;
+    let now = now_secs();
+    let mut removed = 0;
+
+    for path in cache_files()? {
+        let stale = match read_entry(&path) {
+            Some(entry) => now.saturating_sub(entry.cached_at) > max_age,
+            None => true,
+        };
+        if stale {
+            fs::remove_file(&path)
+                .context(format!("Failed to remove cache entry: {}", path.display()))?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+pub fn stored_files() -> Result<Vec<StoredFile>> {
+    Ok(cache_files()?
+        .into_iter()
+        .map(|path| StoredFile {
+            kind: retention::Kind::Cache,
+            size: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            last_used: read_entry(&path).map_or_else(|| retention::modified_secs(&path), |entry| entry.cached_at as i64),
+            path,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_duration;
+
+    #[test]
+    fn parses_each_unit() {
+        assert_eq!(parse_duration("90").unwrap(), 90);
+        assert_eq!(parse_duration("30s").unwrap(), 30);
+        assert_eq!(parse_duration("5m").unwrap(), 300);
+        assert_eq!(parse_duration("2h").unwrap(), 7_200);
+        assert_eq!(parse_duration("7d").unwrap(), 604_800);
+        assert_eq!(parse_duration("2w").unwrap(), 1_209_600);
+    }
+
+    #[test]
+    fn ignores_surrounding_whitespace() {
+        assert_eq!(parse_duration(" 3d ").unwrap(), 259_200);
+        assert_eq!(parse_duration("1 h").unwrap(), 3_600);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("5y").is_err());
+        assert!(parse_duration("-5d").is_err());
+        assert!(parse_duration("1.5h").is_err());
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert!(parse_duration("99999999999999999w").is_err());
+        assert!(parse_duration("99999999999999999999").is_err());
+    }
+}
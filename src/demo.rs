@@ -0,0 +1,110 @@
+use crate::table::TABLE_INSTRUCTION;
+use crate::{Data, FastGPTResponse, Meta, Reference};
+
+pub const DEMO_BALANCE: f64 = 12.34;
+
+struct Canned {
+    keywords: &'static [&'static str],
+    output: &'static str,
+    references: &'static [(&'static str, &'static str, &'static str)],
+}
+
+const CANNED: &[Canned] = &[
+    Canned {
+        keywords: &["rust", "borrow", "ownership"],
+        output: "Rust manages memory through **ownership**: every value has a single owner, and the value is dropped when the owner goes out of scope【1】. References let you *borrow* a value without taking ownership, and the borrow checker enforces that you have either one mutable reference or any number of shared ones at a time【2】.\n\n\
+```rust\nfn main() {\n    let s = String::from(\"hello\");\n    let len = length(&s); // borrow, s is still usable\n    println!(\"{} has {} bytes\", s, len);\n}\n\nfn length(s: &str) -> usize {\n    s.len()\n}\n```\n\n\
+Key rules:\n\n- Each value has exactly one owner\n- Moving a value transfers ownership【1】\n- References must never outlive the value they point to【2】",
+        references: &[
+            ("What is Ownership? - The Rust Programming Language", "https://doc.rust-lang.org/book/ch04-01-what-is-ownership.html", "Ownership is a set of rules that govern how a Rust program manages memory."),
+            ("References and Borrowing - The Rust Programming Language", "https://doc.rust-lang.org/book/ch04-02-references-and-borrowing.html", "A reference is like a pointer in that it's an address we can follow to access the data."),
+        ],
+    },
+    Canned {
+        keywords: &["weather", "forecast", "temperature"],
+        output: "It is currently **18°C and partly cloudy** with a light westerly breeze【1】. The next three days look mild:\n\n\
+- **Tomorrow:** sunny spells, high of 21°C\n- **Day 2:** scattered showers in the afternoon, high of 17°C【2】\n- **Day 3:** dry and bright, high of 20°C\n\n\
+*This is demo data, not a real forecast.*",
+        references: &[
+            ("Local forecast - Example Weather Service", "https://weather.example.com/forecast", "Hourly and 10-day forecasts for your area."),
+            ("Rain radar - Example Weather Service", "https://weather.example.com/radar", "Live precipitation radar."),
+        ],
+    },
+    Canned {
+        keywords: &["mountain", "tallest", "highest"],
+        output: "The tallest mountain in Europe is **Mount Elbrus** in the Caucasus, at 5,642 m (18,510 ft)【1】. If the Caucasus is counted as part of Asia, the title goes to **Mont Blanc** in the Alps at about 4,806 m【2】.",
+        references: &[
+            ("Mount Elbrus - Wikipedia", "https://en.wikipedia.org/wiki/Mount_Elbrus", "Mount Elbrus is the highest and most prominent peak in Russia and Europe."),
+            ("Mont Blanc - Wikipedia", "https://en.wikipedia.org/wiki/Mont_Blanc", "Mont Blanc is the highest mountain in the Alps and Western Europe."),
+        ],
+    },
+    Canned {
+        keywords: &["flight", "trip", "hotel", "notes"],
+        output: "According to your notes, the **return flight leaves Tuesday at 18:05**. Your outbound flight to Lisbon departs Friday at 07:40 from gate B12, and hotel check-in at Casa do Rio is after 15:00.",
+        references: &[],
+    },
+];
+
+const TABLE_OUTPUT: &str = "| Language | First released | Typing | Memory management |\n\
+|---|---|---|---|\n\
+| Rust | 2015 | Static | Ownership and borrowing |\n\
+| Go | 2012 | Static | Garbage collected |\n\
+| Python | 1991 | Dynamic | Reference counting and GC |\n\
+| TypeScript | 2012 | Static (gradual) | Garbage collected (JS runtime) |";
+
+const DEFAULT_OUTPUT: &str = "This is a **demo answer** generated locally, so no API quota was used【1】. \
+In a real session FastGPT searches the web and writes a concise answer with numbered citations like the one at the end of this sentence【2】.\n\n\
+Things to try in demo mode:\n\n\
+- Ask about *Rust ownership* to see a highlighted code block\n\
+- Ask about the *weather* for a bulleted answer\n\
+- Use `/table` to see a tabular answer\n\
+- Run `/history`, `/export html demo.html` or `/save-response demo.md`";
+
+const DEFAULT_REFERENCES: &[(&str, &str, &str)] = &[
+    ("Kagi FastGPT API", "https://help.kagi.com/kagi/api/fastgpt.html", "FastGPT is a Kagi service using powerful LLMs to answer user queries running a full search engine underneath."),
+    ("kagi-fastgpt-cli on GitHub", "https://github.com/0xGingi/kagi-fastgpt-cli", "A command-line interface for Kagi's FastGPT API."),
+];
+
+fn current_question(contextual_query: &str) -> &str {
+    contextual_query
+        .rsplit_once("Current question: ")
+        .map(|(_, question)| question)
+        .unwrap_or(contextual_query)
+}
+
+pub fn response(contextual_query: &str) -> FastGPTResponse {
+    let question = current_question(contextual_query);
+    let lowered = question.to_lowercase();
+
+    let (output, references) = if question.contains(TABLE_INSTRUCTION) {
+        (TABLE_OUTPUT, &[][..])
+    } else {
+        CANNED
+            .iter()
+            .find(|canned| canned.keywords.iter().any(|keyword| lowered.contains(keyword)))
+            .map(|canned| (canned.output, canned.references))
+            .unwrap_or((DEFAULT_OUTPUT, DEFAULT_REFERENCES))
+    };
+
+    FastGPTResponse {
+        meta: Meta {
+            id: format!("demo-{}", uuid::Uuid::new_v4()),
+            node: "demo".to_string(),
+            ms: 850 + (output.len() as u64 % 400),
+            api_balance: Some(DEMO_BALANCE),
+        },
+        data: Data {
+            output: output.to_string(),
+            references: references
+                .iter()
+                .map(|(title, url, snippet)| Reference {
+                    title: title.to_string(),
+                    snippet: snippet.to_string(),
+                    url: url.to_string(),
+                })
+                .collect(),
+            tokens: (contextual_query.len() + output.len()) as u64 / 4,
+        },
+        cached_at: None,
+    }
+}
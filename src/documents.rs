@@ -0,0 +1,307 @@
+use anyhow::{Context, Result};
+use html_escape::decode_html_entities;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use crate::archives::MAX_ENTRY_BYTES;
+use crate::retention;
+
+const OUTPUT_MAX_LINES: usize = 40;
+
+#[derive(Clone, Copy, Default)]
+pub struct Options {
+    pub notebook_outputs: bool,
+}
+
+enum Format {
+    Docx,
+    Odt,
+    Notebook,
+}
+
+fn format_of(path: &Path) -> Option<Format> {
+    match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+        "docx" => Some(Format::Docx),
+        "odt" => Some(Format::Odt),
+        "ipynb" => Some(Format::Notebook),
+        _ => None,
+    }
+}
+
+pub fn is_document(path: &Path) -> bool {
+    format_of(path).is_some()
+}
+
+fn read_entry(path: &Path, name: &str) -> Result<String> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .context(format!("{} is not a valid document archive", path.display()))?;
+    let entry = archive
+        .by_name(name)
+        .context(format!("{} has no {}", path.display(), name))?;
+    if entry.size() > MAX_ENTRY_BYTES {
+        anyhow::bail!("{} in {} is larger than {}", name, path.display(), retention::format_size(MAX_ENTRY_BYTES));
+    }
+    let mut xml = String::new();
+    entry.take(MAX_ENTRY_BYTES).read_to_string(&mut xml).context(format!("Failed to read {} from {}", name, path.display()))?;
+    Ok(xml)
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    attrs: &'a str,
+    closing: bool,
+    empty: bool,
+}
+
+fn tokens(xml: &str) -> impl Iterator<Item = Result<Tag<'_>, &str>> {
+    let mut rest = xml;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        if let Some(tag) = rest.strip_prefix('<') {
+            let end = tag.find('>').unwrap_or(tag.len());
+            let (inner, after) = tag.split_at(end);
+            rest = after.get(1..).unwrap_or_default();
+            let closing = inner.starts_with('/');
+            let empty = inner.ends_with('/');
+            let inner = inner.trim_start_matches('/').trim_end_matches('/');
+            let (name, attrs) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+            return Some(Ok(Tag { name, attrs, closing, empty }));
+        }
+        let end = rest.find('<').unwrap_or(rest.len());
+        let (text, after) = rest.split_at(end);
+        rest = after;
+        Some(Err(text))
+    })
+}
+
+fn attr<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let start = attrs.find(&format!("{}=\"", name))? + name.len() + 2;
+    let len = attrs[start..].find('"')?;
+    Some(&attrs[start..start + len])
+}
+
+fn docx_text(xml: &str) -> String {
+    let mut out = String::new();
+    let mut paragraph = String::new();
+    let mut prefix = String::new();
+    let mut in_text = false;
+    let mut in_run = false;
+
+    for token in tokens(xml) {
+        match token {
+            Err(text) if in_text => paragraph.push_str(&decode_html_entities(text)),
+            Err(_) => {}
+            Ok(tag) => match (tag.name, tag.closing) {
+                ("w:t", false) => in_text = !tag.empty,
+                ("w:t", true) => in_text = false,
+                ("w:r", false) => in_run = !tag.empty,
+                ("w:r", true) => in_run = false,
+                ("w:tab", false) if in_run => paragraph.push('\t'),
+                ("w:br" | "w:cr", false) => paragraph.push('\n'),
+                ("w:pStyle", false) => {
+                    let style = attr(tag.attrs, "w:val").unwrap_or_default().to_lowercase();
+                    if let Some(level) = style.strip_prefix("heading").and_then(|level| level.parse::<usize>().ok()) {
+                        prefix = format!("{} ", "#".repeat(level.clamp(1, 6)));
+                    } else if style == "title" {
+                        prefix = "# ".to_string();
+                    }
+                }
+                ("w:numPr", false) if prefix.is_empty() => prefix = "- ".to_string(),
+                ("w:p", true) => {
+                    if !paragraph.trim().is_empty() {
+                        out.push_str(&prefix);
+                        out.push_str(paragraph.trim_end());
+                    }
+                    out.push('\n');
+                    paragraph.clear();
+                    prefix.clear();
+                }
+                ("w:tc", true) => {
+                    out.truncate(out.trim_end_matches('\n').len());
+                    out.push_str(" | ");
+                }
+                ("w:tr", true) => {
+                    out.truncate(out.trim_end_matches(" | ").len());
+                    out.push('\n');
+                }
+                _ => {}
+            },
+        }
+    }
+    out
+}
+
+fn odt_text(xml: &str) -> String {
+    let mut out = String::new();
+    let mut in_body = false;
+    let mut list_depth = 0usize;
+    let mut paragraph_depth = 0usize;
+
+    for token in tokens(xml) {
+        match token {
+            Err(text) if in_body && paragraph_depth > 0 => out.push_str(&decode_html_entities(text)),
+            Err(_) => {}
+            Ok(tag) => match (tag.name, tag.closing) {
+                ("office:text", closing) => in_body = !closing && !tag.empty,
+                _ if !in_body => {}
+                ("text:h", false) => {
+                    let level = attr(tag.attrs, "text:outline-level").and_then(|level| level.parse().ok()).unwrap_or(1usize);
+                    out.push_str(&format!("{} ", "#".repeat(level.clamp(1, 6))));
+                    paragraph_depth += 1;
+                }
+                ("text:p", false) => {
+                    if list_depth > 0 && (out.is_empty() || out.ends_with('\n')) {
+                        out.push_str(&format!("{}- ", "  ".repeat(list_depth - 1)));
+                    }
+                    if tag.empty {
+                        out.push('\n');
+                    } else {
+                        paragraph_depth += 1;
+                    }
+                }
+                ("text:p" | "text:h", true) => {
+                    paragraph_depth = paragraph_depth.saturating_sub(1);
+                    if paragraph_depth == 0 {
+                        out.push('\n');
+                    }
+                }
+                ("text:list", false) if !tag.empty => list_depth += 1,
+                ("text:list", true) => list_depth = list_depth.saturating_sub(1),
+                ("text:s", false) => {
+                    let count = attr(tag.attrs, "text:c").and_then(|count| count.parse().ok()).unwrap_or(1);
+                    out.push_str(&" ".repeat(count));
+                }
+                ("text:tab", false) => out.push('\t'),
+                ("text:line-break", false) => out.push('\n'),
+                ("table:table-cell", true) => {
+                    out.truncate(out.trim_end_matches('\n').len());
+                    out.push_str(" | ");
+                }
+                ("table:table-row", true) => {
+                    out.truncate(out.trim_end_matches(" | ").len());
+                    out.push('\n');
+                }
+                _ => {}
+            },
+        }
+    }
+    out
+}
+
+fn joined(source: &Value) -> String {
+    match source {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn output_text(output: &Value) -> String {
+    let text = match output["output_type"].as_str().unwrap_or_default() {
+        "stream" => joined(&output["text"]),
+        "error" => format!("{}: {}", output["ename"].as_str().unwrap_or("Error"), output["evalue"].as_str().unwrap_or_default()),
+        _ => match output["data"].get("text/plain") {
+            Some(text) => joined(text),
+            None => output["data"]
+                .as_object()
+                .and_then(|data| data.keys().next())
+                .map(|kind| format!("[{} output]", kind))
+                .unwrap_or_default(),
+        },
+    };
+
+    let lines: Vec<&str> = text.trim_end().lines().collect();
+    if lines.len() <= OUTPUT_MAX_LINES {
+        return lines.join("\n");
+    }
+    format!("{}\n[... {} more lines ...]", lines[..OUTPUT_MAX_LINES].join("\n"), lines.len() - OUTPUT_MAX_LINES)
+}
+
+fn notebook_text(json: &str, options: &Options) -> Result<String> {
+    let notebook: Value = serde_json::from_str(json).context("Notebook is not valid JSON")?;
+    let cells = notebook["cells"].as_array().context("Notebook has no cells")?;
+    let language = notebook["metadata"]["kernelspec"]["language"]
+        .as_str()
+        .or_else(|| notebook["metadata"]["language_info"]["name"].as_str())
+        .unwrap_or("python");
+
+    let mut out = String::new();
+    for cell in cells {
+        let source = joined(&cell["source"]);
+        match cell["cell_type"].as_str().unwrap_or_default() {
+            "markdown" => out.push_str(&format!("{}\n\n", source.trim_end())),
+            "code" if !source.trim().is_empty() => {
+                out.push_str(&format!("```{}\n{}\n```\n", language, source.trim_end()));
+                let outputs = cell["outputs"].as_array().map(Vec::as_slice).unwrap_or_default();
+                if options.notebook_outputs {
+                    for output in outputs.iter().map(output_text).filter(|text| !text.is_empty()) {
+                        out.push_str(&format!("Output:\n```\n{}\n```\n", output));
+                    }
+                } else if !outputs.is_empty() {
+                    out.push_str("[outputs omitted]\n");
+                }
+                out.push('\n');
+            }
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+pub fn tidy(text: &str) -> String {
+    let mut tidied = String::new();
+    let mut blank = true;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            if !blank {
+                tidied.push('\n');
+            }
+            blank = true;
+            continue;
+        }
+        tidied.push_str(line);
+        tidied.push('\n');
+        blank = false;
+    }
+    tidied.trim_end().to_string()
+}
+
+pub fn extract_text(path: &Path, options: &Options) -> Result<String> {
+    let text = match format_of(path) {
+        Some(Format::Docx) => docx_text(&read_entry(path, "word/document.xml")?),
+        Some(Format::Odt) => odt_text(&read_entry(path, "content.xml")?),
+        Some(Format::Notebook) => {
+            let json = fs::read_to_string(path).context(format!("Failed to read file: {}", path.display()))?;
+            notebook_text(&json, options).context(format!("Failed to parse notebook: {}", path.display()))?
+        }
+        None => anyhow::bail!("Unsupported document format: {}", path.display()),
+    };
+    Ok(tidy(&text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_tabs_inside_runs_only() {
+        let xml = r#"<w:p><w:pPr><w:tabs><w:tab w:val="left" w:pos="720"/></w:tabs></w:pPr><w:r><w:t>Name</w:t></w:r><w:r><w:tab/><w:t>Value</w:t></w:r></w:p>"#;
+        assert_eq!(docx_text(xml), "Name\tValue\n");
+    }
+
+    #[test]
+    fn reads_headings_and_lists() {
+        let xml = r#"<w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Intro</w:t></w:r></w:p><w:p><w:pPr><w:numPr/></w:pPr><w:r><w:t>Item</w:t></w:r></w:p>"#;
+        assert_eq!(docx_text(xml), "## Intro\n- Item\n");
+    }
+
+    #[test]
+    fn tidy_collapses_blank_lines() {
+        assert_eq!(tidy("\n\nfirst  \n \n\n\tsecond\n\n"), "first\n\n\tsecond");
+    }
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("FastGPT API rejected the API key (status {status}): {message}")]
+    Auth { status: u16, message: String },
+
+    #[error("FastGPT API rate limit reached{}", retry_after.map(|after| format!(", retry after {}s", after.as_secs())).unwrap_or_default())]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error("API request failed with status {status}: {message}")]
+    Api { status: u16, message: String },
+
+    #[error("Failed to send request to FastGPT API")]
+    Network(#[source] reqwest::Error),
+
+    #[error("FastGPT API did not answer within {}s", after.as_secs_f64())]
+    Timeout { after: Duration },
+
+    #[error("Request to FastGPT API was cancelled")]
+    Cancelled,
+
+    #[error("Failed to parse response from FastGPT API: {0}")]
+    Parse(String),
+
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("Invalid session snapshot: {0}")]
+    Snapshot(String),
+}
@@ -0,0 +1,229 @@
+use anyhow::Result;
+use chrono::Local;
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::path::PathBuf;
+use syntect::html::highlighted_html_for_string;
+
+use crate::{assets, markdown, ConversationEntry, FileContext, Reference};
+
+const HTML_CODE_THEME: &str = "InspiredGitHub";
+
+const HTML_STYLE_ASSET: &str = "themes/export.css";
+
+fn link_citations(text: &str, references: &[Reference]) -> String {
+    let reference_regex = Regex::new(r"【(\d+)】").unwrap();
+    reference_regex.replace_all(text, |caps: &Captures| {
+        match caps[1].parse::<usize>().ok().and_then(|n| references.get(n.wrapping_sub(1))) {
+            Some(reference) => format!("[\\[{}\\]](<{}>)", &caps[1], reference.url),
+            None => String::new(),
+        }
+    }).to_string()
+}
+
+fn highlight_html(code: &str, language: &str) -> String {
+    let (syntaxes, themes) = markdown::highlighting();
+    let syntax = syntaxes
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+
+    highlighted_html_for_string(code, syntaxes, syntax, &themes.themes[HTML_CODE_THEME])
+        .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>", encode_text(code)))
+}
+
+fn answer_html(entry: &ConversationEntry) -> String {
+    let text = link_citations(&html_escape::decode_html_entities(&entry.response), &entry.references);
+
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let mut events = Vec::new();
+    let mut code: Option<(String, String)> = None;
+    for event in Parser::new_ext(&text, options) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(language) => language.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((language, String::new()));
+            }
+            Event::Text(text) if code.is_some() => {
+                if let Some((_, code)) = &mut code {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((language, code)) = code.take() {
+                    events.push(Event::Html(CowStr::from(highlight_html(&code, &language))));
+                }
+            }
+            Event::Html(html) | Event::InlineHtml(html) => events.push(Event::Text(html)),
+            event => events.push(event),
+        }
+    }
+
+    let mut html = String::new();
+    html::push_html(&mut html, events.into_iter());
+    html
+}
+
+pub fn html(entries: &[ConversationEntry], session_id: &str) -> Result<String> {
+    if entries.is_empty() {
+        anyhow::bail!("Nothing to export yet: ask a question first");
+    }
+
+    let mut page = String::new();
+    page.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    page.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    page.push_str(&format!("<title>{}</title>\n", encode_text(&entries[0].query)));
+    page.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", assets::read(HTML_STYLE_ASSET).unwrap_or_default()));
+    page.push_str(&format!(
+        "<header>\n<h1>FastGPT conversation</h1>\n<p>Exported {} &middot; session {}</p>\n</header>\n",
+        Local::now().format("%Y-%m-%d %H:%M"),
+        encode_text(session_id)
+    ));
+
+    for (i, entry) in entries.iter().enumerate() {
+        page.push_str(&format!("<section class=\"exchange\" id=\"q{}\">\n", i + 1));
+        page.push_str(&format!("<div class=\"question\">Q{}: {}</div>\n", i + 1, encode_text(&entry.query)));
+        page.push_str(&format!("<div class=\"answer\">\n{}</div>\n", answer_html(entry)));
+
+        if !entry.references.is_empty() {
+            page.push_str("<ol class=\"references\">\n");
+            for reference in &entry.references {
+                page.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    encode_double_quoted_attribute(&reference.url),
+                    encode_text(&html_escape::decode_html_entities(&reference.title))
+                ));
+            }
+            page.push_str("</ol>\n");
+        }
+        page.push_str("</section>\n");
+    }
+
+    page.push_str("</body>\n</html>\n");
+    Ok(page)
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'static str,
+    content: &'a str,
+}
+
+pub fn openai(entries: &[ConversationEntry]) -> Result<String> {
+    if entries.is_empty() {
+        anyhow::bail!("Nothing to export yet: ask a question first");
+    }
+
+    let messages: Vec<ChatMessage> = entries
+        .iter()
+        .flat_map(|entry| [
+            ChatMessage { role: "user", content: &entry.query },
+            ChatMessage { role: "assistant", content: &entry.response },
+        ])
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&messages)? + "\n")
+}
+
+const GRAPH_LABEL_CHARS: usize = 60;
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn shorten(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() > GRAPH_LABEL_CHARS {
+        text.chars().take(GRAPH_LABEL_CHARS - 3).collect::<String>() + "..."
+    } else {
+        text
+    }
+}
+
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", dot_escape(text))
+}
+
+fn dot_label(text: &str) -> String {
+    dot_string(&shorten(text))
+}
+
+fn url_host(url: &str) -> &str {
+    url.split("://").nth(1).unwrap_or(url).split('/').next().unwrap_or(url)
+}
+
+pub fn graph(entries: &[ConversationEntry], file_contexts: &[FileContext], session_id: &str) -> Result<String> {
+    if entries.is_empty() {
+        anyhow::bail!("Nothing to export yet: ask a question first");
+    }
+
+    let mut dot = String::from("digraph session {\n");
+    dot.push_str(&format!("  label={};\n  labelloc=t;\n", dot_label(&format!("fastgpt session {}", session_id))));
+    dot.push_str("  node [fontname=\"Helvetica\", fontsize=10];\n  edge [fontname=\"Helvetica\", fontsize=9];\n\n");
+
+    for (i, entry) in entries.iter().enumerate() {
+        let fill = if entry.sources.is_empty() { "#e8f0fe" } else { "#fff4e5" };
+        dot.push_str(&format!("  q{} [shape=box, style=\"rounded,filled\", fillcolor=\"{}\", label={}];\n",
+            i + 1, fill, dot_label(&format!("Q{}: {}", i + 1, entry.query))));
+        if i > 0 {
+            dot.push_str(&format!("  q{} -> q{} [weight=10];\n", i, i + 1));
+        }
+        for source in &entry.sources {
+            dot.push_str(&format!("  q{} -> q{} [style=dashed, color=\"#f29900\", label=\"derived\"];\n", source, i + 1));
+        }
+    }
+
+    let mut files: Vec<PathBuf> = file_contexts.iter().map(|file| file.path.clone()).collect();
+    for path in entries.iter().flat_map(|entry| &entry.context_files) {
+        if !files.contains(path) {
+            files.push(path.clone());
+        }
+    }
+    if !files.is_empty() {
+        dot.push('\n');
+    }
+    for (n, path) in files.iter().enumerate() {
+        let style = match file_contexts.iter().find(|file| &file.path == path) {
+            Some(file) if file.enabled => "filled",
+            Some(_) => "\"filled,dashed\"",
+            None => "dashed",
+        };
+        dot.push_str(&format!("  f{} [shape=note, style={}, fillcolor=\"#e6f4ea\", label={}];\n", n + 1, style, dot_label(&path.display().to_string())));
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.context_files.contains(path) {
+                dot.push_str(&format!("  f{} -> q{} [color=\"#34a853\"];\n", n + 1, i + 1));
+            }
+        }
+    }
+
+    let mut urls: Vec<&Reference> = Vec::new();
+    for reference in entries.iter().flat_map(|entry| &entry.references) {
+        if !urls.iter().any(|seen| seen.url == reference.url) {
+            urls.push(reference);
+        }
+    }
+    if !urls.is_empty() {
+        dot.push('\n');
+    }
+    for (n, reference) in urls.iter().enumerate() {
+        let title = html_escape::decode_html_entities(&reference.title);
+        let url = dot_string(&reference.url);
+        dot.push_str(&format!("  r{} [shape=ellipse, style=filled, fillcolor=\"#f1f3f4\", label=\"{}\\n{}\", URL={}, tooltip={}];\n",
+            n + 1, dot_escape(&shorten(&title)), dot_escape(url_host(&reference.url)), url, url));
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.references.iter().any(|r| r.url == reference.url) {
+                dot.push_str(&format!("  q{} -> r{} [color=\"#9aa0a6\"];\n", i + 1, n + 1));
+            }
+        }
+    }
+
+    dot.push_str("}\n");
+    Ok(dot)
+}
@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::markdown::{self, CodeBlock};
+
+fn extension(language: &str) -> &str {
+    match language.to_lowercase().as_str() {
+        "rust" | "rs" => "rs",
+        "python" | "py" | "python3" => "py",
+        "javascript" | "js" | "node" => "js",
+        "typescript" | "ts" => "ts",
+        "tsx" => "tsx",
+        "jsx" => "jsx",
+        "bash" | "sh" | "shell" | "zsh" | "console" => "sh",
+        "powershell" | "ps1" | "pwsh" => "ps1",
+        "go" | "golang" => "go",
+        "java" => "java",
+        "kotlin" | "kt" => "kt",
+        "c" => "c",
+        "cpp" | "c++" | "cc" => "cpp",
+        "csharp" | "c#" | "cs" => "cs",
+        "ruby" | "rb" => "rb",
+        "php" => "php",
+        "swift" => "swift",
+        "html" => "html",
+        "css" => "css",
+        "json" => "json",
+        "yaml" | "yml" => "yml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "sql" => "sql",
+        "markdown" | "md" => "md",
+        "dockerfile" | "docker" => "Dockerfile",
+        "makefile" | "make" => "Makefile",
+        "lua" => "lua",
+        "haskell" | "hs" => "hs",
+        _ => "txt",
+    }
+}
+
+fn is_safe_relative(path: &Path) -> bool {
+    !path.as_os_str().is_empty() && path.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn inferred_filename(block: &CodeBlock) -> Option<PathBuf> {
+    let info_name = block.info
+        .split(|c: char| c.is_whitespace() || c == ':' || c == '=')
+        .map(|part| part.trim_matches('"'))
+        .skip(1)
+        .find(|part| part.contains('.') && !part.starts_with('.'));
+
+    let comment_regex = Regex::new(r"^\s*(?:#|//|--|;|/\*|<!--)\s*(?:file(?:name)?:\s*)?([\w./-]+\.\w+)\s*(?:\*/|-->)?\s*$").unwrap();
+    let comment_name = block.code
+        .lines()
+        .find(|line| !line.starts_with("#!"))
+        .and_then(|line| comment_regex.captures(line))
+        .map(|caps| caps[1].to_string());
+
+    info_name
+        .map(str::to_string)
+        .or(comment_name)
+        .map(PathBuf::from)
+        .filter(|path| is_safe_relative(path))
+}
+
+fn default_filename(index: usize, block: &CodeBlock) -> PathBuf {
+    match extension(&block.language) {
+        name @ ("Dockerfile" | "Makefile") if index == 1 => PathBuf::from(name),
+        name @ ("Dockerfile" | "Makefile") => PathBuf::from(format!("{}.{}", name, index)),
+        ext => PathBuf::from(format!("snippet-{}.{}", index, ext)),
+    }
+}
+
+fn unique_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    (2..)
+        .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(path)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .context(format!("Failed to make executable: {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+pub fn extract_code_blocks(text: &str, dir: &Path) -> Result<Vec<PathBuf>> {
+    let blocks = markdown::code_blocks(text);
+    if blocks.is_empty() {
+        anyhow::bail!("The last answer has no code blocks");
+    }
+
+    let mut written = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let name = inferred_filename(block).unwrap_or_else(|| default_filename(i + 1, block));
+        let path = unique_path(dir.join(name));
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create directory: {}", parent.display()))?;
+        }
+        fs::write(&path, &block.code)
+            .context(format!("Failed to write file: {}", path.display()))?;
+        if block.code.starts_with("#!") {
+            make_executable(&path)?;
+        }
+
+        written.push(path);
+    }
+
+    Ok(written)
+}
@@ -0,0 +1,82 @@
+use anyhow::{Context, Result};
+use regex::{NoExpand, Regex};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AnswerFilter {
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: String,
+    #[serde(default)]
+    pub regex: bool,
+}
+
+pub struct CompiledFilter {
+    regex: Regex,
+    replacement: String,
+    literal: bool,
+}
+
+pub fn compile(filters: &[AnswerFilter]) -> Result<Vec<CompiledFilter>> {
+    filters
+        .iter()
+        .map(|filter| {
+            let pattern = if filter.regex {
+                filter.pattern.clone()
+            } else {
+                regex::escape(&filter.pattern)
+            };
+            let regex = Regex::new(&pattern)
+                .context(format!("Invalid answer filter pattern: {}", filter.pattern))?;
+
+            Ok(CompiledFilter {
+                regex,
+                replacement: filter.replacement.clone(),
+                literal: !filter.regex,
+            })
+        })
+        .collect()
+}
+
+pub fn apply(filters: &[CompiledFilter], text: &str) -> String {
+    let mut output = text.to_string();
+
+    for filter in filters {
+        output = if filter.literal {
+            filter.regex.replace_all(&output, NoExpand(&filter.replacement)).to_string()
+        } else {
+            filter.regex.replace_all(&output, filter.replacement.as_str()).to_string()
+        };
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(pattern: &str, replacement: &str, regex: bool) -> AnswerFilter {
+        AnswerFilter { pattern: pattern.to_string(), replacement: replacement.to_string(), regex }
+    }
+
+    #[test]
+    fn replaces_literal_text_without_expanding_groups() {
+        let filters = compile(&[filter("a.b", "$0 ($1)", false)]).unwrap();
+        assert_eq!(apply(&filters, "a.b axb a.b"), "$0 ($1) axb $0 ($1)");
+    }
+
+    #[test]
+    fn expands_regex_groups() {
+        let filters = compile(&[filter(r"(\d+)°F", "${1} degrees", true)]).unwrap();
+        assert_eq!(apply(&filters, "It is 70°F"), "It is 70 degrees");
+    }
+
+    #[test]
+    fn applies_filters_in_order() {
+        let filters = compile(&[filter("colour", "color", false), filter("color", "hue", false)]).unwrap();
+        assert_eq!(apply(&filters, "colour"), "hue");
+        assert!(compile(&[filter("(", "", true)]).is_err());
+        assert!(compile(&[filter("(", "", false)]).is_ok());
+    }
+}
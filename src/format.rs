@@ -0,0 +1,32 @@
+use html_escape::decode_html_entities;
+use regex::Regex;
+
+use crate::FastGPTResponse;
+
+pub fn render_markdown(response: &FastGPTResponse, show_references: bool) -> String {
+    let decoded = decode_html_entities(&response.data.output).to_string();
+
+    if !show_references {
+        return remove_reference_numbers(&decoded);
+    }
+
+    let reference_regex = Regex::new(r"【(\d+)】").unwrap();
+    let mut markdown = reference_regex.replace_all(&decoded, "[^$1]").to_string();
+
+    if !response.data.references.is_empty() {
+        markdown.push('\n');
+        for (i, reference) in response.data.references.iter().enumerate() {
+            markdown.push_str(&format!("\n[^{}]: [{}]({})", i + 1, decode_html_entities(&reference.title), reference.url));
+            if !reference.snippet.is_empty() {
+                markdown.push_str(&format!(" — {}", decode_html_entities(&reference.snippet)));
+            }
+        }
+    }
+
+    markdown
+}
+
+pub fn remove_reference_numbers(text: &str) -> String {
+    let reference_regex = Regex::new(r"【\d+】").unwrap();
+    reference_regex.replace_all(text, "").to_string()
+}
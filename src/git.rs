@@ -0,0 +1,43 @@
+use anyhow::{Context, Result};
+use fastgpt::format::remove_reference_numbers;
+use html_escape::decode_html_entities;
+use std::process::Command;
+
+pub fn diff(staged: bool, reference: Option<&str>) -> Result<String> {
+    let mut command = Command::new("git");
+    command.args(["diff", "--no-color", "--no-ext-diff"]);
+    if staged {
+        command.arg("--staged");
+    }
+    if let Some(reference) = reference {
+        command.arg(reference);
+    }
+
+    let output = command.output().context("Failed to run git (is it installed?)")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git diff failed: {}", stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub fn changed_files(diff: &str) -> usize {
+    diff.lines().filter(|line| line.starts_with("diff --git ")).count()
+}
+
+pub const COMMIT_MESSAGE_QUERY: &str = "Write a git commit message for the staged changes in the attached diff, \
+    following the Conventional Commits format: a subject line `type(scope): summary` of at most 72 characters, \
+    where type is one of feat, fix, docs, style, refactor, perf, test, build, ci or chore and the scope is optional; \
+    then a blank line and a short body explaining what changed and why, wrapped at 72 characters. \
+    Reply with only the commit message, without code fences, quotes or commentary.";
+
+pub fn clean_commit_message(answer: &str) -> String {
+    let text = remove_reference_numbers(&decode_html_entities(answer));
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(str::trim_end)
+        .collect();
+    let message = lines.join("\n");
+    message.trim().trim_matches('"').trim().to_string()
+}
@@ -0,0 +1,252 @@
+use anyhow::Result;
+use eframe::egui;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::mpsc as tokio_mpsc;
+
+use crate::{export, output, AddFileOptions, ConversationEntry, Session};
+
+enum Request {
+    Ask(String),
+    AddFile(String),
+    RemoveFile(String),
+    Export(ExportKind, PathBuf),
+    Clear,
+}
+
+#[derive(Clone, Copy)]
+enum ExportKind {
+    Html,
+    OpenAi,
+    Markdown,
+}
+
+struct Update {
+    history: Vec<ConversationEntry>,
+    files: Vec<String>,
+    status: std::result::Result<String, String>,
+}
+
+async fn handle(session: &mut Session, request: Request) -> Result<String> {
+    match request {
+        Request::Ask(query) => {
+            session.ask_question(&query).await?;
+            Ok(String::new())
+        }
+        Request::AddFile(path) => {
+            session.add_file_context(&path, AddFileOptions::default())?;
+            Ok(format!("Added {}", path))
+        }
+        Request::RemoveFile(path) => {
+            session.remove_file_context(&path)?;
+            Ok(format!("Removed {}", path))
+        }
+        Request::Export(kind, path) => {
+            let content = match kind {
+                ExportKind::Html => export::html(&session.history, &session.id)?,
+                ExportKind::OpenAi => export::openai(&session.history)?,
+                ExportKind::Markdown => {
+                    session.save_response(&path, false)?;
+                    return Ok(format!("Saved the last answer to {}", path.display()));
+                }
+            };
+            std::fs::write(&path, content)?;
+            Ok(format!("Exported to {}", path.display()))
+        }
+        Request::Clear => {
+            session.history.clear();
+            session.last_query = None;
+            session.last_response = None;
+            Ok("Conversation cleared".to_string())
+        }
+    }
+}
+
+async fn serve(mut session: Session, mut requests: tokio_mpsc::UnboundedReceiver<Request>, updates: mpsc::Sender<Update>) {
+    while let Some(request) = requests.recv().await {
+        let status = handle(&mut session, request).await.map_err(|e| e.to_string());
+        let update = Update {
+            history: session.history.clone(),
+            files: session.file_contexts.iter().map(|file| file.path.display().to_string()).collect(),
+            status,
+        };
+        if updates.send(update).is_err() {
+            break;
+        }
+    }
+}
+
+struct App {
+    requests: tokio_mpsc::UnboundedSender<Request>,
+    updates: mpsc::Receiver<Update>,
+    session_id: String,
+    history: Vec<ConversationEntry>,
+    files: Vec<String>,
+    pending: usize,
+    status: Option<std::result::Result<String, String>>,
+    question: String,
+    file_path: String,
+    export_path: String,
+}
+
+impl App {
+    fn send(&mut self, request: Request) {
+        if self.requests.send(request).is_ok() {
+            self.pending += 1;
+        }
+    }
+
+    fn receive(&mut self) {
+        while let Ok(update) = self.updates.try_recv() {
+            self.pending = self.pending.saturating_sub(1);
+            self.history = update.history;
+            self.files = update.files;
+            self.status = match update.status {
+                Ok(message) if message.is_empty() => None,
+                status => Some(status),
+            };
+        }
+    }
+
+    fn side_panel(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Files");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.file_path);
+            if ui.button("Add").clicked() && !self.file_path.trim().is_empty() {
+                let path = self.file_path.trim().to_string();
+                self.file_path.clear();
+                self.send(Request::AddFile(path));
+            }
+        });
+        let mut removed = None;
+        for file in &self.files {
+            ui.horizontal(|ui| {
+                if ui.small_button("✖").clicked() {
+                    removed = Some(file.clone());
+                }
+                ui.label(file);
+            });
+        }
+        if let Some(file) = removed {
+            self.send(Request::RemoveFile(file));
+        }
+
+        ui.separator();
+        ui.heading("Export");
+        ui.text_edit_singleline(&mut self.export_path);
+        ui.horizontal(|ui| {
+            let path = PathBuf::from(self.export_path.trim());
+            let enabled = !self.export_path.trim().is_empty();
+            for (label, kind) in [("HTML", ExportKind::Html), ("OpenAI JSON", ExportKind::OpenAi), ("Last answer (Markdown)", ExportKind::Markdown)] {
+                if ui.add_enabled(enabled, egui::Button::new(label)).clicked() {
+                    self.send(Request::Export(kind, path.clone()));
+                }
+            }
+        });
+
+        ui.separator();
+        if ui.button("Clear conversation").clicked() {
+            self.send(Request::Clear);
+        }
+        ui.add_space(8.0);
+        ui.weak(format!("Session {}", self.session_id));
+    }
+
+    fn conversation(&mut self, ui: &mut egui::Ui) {
+        egui::ScrollArea::vertical().stick_to_bottom(true).auto_shrink(false).show(ui, |ui| {
+            if self.history.is_empty() {
+                ui.weak("Ask a question below to start.");
+            }
+            for (i, entry) in self.history.iter().enumerate() {
+                ui.label(egui::RichText::new(format!("Q{}: {}", i + 1, entry.query)).strong());
+                ui.label(output::remove_reference_numbers(&html_escape::decode_html_entities(&entry.response)));
+                if !entry.references.is_empty() {
+                    ui.collapsing(format!("References ({})", entry.references.len()), |ui| {
+                        for (n, reference) in entry.references.iter().enumerate() {
+                            ui.hyperlink_to(format!("{}. {}", n + 1, html_escape::decode_html_entities(&reference.title)), &reference.url);
+                        }
+                    });
+                }
+                ui.separator();
+            }
+        });
+    }
+
+    fn input(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::multiline(&mut self.question)
+                    .desired_rows(2)
+                    .desired_width(ui.available_width() - 70.0)
+                    .hint_text("Ask a question (Ctrl+Enter to send)"),
+            );
+            let shortcut = response.has_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter) && input.modifiers.command);
+            let send = ui.add_enabled(self.pending == 0, egui::Button::new("Send")).clicked();
+            if (send || shortcut) && self.pending == 0 && !self.question.trim().is_empty() {
+                let question = self.question.trim().to_string();
+                self.question.clear();
+                self.send(Request::Ask(question));
+            }
+        });
+        match &self.status {
+            _ if self.pending > 0 => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Working...");
+                });
+            }
+            Some(Ok(message)) => {
+                ui.label(message);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Error: {}", error));
+            }
+            None => {}
+        }
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.receive();
+
+        egui::SidePanel::right("side").resizable(true).show(ctx, |ui| self.side_panel(ui));
+        egui::TopBottomPanel::bottom("input").show(ctx, |ui| {
+            ui.add_space(6.0);
+            self.input(ui);
+            ui.add_space(6.0);
+        });
+        egui::CentralPanel::default().show(ctx, |ui| self.conversation(ui));
+
+        if self.pending > 0 {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+}
+
+pub fn run(session: Session) -> Result<()> {
+    let (request_sender, request_receiver) = tokio_mpsc::unbounded_channel();
+    let (update_sender, update_receiver) = mpsc::channel();
+
+    let app = App {
+        requests: request_sender,
+        updates: update_receiver,
+        session_id: session.id.clone(),
+        history: session.history.clone(),
+        files: Vec::new(),
+        pending: 0,
+        status: None,
+        question: String::new(),
+        file_path: String::new(),
+        export_path: "conversation.html".to_string(),
+    };
+    tokio::spawn(serve(session, request_receiver, update_sender));
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([960.0, 720.0]).with_title("Kagi FastGPT"),
+        ..Default::default()
+    };
+    eframe::run_native("Kagi FastGPT", options, Box::new(|_| Ok(Box::new(app))))
+        .map_err(|e| anyhow::anyhow!("Failed to start the GUI: {}", e))
+}
@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::ConversationEntry;
+
+pub struct Imported {
+    pub title: Option<String>,
+    pub entries: Vec<ConversationEntry>,
+}
+
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                Value::String(text) => Some(text.as_str()),
+                _ => part.get("text").and_then(Value::as_str),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Object(_) => content
+            .get("parts")
+            .or_else(|| content.get("text"))
+            .map(content_text)
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+fn pair_messages(messages: &[(String, String)]) -> Vec<ConversationEntry> {
+    let mut entries = Vec::new();
+    let mut pending: Option<String> = None;
+
+    for (role, text) in messages {
+        if text.trim().is_empty() {
+            continue;
+        }
+        match role.as_str() {
+            "user" => {
+                if let Some(query) = pending.take() {
+                    entries.push(ConversationEntry { query, ..Default::default() });
+                }
+                pending = Some(text.clone());
+            }
+            "assistant" => match pending.take() {
+                Some(query) => entries.push(ConversationEntry { query, response: text.clone(), ..Default::default() }),
+                None => {
+                    if let Some(last) = entries.last_mut() {
+                        last.response.push_str("\n\n");
+                        last.response.push_str(text);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    entries.retain(|entry| !entry.response.is_empty());
+    entries
+}
+
+fn chat_messages(messages: &[Value]) -> Vec<(String, String)> {
+    messages
+        .iter()
+        .filter_map(|message| {
+            let role = message.get("role")?.as_str()?.to_string();
+            Some((role, content_text(message.get("content")?)))
+        })
+        .collect()
+}
+
+fn chatgpt_messages(conversation: &Value) -> Vec<(String, String)> {
+    let Some(mapping) = conversation.get("mapping").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+
+    let mut messages = Vec::new();
+    let mut node_id = conversation.get("current_node").and_then(Value::as_str).map(str::to_string);
+    while let Some(node) = node_id.as_deref().and_then(|id| mapping.get(id)) {
+        if let Some(message) = node.get("message").filter(|message| !message.is_null()) {
+            let role = message.pointer("/author/role").and_then(Value::as_str).unwrap_or_default();
+            let text = message.get("content").map(content_text).unwrap_or_default();
+            messages.push((role.to_string(), text));
+        }
+        node_id = node.get("parent").and_then(Value::as_str).map(str::to_string);
+    }
+    messages.reverse();
+    messages
+}
+
+fn select_conversation<'a>(conversations: &'a [Value], title: Option<&str>) -> Result<&'a Value> {
+    let title_of = |conversation: &Value| conversation.get("title").and_then(Value::as_str).unwrap_or_default().to_string();
+
+    match title {
+        Some(wanted) => conversations
+            .iter()
+            .find(|conversation| title_of(conversation).to_lowercase().contains(&wanted.to_lowercase()))
+            .context(format!("No conversation with a title matching '{}' in the export", wanted)),
+        None => conversations
+            .iter()
+            .max_by(|a, b| {
+                let updated = |conversation: &Value| conversation.get("update_time").and_then(Value::as_f64).unwrap_or(0.0);
+                updated(a).total_cmp(&updated(b))
+            })
+            .context("The export contains no conversations"),
+    }
+}
+
+pub fn load(path: &Path, title: Option<&str>) -> Result<Imported> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read file: {}", path.display()))?;
+    let json: Value = serde_json::from_str(&content)
+        .context(format!("Failed to parse JSON: {}", path.display()))?;
+
+    let imported = match &json {
+        Value::Array(items) if items.iter().any(|item| item.get("mapping").is_some()) => {
+            let conversation = select_conversation(items, title)?;
+            Imported {
+                title: conversation.get("title").and_then(Value::as_str).map(str::to_string),
+                entries: pair_messages(&chatgpt_messages(conversation)),
+            }
+        }
+        Value::Array(items) => Imported { title: None, entries: pair_messages(&chat_messages(items)) },
+        Value::Object(object) if object.contains_key("mapping") => Imported {
+            title: json.get("title").and_then(Value::as_str).map(str::to_string),
+            entries: pair_messages(&chatgpt_messages(&json)),
+        },
+        Value::Object(object) if object.contains_key("messages") => Imported {
+            title: json.get("title").and_then(Value::as_str).map(str::to_string),
+            entries: pair_messages(&chat_messages(json["messages"].as_array().map(Vec::as_slice).unwrap_or_default())),
+        },
+        Value::Object(object) if object.contains_key("exchanges") => Imported {
+            title: json.get("id").and_then(Value::as_str).map(|id| format!("session {}", id)),
+            entries: json["exchanges"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|exchange| Some(ConversationEntry {
+                    query: exchange.get("query")?.as_str()?.to_string(),
+                    response: exchange.pointer("/response/data/output")?.as_str()?.to_string(),
+                    references: exchange
+                        .pointer("/response/data/references")
+                        .and_then(|references| serde_json::from_value(references.clone()).ok())
+                        .unwrap_or_default(),
+                    ..Default::default()
+                }))
+                .collect(),
+        },
+        _ => anyhow::bail!("Unrecognized conversation format in {} (expected chat messages, a ChatGPT export or a saved session)", path.display()),
+    };
+
+    if imported.entries.is_empty() {
+        anyhow::bail!("No question/answer pairs found in {}", path.display());
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pairs(entries: &[ConversationEntry]) -> Vec<(&str, &str)> {
+        entries.iter().map(|entry| (entry.query.as_str(), entry.response.as_str())).collect()
+    }
+
+    fn messages(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|(role, text)| (role.to_string(), text.to_string())).collect()
+    }
+
+    #[test]
+    fn pairs_questions_with_answers() {
+        let entries = pair_messages(&messages(&[
+            ("system", "be brief"),
+            ("user", "unanswered"),
+            ("user", "q1"),
+            ("assistant", "a1"),
+            ("assistant", "more"),
+            ("user", "  "),
+            ("user", "q2"),
+            ("assistant", "a2"),
+            ("user", "dangling"),
+        ]));
+        assert_eq!(pairs(&entries), [("q1", "a1\n\nmore"), ("q2", "a2")]);
+    }
+
+    #[test]
+    fn reads_text_from_content_parts() {
+        assert_eq!(content_text(&json!("plain")), "plain");
+        assert_eq!(content_text(&json!([{"type": "text", "text": "a"}, "b", {"type": "image"}])), "a\nb");
+        assert_eq!(content_text(&json!({"content_type": "text", "parts": ["x", "y"]})), "x\ny");
+        assert_eq!(content_text(&json!(null)), "");
+    }
+
+    #[test]
+    fn follows_the_chatgpt_branch_to_the_current_node() {
+        let conversation = json!({
+            "current_node": "c",
+            "mapping": {
+                "root": {"message": null, "parent": null},
+                "a": {"message": {"author": {"role": "user"}, "content": {"parts": ["q"]}}, "parent": "root"},
+                "old": {"message": {"author": {"role": "assistant"}, "content": {"parts": ["old answer"]}}, "parent": "a"},
+                "c": {"message": {"author": {"role": "assistant"}, "content": {"parts": ["new answer"]}}, "parent": "a"}
+            }
+        });
+        assert_eq!(pairs(&pair_messages(&chatgpt_messages(&conversation))), [("q", "new answer")]);
+    }
+
+    #[test]
+    fn selects_conversations_by_title_or_recency() {
+        let conversations = [
+            json!({"title": "Rust lifetimes", "update_time": 2.0}),
+            json!({"title": "Cooking", "update_time": 5.0}),
+        ];
+        assert_eq!(select_conversation(&conversations, Some("rust")).unwrap()["title"], "Rust lifetimes");
+        assert_eq!(select_conversation(&conversations, None).unwrap()["title"], "Cooking");
+        assert!(select_conversation(&conversations, Some("python")).is_err());
+        assert!(select_conversation(&[], None).is_err());
+    }
+}
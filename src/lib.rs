@@ -0,0 +1,10 @@
+pub mod api;
+pub mod error;
+pub mod format;
+pub mod prompt;
+pub mod session;
+
+pub use api::{CallOptions, Client, Data, Event, FastGPTRequest, FastGPTResponse, Hook, Meta, Reference};
+pub use error::{Error, Result};
+pub use session::{Context, Exchange, Session, SessionBuilder, Settings, Snapshot};
+pub use tokio_util::sync::CancellationToken;
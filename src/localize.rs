@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::markdown;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct LocalizeConfig {
+    pub units: Option<String>,
+    pub date_format: Option<String>,
+    pub decimal_separator: Option<String>,
+    pub thousands_separator: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum UnitSystem {
+    Metric,
+    Imperial,
+}
+
+enum Conversion {
+    Scale(f64),
+    FahrenheitToCelsius,
+    CelsiusToFahrenheit,
+}
+
+struct UnitRule {
+    units: &'static str,
+    conversion: Conversion,
+    target: &'static str,
+}
+
+pub struct Localizer {
+    unit_rules: Vec<UnitRule>,
+    unit_regex: Option<Regex>,
+    date_format: Option<String>,
+    decimal_separator: Option<String>,
+    thousands_separator: Option<String>,
+    us_date_regex: Regex,
+    long_date_regex: Regex,
+    number_regex: Regex,
+}
+
+const NUMBER: &str = r"(\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?)";
+
+fn unit_rule(units: &'static str, conversion: Conversion, target: &'static str) -> UnitRule {
+    UnitRule {
+        units,
+        conversion,
+        target,
+    }
+}
+
+fn unit_regex(rules: &[UnitRule]) -> Option<Regex> {
+    if rules.is_empty() {
+        return None;
+    }
+
+    let alternatives: Vec<String> = rules.iter().map(|rule| format!("({})", rule.units)).collect();
+    let pattern = format!(r"{}\s*(?:{})\b(\s*\()?", NUMBER, alternatives.join("|"));
+    Some(Regex::new(&pattern).unwrap())
+}
+
+fn unit_rules(system: UnitSystem) -> Vec<UnitRule> {
+    match system {
+        UnitSystem::Metric => vec![
+            unit_rule("miles per hour|mph", Conversion::Scale(1.609344), "km/h"),
+            unit_rule("miles|mile|mi", Conversion::Scale(1.609344), "km"),
+            unit_rule("feet|foot|ft", Conversion::Scale(0.3048), "m"),
+            unit_rule("inches|inch", Conversion::Scale(2.54), "cm"),
+            unit_rule("pounds|pound|lbs|lb", Conversion::Scale(0.453_592_37), "kg"),
+            unit_rule("ounces|ounce|oz", Conversion::Scale(28.349_523), "g"),
+            unit_rule("gallons|gallon|gal", Conversion::Scale(3.785_411_8), "L"),
+            unit_rule("°F|degrees Fahrenheit|Fahrenheit", Conversion::FahrenheitToCelsius, "°C"),
+        ],
+        UnitSystem::Imperial => vec![
+            unit_rule("kilometers per hour|kilometres per hour|km/h|kph", Conversion::Scale(0.621_371), "mph"),
+            unit_rule("kilometers|kilometres|kilometer|kilometre|km", Conversion::Scale(0.621_371), "miles"),
+            unit_rule("centimeters|centimetres|centimeter|centimetre|cm", Conversion::Scale(0.393_701), "in"),
+            unit_rule("meters|metres|meter|metre", Conversion::Scale(3.280_84), "ft"),
+            unit_rule("kilograms|kilogram|kg", Conversion::Scale(2.204_62), "lb"),
+            unit_rule("liters|litres|liter|litre", Conversion::Scale(0.264_172), "gal"),
+            unit_rule("°C|degrees Celsius|Celsius", Conversion::CelsiusToFahrenheit, "°F"),
+        ],
+    }
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    text.replace(',', "").parse().ok()
+}
+
+fn format_converted(value: f64) -> String {
+    let rounded = if value.abs() >= 100.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.1}", value)
+    };
+    rounded.trim_end_matches(".0").to_string()
+}
+
+impl Localizer {
+    pub fn new(config: &LocalizeConfig) -> Result<Self> {
+        let unit_rules = match config.units.as_deref() {
+            None => Vec::new(),
+            Some("metric") => unit_rules(UnitSystem::Metric),
+            Some("imperial") => unit_rules(UnitSystem::Imperial),
+            Some(other) => anyhow::bail!("Invalid localize.units '{}' (use \"metric\" or \"imperial\")", other),
+        };
+
+        if let Some(format) = &config.date_format {
+            chrono::format::StrftimeItems::new(format)
+                .parse()
+                .context(format!("Invalid localize.date_format: {}", format))?;
+        }
+
+        Ok(Self {
+            unit_regex: unit_regex(&unit_rules),
+            unit_rules,
+            date_format: config.date_format.clone(),
+            decimal_separator: config.decimal_separator.clone(),
+            thousands_separator: config.thousands_separator.clone(),
+            us_date_regex: Regex::new(r"\b(\d{1,2})/(\d{1,2})/(\d{4})\b").unwrap(),
+            long_date_regex: Regex::new(
+                r"\b(January|February|March|April|May|June|July|August|September|October|November|December) (\d{1,2}), (\d{4})\b",
+            )
+            .unwrap(),
+            number_regex: Regex::new(r"\d[\d.,]*\d|\d").unwrap(),
+        })
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut prose_start = 0;
+        for code in markdown::code_ranges(text) {
+            output.push_str(&self.localize_prose(&text[prose_start..code.start]));
+            output.push_str(&text[code.clone()]);
+            prose_start = code.end;
+        }
+        output.push_str(&self.localize_prose(&text[prose_start..]));
+        output
+    }
+
+    fn localize_prose(&self, text: &str) -> String {
+        let mut output = text.to_string();
+
+        if let Some(regex) = &self.unit_regex {
+            let paren_group = self.unit_rules.len() + 2;
+            output = regex.replace_all(&output, |caps: &Captures| {
+                let original = &caps[0];
+                let rule = (0..self.unit_rules.len())
+                    .find(|&i| caps.get(i + 2).is_some())
+                    .map(|i| &self.unit_rules[i]);
+                let (Some(rule), Some(value)) = (rule, parse_number(&caps[1])) else {
+                    return original.to_string();
+                };
+                if caps.get(paren_group).is_some() {
+                    return original.to_string();
+                }
+                let converted = match rule.conversion {
+                    Conversion::Scale(factor) => value * factor,
+                    Conversion::FahrenheitToCelsius => (value - 32.0) * 5.0 / 9.0,
+                    Conversion::CelsiusToFahrenheit => value * 9.0 / 5.0 + 32.0,
+                };
+                let separator = if rule.target.starts_with('°') { "" } else { " " };
+                format!("{} ({}{}{})", original, format_converted(converted), separator, rule.target)
+            }).to_string();
+        }
+
+        if let Some(format) = &self.date_format {
+            output = self.us_date_regex.replace_all(&output, |caps: &Captures| {
+                let date = format!("{}/{}/{}", &caps[1], &caps[2], &caps[3]);
+                match NaiveDate::parse_from_str(&date, "%m/%d/%Y") {
+                    Ok(date) => date.format(format).to_string(),
+                    Err(_) => caps[0].to_string(),
+                }
+            }).to_string();
+
+            output = self.long_date_regex.replace_all(&output, |caps: &Captures| {
+                match NaiveDate::parse_from_str(&caps[0], "%B %d, %Y") {
+                    Ok(date) => date.format(format).to_string(),
+                    Err(_) => caps[0].to_string(),
+                }
+            }).to_string();
+        }
+
+        if self.decimal_separator.is_some() || self.thousands_separator.is_some() {
+            output = self.number_regex.replace_all(&output, |caps: &Captures| {
+                self.localize_number(&caps[0])
+            }).to_string();
+        }
+
+        output
+    }
+
+    fn localize_number(&self, number: &str) -> String {
+        let (integer, fraction) = match number.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (number, None),
+        };
+        if fraction.is_some_and(|fraction| !fraction.chars().all(|c| c.is_ascii_digit())) {
+            return number.to_string();
+        }
+
+        let groups: Vec<&str> = integer.split(',').collect();
+        let well_formed = groups.len() == 1
+            || (groups[0].len() <= 3 && groups[1..].iter().all(|group| group.len() == 3));
+        if !well_formed || groups.iter().any(|group| group.is_empty()) {
+            return number.to_string();
+        }
+
+        let thousands = self.thousands_separator.as_deref().unwrap_or(",");
+        let decimal = self.decimal_separator.as_deref().unwrap_or(".");
+
+        let mut localized = groups.join(thousands);
+        if let Some(fraction) = fraction {
+            localized.push_str(decimal);
+            localized.push_str(fraction);
+        }
+        localized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn localizer() -> Localizer {
+        Localizer::new(&LocalizeConfig {
+            units: Some("metric".to_string()),
+            decimal_separator: Some(",".to_string()),
+            thousands_separator: Some(".".to_string()),
+            ..LocalizeConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn localizes_prose() {
+        assert_eq!(localizer().apply("It is 10 ft and costs 1,234.5 dollars."), "It is 10 ft (3 m) and costs 1.234,5 dollars.");
+    }
+
+    #[test]
+    fn leaves_code_untouched() {
+        let fenced = "```rust\nlet x = 0.5; // 10 ft\nlet n = 1,000;\n```\n";
+        let answer = format!("Use 0.5 here:\n\n{}\nThen call `scale(2.5, 10 ft)` for 2.5 ft.", fenced);
+        let localized = localizer().apply(&answer);
+        assert!(localized.starts_with("Use 0,5 here:\n\n"));
+        assert!(localized.contains(fenced));
+        assert!(localized.ends_with("Then call `scale(2.5, 10 ft)` for 2,5 ft (0,8 m)."));
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+pub const LOCK_FILE: &str = ".lock";
+
+pub struct StoreLock {
+    _file: File,
+}
+
+fn open(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .context(format!("Failed to open lock file: {}", path.display()))
+}
+
+pub fn exclusive(path: &Path) -> Result<StoreLock> {
+    let file = open(path)?;
+    file.lock()
+        .context(format!("Failed to lock {}", path.display()))?;
+    Ok(StoreLock { _file: file })
+}
+
+pub fn shared(path: &Path) -> Result<StoreLock> {
+    let file = open(path)?;
+    file.lock_shared()
+        .context(format!("Failed to lock {}", path.display()))?;
+    Ok(StoreLock { _file: file })
+}
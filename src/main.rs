@@ -1,27 +1,74 @@
+mod ab;
+mod archives;
+mod assets;
+mod backup;
+mod batch;
+mod cache;
+mod demo;
+mod documents;
+mod export;
+mod extract;
+mod filters;
+mod git;
+#[cfg(feature = "gui")]
+mod gui;
+mod import;
+mod localize;
+mod lock;
+mod markdown;
+mod minify;
+mod news;
+mod output;
+mod picker;
+mod repomap;
+mod report;
+mod research;
+mod retention;
+mod schedule;
+mod secrets;
+mod sessions;
+mod snippets;
+mod table;
+mod templates;
+mod theme;
+mod tokens;
+mod tutorial;
+mod usage;
+mod watch;
+mod webpage;
+
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use colored::*;
 use dirs::config_dir;
-use html_escape::decode_html_entities;
-use regex::Regex;
-use reqwest::Client;
+use fastgpt::{prompt, CallOptions, Client, Data, FastGPTRequest, FastGPTResponse, Meta, Reference};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 use rustyline::completion::{Completer, Pair};
 use rustyline::hint::{HistoryHinter, Hinter};
+use rustyline::history::DefaultHistory;
 use rustyline::highlight::Highlighter;
 use rustyline::validate::Validator;
 use rustyline::{Helper, Context as RustylineContext, Result as RustylineResult};
+use rustyline::config::{BellStyle, CompletionType, EditMode};
 use serde::{Deserialize, Serialize};
-use serde_json;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::{IsTerminal, Read};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use uuid::Uuid;
 
+use output::{format_markdown_text, print_context_window, print_formatted_response, OutputFormat};
+use theme::{Paint, Role};
+
 #[derive(Parser)]
 #[command(name = "fastgpt")]
 #[command(about = "Kagi FastGPT CLI client")]
 #[command(version = "0.2.2")]
+#[command(after_help = "A question whose first word is a subcommand name (usage, backup, news, ...) runs that subcommand. \
+Use `fastgpt ask <question>` or `fastgpt -- <question>` to always send the words as a question.")]
 struct Cli {
     #[arg(long, help = "Set API key (will be saved for future use)")]
     set_api_key: Option<String>,
@@ -35,94 +82,833 @@ struct Cli {
     #[arg(long, default_value = "true", help = "Whether to allow cached responses")]
     cache: bool,
 
-    #[arg(long, help = "Output raw JSON response")]
+    #[arg(long, conflicts_with = "output", help = "Output raw JSON response (same as --output json)")]
     json: bool,
 
+    #[arg(long, value_enum, help = "Output format: pretty (default), plain, markdown, json or jsonl")]
+    output: Option<OutputFormat>,
+
+    #[arg(long, help = "Disable colored output (also enabled by the NO_COLOR environment variable)")]
+    no_color: bool,
+
     #[arg(long, help = "Reset stored API key")]
     reset_api_key: bool,
 
     #[arg(long, help = "Enable or disable showing references (saves to config)")]
     references: Option<bool>,
 
-    #[arg(help = "Query to send to FastGPT")]
+    #[arg(long, help = "Bypass the local on-disk response cache")]
+    no_local_cache: bool,
+
+    #[arg(long, help = "Save this session's exchanges to disk, as if save_sessions = true were set in the config")]
+    save: bool,
+
+    #[arg(long, conflicts_with = "no_local_cache", help = "Never use the network; answer only from the local cache")]
+    offline: bool,
+
+    #[arg(long, conflicts_with = "offline", help = "Run against canned demo answers: no API key needed and no quota used")]
+    demo: bool,
+
+    #[arg(long, value_name = "TEXT", help = "Standing instructions sent with every question, overriding system_prompt in the config (\"\" to disable)")]
+    system: Option<String>,
+
+    #[arg(long, value_name = "SECS", help = "Give up on the API after this many seconds and fall back to a cached answer, or fail with a timeout error")]
+    max_wait: Option<f64>,
+
+    #[arg(long, help = "Ask for a tabular answer and print it as an aligned table (CSV when piped)")]
+    table: bool,
+
+    #[arg(long = "context", value_name = "PATH", help = "Attach a file, directory, archive or glob as context; - reads stdin in one-shot mode (repeatable)")]
+    contexts: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[arg(help = "Query to send to FastGPT (use `ask` or `--` if it starts with a subcommand name)")]
     query: Vec<String>,
 }
 
+#[derive(Subcommand)]
+enum Commands {
+    #[command(about = "Ask a single question, even one that starts with a subcommand name, e.g. `fastgpt ask usage of rust lifetimes`")]
+    Ask {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true, help = "Question to send to FastGPT")]
+        query: Vec<String>,
+    },
+
+    #[command(about = "Manage the local response cache")]
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
+    #[command(about = "Work with the assets (templates, themes) embedded in the binary")]
+    Assets {
+        #[command(subcommand)]
+        action: AssetsAction,
+    },
+
+    #[command(about = "Bundle config, sessions, snippets, schedules and custom templates into one archive")]
+    Backup {
+        #[arg(help = "Archive to write (.tar.gz)")]
+        file: PathBuf,
+
+        #[arg(long, help = "Also back up the API key stored in the config (left out by default)")]
+        include_key: bool,
+    },
+
+    #[command(about = "Restore config and data from an archive created by 'fastgpt backup'")]
+    Restore {
+        #[arg(help = "Archive to restore from")]
+        file: PathBuf,
+
+        #[arg(long, help = "Overwrite files that already exist")]
+        force: bool,
+    },
+
+    #[command(about = "Delete old sessions and cached responses according to the [retention] config")]
+    Gc {
+        #[arg(long, help = "Show what would be removed without deleting anything")]
+        dry_run: bool,
+
+        #[arg(long, help = "Remove sessions unused for longer than this (e.g. 90d), overriding the config")]
+        sessions: Option<String>,
+
+        #[arg(long, help = "Remove cached responses older than this (e.g. 30d), overriding the config")]
+        cache: Option<String>,
+
+        #[arg(long, help = "Cap the total size of sessions and cache (e.g. 200MB), removing the oldest first")]
+        max_disk: Option<String>,
+    },
+
+    #[command(about = "Re-run a saved session's queries to reproduce its answers")]
+    Replay {
+        #[arg(help = "ID of the saved session to replay")]
+        session_id: String,
+
+        #[arg(long, help = "Replay up to and including this exchange (default: all)")]
+        to: Option<usize>,
+    },
+
+    #[command(about = "Work with answer ratings recorded via /rate")]
+    Ratings {
+        #[command(subcommand)]
+        action: RatingsAction,
+    },
+
+    #[command(about = "Compare two prompt variants over a set of inputs")]
+    Ab {
+        #[arg(long, help = "Prompt file for variant A ({input} is replaced by each input, otherwise the input is appended)")]
+        prompt_a: PathBuf,
+
+        #[arg(long, help = "Prompt file for variant B")]
+        prompt_b: PathBuf,
+
+        #[arg(long, help = "File with one input per line")]
+        inputs: PathBuf,
+
+        #[arg(long, short, help = "Write the Markdown report to this file instead of stdout")]
+        report: Option<PathBuf>,
+    },
+
+    #[command(about = "Research a topic by following up on each answer, then write a report")]
+    Research {
+        #[arg(help = "Topic or opening question")]
+        topic: String,
+
+        #[arg(long, default_value_t = 3, help = "Maximum number of questions to ask before synthesizing")]
+        depth: usize,
+
+        #[arg(long, help = "Stop asking follow-ups after this long, e.g. 2m or 90s")]
+        time_limit: Option<String>,
+
+        #[arg(long, short, help = "Write the Markdown report to this file instead of stdout")]
+        report: Option<PathBuf>,
+    },
+
+    #[command(about = "Answer every query in a file (one per line) as JSON lines, resumable after an interruption")]
+    Batch {
+        #[arg(required_unless_present = "resume", conflicts_with = "resume", help = "File with one query per line")]
+        inputs: Option<PathBuf>,
+
+        #[arg(long, short, conflicts_with = "resume", help = "Write results to this JSONL file instead of stdout")]
+        output: Option<PathBuf>,
+
+        #[arg(long, conflicts_with = "resume", help = "Where to record progress (default: <inputs>.checkpoint.json)")]
+        checkpoint: Option<PathBuf>,
+
+        #[arg(long, value_name = "CHECKPOINT", help = "Continue an interrupted batch from its checkpoint file")]
+        resume: Option<PathBuf>,
+    },
+
+    #[command(about = "Ask a quick question from a built-in template, e.g. `fastgpt q weather Berlin`")]
+    Q {
+        #[arg(help = "Template name (omit to list templates)")]
+        template: Option<String>,
+
+        #[arg(help = "Template arguments; extra words go to the last parameter")]
+        args: Vec<String>,
+    },
+
+    #[command(about = "Write a conventional commit message for the staged changes")]
+    CommitMsg {
+        #[arg(help = "Commit message file to write to, as passed to a prepare-commit-msg hook (default: print it)")]
+        file: Option<PathBuf>,
+
+        #[arg(help = "Message source passed by the hook; existing messages (-m, merge, squash, amend) are left alone")]
+        source: Option<String>,
+    },
+
+    #[command(about = "List, add, edit or remove the templates used by `fastgpt q`")]
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
+
+    #[command(about = "Get a dated news digest about a topic, with references")]
+    News {
+        #[arg(help = "Topic to get news about")]
+        topic: String,
+
+        #[arg(long, default_value = "yesterday", help = "Only include news since: today, yesterday, last week, a duration like 3d, or YYYY-MM-DD")]
+        since: String,
+    },
+
+    #[command(about = "Continue a conversation exported from ChatGPT or another tool")]
+    Import {
+        #[arg(help = "JSON file: chat messages ([{role, content}]), a ChatGPT conversations.json export or a saved session")]
+        file: PathBuf,
+
+        #[arg(long, help = "Title (or part of it) of the conversation to import from a multi-conversation export (default: most recent)")]
+        conversation: Option<String>,
+    },
+
+    #[cfg(feature = "gui")]
+    #[command(about = "Open the graphical interface")]
+    Gui,
+
+    #[command(about = "Take a guided tour of the interactive session")]
+    Tutorial,
+
+    #[command(about = "Run queries on a cron schedule and report changes in their answers")]
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+
+    #[command(about = "List saved sessions")]
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsAction,
+    },
+
+    #[command(about = "Show recorded API usage with daily and monthly rollups")]
+    Usage {
+        #[arg(long, default_value_t = 7, help = "Number of most recent days to show")]
+        days: usize,
+
+        #[arg(long, default_value_t = 6, help = "Number of most recent months to show")]
+        months: usize,
+    },
+
+    #[command(about = "Write a usage report (queries, topics, tokens and cost) as Markdown or HTML")]
+    Report {
+        #[arg(long, help = "Cover the last 7 days (the default)")]
+        week: bool,
+
+        #[arg(long, conflicts_with = "week", help = "Cover the last N days instead of a week")]
+        days: Option<u64>,
+
+        #[arg(long, value_enum, default_value = "markdown", help = "Report format")]
+        format: ReportFormat,
+
+        #[arg(short, long, help = "Write the report to a file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScheduleAction {
+    #[command(about = "Schedule a query, e.g. \"0 8 * * 1-5 latest Rust release\"")]
+    Add {
+        #[arg(help = "Five cron fields (minute hour day month weekday) followed by the query")]
+        spec: String,
+    },
+    #[command(about = "List scheduled queries")]
+    List,
+    #[command(about = "Remove a scheduled query")]
+    Remove {
+        #[arg(help = "ID shown by `schedule list`")]
+        id: u32,
+    },
+    #[command(about = "Run due queries and print answers that changed (call this from cron or a systemd timer)")]
+    Run {
+        #[arg(long, help = "Run every scheduled query, due or not")]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionsAction {
+    #[command(about = "List saved sessions with their metadata")]
+    List {
+        #[arg(long = "where", value_name = "KEY=VALUE", help = "Only list sessions with this metadata (repeat to require several)")]
+        filters: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RatingsAction {
+    #[command(about = "Export all rated exchanges from saved sessions")]
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv, help = "Export format")]
+        format: ExportFormat,
+
+        #[arg(long, short, help = "Write to this file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Subcommand)]
+enum TemplateAction {
+    #[command(about = "List built-in and custom templates")]
+    List,
+
+    #[command(about = "Create a custom template")]
+    Add {
+        #[arg(help = "Template name, used as `fastgpt q <name>`")]
+        name: String,
+
+        #[arg(long, help = "Short description shown in the template list")]
+        description: Option<String>,
+
+        #[arg(long, help = "Question text with {{param}} or {{param|default}} placeholders")]
+        body: Option<String>,
+
+        #[arg(long, help = "Write the template in $EDITOR (the default when --description or --body is missing)")]
+        edit: bool,
+    },
+
+    #[command(about = "Edit a template in $EDITOR; built-in templates are copied to your config dir first")]
+    Edit {
+        #[arg(help = "Template name")]
+        name: String,
+    },
+
+    #[command(about = "Remove a custom template, or restore a customized built-in one")]
+    Remove {
+        #[arg(help = "Template name")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AssetsAction {
+    #[command(about = "Write the embedded assets to a directory for customization")]
+    Dump {
+        #[arg(help = "Directory to write to (default: the override directory in your config dir)")]
+        dir: Option<PathBuf>,
+
+        #[arg(long, help = "Overwrite files that already exist")]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    #[command(about = "Remove all locally cached responses")]
+    Clear,
+
+    #[command(about = "Show the number and total size of cached responses")]
+    Stats,
+
+    #[command(about = "Remove expired cached responses")]
+    Purge {
+        #[arg(long, help = "Remove entries older than this age instead of the configured TTL (e.g. 12h, 7d, 2w)")]
+        older_than: Option<String>,
+    },
+}
+
 #[derive(Serialize, Deserialize, Default)]
 struct Config {
     api_key: Option<String>,
     show_references: Option<bool>,
+    cache_ttl: Option<String>,
+    context_budget: Option<usize>,
+    enforce_context_budget: Option<bool>,
+    directory_depth: Option<usize>,
+    directory_max_files: Option<usize>,
+    context_extensions: Option<Vec<String>>,
+    large_file_bytes: Option<usize>,
+    large_file_strategy: Option<String>,
+    large_file_head_lines: Option<usize>,
+    large_file_tail_lines: Option<usize>,
+    notebook_outputs: Option<bool>,
+    live_contexts: Option<bool>,
+    watch_contexts: Option<bool>,
+    redact_secrets: Option<bool>,
+    minify_contexts: Option<bool>,
+    tokenizer: Option<String>,
+    tokenizer_command: Option<String>,
+    save_sessions: Option<bool>,
+    monthly_budget_tokens: Option<u64>,
+    monthly_budget_cost: Option<f64>,
+    budget_hard_stop: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    filters: Vec<filters::AnswerFilter>,
+    localize: Option<localize::LocalizeConfig>,
+    pager: Option<String>,
+    paging: Option<bool>,
+    confirm_before_send: Option<bool>,
+    system_prompt: Option<String>,
+    prompt: Option<String>,
+    editor_mode: Option<String>,
+    completion_type: Option<String>,
+    bell_style: Option<String>,
+    theme: Option<theme::ThemeConfig>,
+    retention: Option<retention::RetentionConfig>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    aliases: BTreeMap<String, String>,
 }
 
-#[derive(Serialize)]
-struct FastGPTRequest {
-    query: String,
-    cache: bool,
-    web_search: bool,
+#[derive(Deserialize, Default)]
+struct ProjectConfig {
+    persona: Option<String>,
 }
 
-#[derive(Deserialize, Serialize)]
-struct FastGPTResponse {
-    meta: Meta,
-    data: Data,
+#[derive(Clone, Copy)]
+struct EditorSettings {
+    edit_mode: EditMode,
+    completion_type: CompletionType,
+    bell_style: BellStyle,
 }
 
-#[derive(Deserialize, Serialize)]
-struct Meta {
-    id: String,
-    node: String,
-    ms: u64,
-    api_balance: Option<f64>,
+struct Persona {
+    source: PathBuf,
+    prompt: String,
 }
 
-#[derive(Deserialize, Serialize)]
-struct Data {
-    output: String,
-    references: Vec<Reference>,
-    tokens: u64,
+#[derive(Debug)]
+struct MaxWaitExceeded {
+    secs: f64,
 }
 
-#[derive(Deserialize, Serialize)]
-struct Reference {
-    title: String,
-    snippet: String,
-    url: String,
+impl std::fmt::Display for MaxWaitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FastGPT did not answer within {}s and no cached answer is available", self.secs)
+    }
 }
 
-#[derive(Clone)]
+impl std::error::Error for MaxWaitExceeded {}
+
+#[derive(Debug)]
+struct BudgetExhausted {
+    spent: String,
+}
+
+impl std::fmt::Display for BudgetExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Monthly budget exhausted ({}). Raise the limit or disable budget_hard_stop in your config.", self.spent)
+    }
+}
+
+impl std::error::Error for BudgetExhausted {}
+
+#[derive(Clone, Default)]
 struct ConversationEntry {
     query: String,
     response: String,
+    references: Vec<Reference>,
+    context_files: Vec<PathBuf>,
+    sources: Vec<usize>,
+}
+
+#[derive(Default)]
+struct AddFileOptions {
+    label: Option<String>,
+    recursive: bool,
+    depth: Option<usize>,
+    max_files: Option<usize>,
+    lossy: bool,
+    truncate: Option<TruncateStrategy>,
+    all_files: bool,
+    minify: Option<bool>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TruncateStrategy {
+    Head,
+    Tail,
+    Ends,
+    Full,
+}
+
+impl TruncateStrategy {
+    const NAMES: &'static [&'static str] = &["head", "tail", "ends", "full"];
+
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "head" => Ok(TruncateStrategy::Head),
+            "tail" => Ok(TruncateStrategy::Tail),
+            "ends" => Ok(TruncateStrategy::Ends),
+            "full" => Ok(TruncateStrategy::Full),
+            other => anyhow::bail!("Invalid truncation strategy '{}' (expected one of: {})", other, Self::NAMES.join(", ")),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Truncation {
+    strategy: TruncateStrategy,
+    head_lines: usize,
+    tail_lines: usize,
+    max_bytes: usize,
+}
+
+impl Truncation {
+    fn apply(&self, content: String) -> (String, usize) {
+        let (head, tail) = match self.strategy {
+            TruncateStrategy::Head => (self.head_lines, 0),
+            TruncateStrategy::Tail => (0, self.tail_lines),
+            TruncateStrategy::Ends => (self.head_lines, self.tail_lines),
+            TruncateStrategy::Full => return (content, 0),
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        if lines.len() > head + tail {
+            let elided = lines.len() - head - tail;
+            let mut kept = lines[..head].to_vec();
+            let marker = format!("[... {} lines omitted ...]", elided);
+            kept.push(&marker);
+            kept.extend_from_slice(&lines[lines.len() - tail..]);
+            let kept = kept.join("\n");
+            if kept.len() <= self.max_bytes {
+                return (kept, elided);
+            }
+        }
+        if content.len() <= self.max_bytes {
+            return (content, 0);
+        }
+        Self::cut_bytes(&content, head, tail, self.max_bytes)
+    }
+
+    fn cut_bytes(content: &str, head: usize, tail: usize, max_bytes: usize) -> (String, usize) {
+        let budget = max_bytes.saturating_sub(64);
+        let head_bytes = (budget * head).checked_div(head + tail).unwrap_or(budget / 2);
+        let mut start = head_bytes;
+        while !content.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = content.len() - (budget - head_bytes);
+        while !content.is_char_boundary(end) {
+            end += 1;
+        }
+        let elided = content[start..end].matches('\n').count() + 1;
+        (format!("{}\n[... {} bytes omitted ...]\n{}", &content[..start], end - start, &content[end..]), elided)
+    }
+}
+
+enum FileText {
+    Text(String),
+    NotUtf8(Vec<u8>),
+    Binary,
+}
+
+#[derive(Default)]
+struct SkippedFiles {
+    binary: usize,
+    not_utf8: usize,
+    oversized: usize,
+}
+
+impl SkippedFiles {
+    fn skip(&mut self, text: FileText, lossy: bool) -> Option<String> {
+        match text {
+            FileText::Text(content) => Some(content),
+            FileText::NotUtf8(bytes) if lossy => Some(String::from_utf8_lossy(&bytes).into_owned()),
+            FileText::NotUtf8(_) => {
+                self.not_utf8 += 1;
+                None
+            }
+            FileText::Binary => {
+                self.binary += 1;
+                None
+            }
+        }
+    }
+
+    fn report(&self) {
+        if self.binary > 0 {
+            eprintln!("{} Skipped {} binary file(s)", "Warning:".bright_yellow(), self.binary);
+        }
+        if self.not_utf8 > 0 {
+            eprintln!("{} Skipped {} file(s) that are not valid UTF-8; use --lossy to add them with invalid bytes replaced",
+                "Warning:".bright_yellow(), self.not_utf8);
+        }
+        if self.oversized > 0 {
+            eprintln!("{} Skipped {} file(s) larger than {}", "Warning:".bright_yellow(), self.oversized,
+                retention::format_size(archives::MAX_ENTRY_BYTES));
+        }
+    }
+}
+
+fn read_file_text(path: &Path, options: &documents::Options) -> Result<FileText> {
+    if documents::is_document(path) {
+        return documents::extract_text(path, options).map(FileText::Text);
+    }
+    let bytes = fs::read(path).context(format!("Failed to read file: {}", path.display()))?;
+    Ok(decode_text(bytes))
+}
+
+fn decode_text(bytes: Vec<u8>) -> FileText {
+    if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return FileText::Binary;
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => FileText::Text(text),
+        Err(e) => FileText::NotUtf8(e.into_bytes()),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ContextSource {
+    File,
+    Archive,
+    Url,
+    Clipboard,
+    Stdin,
+    Diff,
+    Command,
+    RepoMap,
 }
 
 #[derive(Clone)]
 struct FileContext {
     path: PathBuf,
+    source: ContextSource,
     content: String,
     size: usize,
+    label: Option<String>,
+    enabled: bool,
+    declined_change: Option<u64>,
+    truncation: Option<Truncation>,
+    elided_lines: usize,
+    minified: bool,
 }
 
 struct Session {
+    settings: fastgpt::Settings,
     id: String,
     history: Vec<ConversationEntry>,
     client: Client,
-    api_key: String,
-    cache: bool,
-    json_mode: bool,
+    output: OutputFormat,
     show_references: bool,
+    local_cache: bool,
+    cache_ttl: Option<u64>,
+    offline: bool,
+    max_wait: Option<Duration>,
+    demo: bool,
+    ephemeral: bool,
+    directory_depth: usize,
+    directory_max_files: usize,
+    context_extensions: Vec<String>,
+    large_file_bytes: usize,
+    truncation: Truncation,
+    documents: documents::Options,
+    live_contexts: bool,
+    watch_contexts: bool,
+    redact_secrets: bool,
+    minify_contexts: bool,
+    tokenizer: tokens::Tokenizer,
+    save_sessions: bool,
+    budget: Option<usage::Budget>,
+    filters: Vec<filters::CompiledFilter>,
+    localizer: Option<localize::Localizer>,
+    pager: String,
+    paging: bool,
+    prompt: String,
+    editor: EditorSettings,
+    confirm_before_send: bool,
+    persona: Option<Persona>,
+    aliases: BTreeMap<String, String>,
+    metadata: BTreeMap<String, String>,
     file_contexts: Vec<FileContext>,
+    usage: usage::Rollup,
+    cached_answers: u64,
+    last_window: Option<ContextWindow>,
+    last_query: Option<String>,
+    last_response: Option<FastGPTResponse>,
+    refresh_cache: bool,
+    clipboard: Option<arboard::Clipboard>,
+}
+
+#[derive(Default)]
+struct ContextWindow {
+    files: Vec<PathBuf>,
+    skipped_files: Vec<PathBuf>,
+    history: Vec<usize>,
+    history_total: usize,
+}
+
+struct SessionOptions {
+    settings: fastgpt::Settings,
+    output: OutputFormat,
+    show_references: bool,
+    local_cache: bool,
+    cache_ttl: Option<u64>,
+    offline: bool,
+    max_wait: Option<Duration>,
+    demo: bool,
+    ephemeral: bool,
+    directory_depth: usize,
+    directory_max_files: usize,
+    context_extensions: Vec<String>,
+    large_file_bytes: usize,
+    truncation: Truncation,
+    documents: documents::Options,
+    live_contexts: bool,
+    watch_contexts: bool,
+    redact_secrets: bool,
+    minify_contexts: bool,
+    tokenizer: tokens::Tokenizer,
+    save_sessions: bool,
+    budget: Option<usage::Budget>,
+    filters: Vec<filters::CompiledFilter>,
+    localizer: Option<localize::Localizer>,
+    pager: String,
+    paging: bool,
+    prompt: String,
+    editor: EditorSettings,
+    confirm_before_send: bool,
+    persona: Option<Persona>,
+    aliases: BTreeMap<String, String>,
+}
+
+const DEFAULT_CONTEXT_BUDGET: usize = fastgpt::session::DEFAULT_CONTEXT_BUDGET;
+const DEFAULT_DIRECTORY_DEPTH: usize = 5;
+const DEFAULT_DIRECTORY_MAX_FILES: usize = 100;
+const DEFAULT_CONTEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "rs", "py", "js", "ts", "html", "css", "json", "xml", "yml", "yaml", "toml", "sh", "bat",
+];
+const DEFAULT_LARGE_FILE_BYTES: usize = 100_000;
+const DEFAULT_HEAD_LINES: usize = 200;
+const DEFAULT_TAIL_LINES: usize = 50;
+const DEFAULT_PAGER: &str = "less -R";
+const DEFAULT_EDITOR: &str = "vi";
+const DEFAULT_PROMPT: &str = "❯ ";
+const READLINE_HISTORY_SIZE: usize = 1000;
+const PROJECT_CONFIG_FILE: &str = ".fastgpt.toml";
+const IGNORE_FILE: &str = ".fastgptignore";
+const API_KEY_ENV: &str = "FASTGPT_API_KEY";
+const CONTEXT_DIFF_LINES: usize = 12;
+const TIMEOUT_EXIT_CODE: i32 = 124;
+const DUPLICATE_SIMILARITY: f32 = 0.9;
+const BINARY_SNIFF_BYTES: usize = 8000;
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_COMMAND_OUTPUT_BYTES: usize = 1_000_000;
+
+const COMMANDS: &[&str] = &[
+    "/exit",
+    "/quit",
+    "/clear",
+    "/history",
+    "/help",
+    "/add-file ",
+    "/pick",
+    "/add-url ",
+    "/add-clipboard",
+    "/add-repo-map",
+    "/add-diff",
+    "/add-cmd ",
+    "/remove-file ",
+    "/list-files",
+    "/move-file ",
+    "/disable-file ",
+    "/enable-file ",
+    "/clear-files",
+    "/balance",
+    "/usage",
+    "/rate ",
+    "/meta ",
+    "/open ",
+    "/merge ",
+    "/copy-code ",
+    "/verify",
+    "/retry",
+    "/edit",
+    "/compose",
+    "/table ",
+    "/extract-code ",
+    "/save-response ",
+    "/pager ",
+    "/confirm ",
+    "/save ",
+    "/live ",
+    "/watch ",
+    "/snippets ",
+];
+
+const PATH_COMMANDS: &[&str] = &[
+    "/extract-code ",
+    "/save-response ",
+    "/add-file ",
+    "/add-repo-map ",
+    "/pick ",
+];
+
+const ATTACHED_PATH_COMMANDS: &[&str] = &[
+    "/remove-file ",
+    "/disable-file ",
+    "/enable-file ",
+];
+
+fn path_command_len(line: &str) -> Option<usize> {
+    PATH_COMMANDS
+        .iter()
+        .find(|cmd| line.starts_with(*cmd))
+        .map(|cmd| cmd.len())
+}
+
+fn attached_command_len(line: &str) -> Option<usize> {
+    ATTACHED_PATH_COMMANDS
+        .iter()
+        .find(|cmd| line.starts_with(*cmd))
+        .map(|cmd| cmd.len())
+}
+
+fn takes_path(line: &str) -> bool {
+    path_command_len(line).is_some() || attached_command_len(line).is_some()
 }
 
 struct FastGPTHelper {
     hinter: HistoryHinter,
+    aliases: BTreeMap<String, String>,
+    attached: Vec<String>,
 }
 
-impl Default for FastGPTHelper {
-    fn default() -> Self {
+impl FastGPTHelper {
+    fn new(aliases: BTreeMap<String, String>) -> Self {
         Self {
             hinter: HistoryHinter::new(),
+            aliases,
+            attached: Vec::new(),
         }
     }
+
+    fn command_names(&self) -> impl Iterator<Item = &str> {
+        COMMANDS.iter().map(|cmd| cmd.trim_end()).chain(self.aliases.keys().map(String::as_str))
+    }
 }
 
 impl Helper for FastGPTHelper {}
@@ -131,27 +917,20 @@ impl Hinter for FastGPTHelper {
     type Hint = String;
 
     fn hint(&self, line: &str, pos: usize, ctx: &RustylineContext<'_>) -> Option<String> {
-        if (line.starts_with("/add-file ") || line.starts_with("/remove-file ")) && pos == line.len() {
-            return self.hint_file_path(line);
+        if let Some(expanded) = expand_alias(&self.aliases, line).filter(|_| pos == line.len()) {
+            if takes_path(&expanded) {
+                return self.hint_path(&expanded);
+            }
+        }
+
+        if takes_path(line) && pos == line.len() {
+            return self.hint_path(line);
         }
         
         if line.starts_with('/') && pos == line.len() {
-            let commands = vec![
-                "/exit",
-                "/quit", 
-                "/clear",
-                "/history",
-                "/help",
-                "/add-file",
-                "/remove-file",
-                "/list-files",
-                "/clear-files",
-                "/balance",
-            ];
-
             let input = &line[1..];
             
-            for cmd in commands {
+            for cmd in self.command_names() {
                 if cmd[1..].starts_with(input) && cmd.len() > line.len() {
                     return Some(cmd[line.len()..].to_string());
                 }
@@ -179,25 +958,20 @@ impl Completer for FastGPTHelper {
             return Ok((0, vec![]));
         }
 
-        if line.starts_with("/add-file ") || line.starts_with("/remove-file ") {
-            return self.complete_file_path(line, pos);
+        if let Some(expanded) = expand_alias(&self.aliases, &line[..pos]) {
+            if takes_path(&expanded) {
+                let shift = expanded.len() - pos;
+                let (start, matches) = self.complete_path(&expanded, expanded.len())?;
+                return Ok((start.saturating_sub(shift), matches));
+            }
         }
 
-        let commands = vec![
-            "/exit",
-            "/quit", 
-            "/clear",
-            "/history",
-            "/help",
-            "/add-file ",
-            "/remove-file ",
-            "/list-files",
-            "/clear-files",
-            "/balance",
-        ];
+        if takes_path(line) {
+            return self.complete_path(line, pos);
+        }
 
         let input = &line[1..pos];
-        let matches: Vec<Pair> = commands
+        let mut matches: Vec<Pair> = COMMANDS
             .iter()
             .filter(|cmd| cmd[1..].starts_with(input))
             .map(|cmd| Pair {
@@ -205,20 +979,53 @@ impl Completer for FastGPTHelper {
                 replacement: cmd.to_string(),
             })
             .collect();
+        matches.extend(self.aliases.iter()
+            .filter(|(alias, _)| alias[1..].starts_with(input))
+            .map(|(alias, expansion)| Pair {
+                display: format!("{} ({})", alias, expansion),
+                replacement: format!("{} ", alias),
+            }));
 
         Ok((0, matches))
     }
 }
 
 impl FastGPTHelper {
-    fn hint_file_path(&self, line: &str) -> Option<String> {
-        let cmd_start = if line.starts_with("/add-file ") {
-            "/add-file ".len()
-        } else if line.starts_with("/remove-file ") {
-            "/remove-file ".len()
-        } else {
-            return None;
+    fn hint_path(&self, line: &str) -> Option<String> {
+        match attached_command_len(line) {
+            Some(cmd_start) => {
+                let partial = &line[cmd_start..];
+                self.attached
+                    .iter()
+                    .find(|path| path.starts_with(partial) && path.len() > partial.len())
+                    .map(|path| path[partial.len()..].to_string())
+            }
+            None => self.hint_file_path(line),
+        }
+    }
+
+    fn complete_path(&self, line: &str, pos: usize) -> RustylineResult<(usize, Vec<Pair>)> {
+        let Some(cmd_start) = attached_command_len(line) else {
+            return self.complete_file_path(line, pos);
         };
+        if pos < cmd_start {
+            return Ok((0, vec![]));
+        }
+
+        let partial = &line[cmd_start..pos];
+        let matches = self.attached
+            .iter()
+            .filter(|path| path.starts_with(partial))
+            .map(|path| Pair {
+                display: path.clone(),
+                replacement: path.clone(),
+            })
+            .collect();
+        Ok((cmd_start, matches))
+    }
+
+    fn hint_file_path(&self, line: &str) -> Option<String> {
+        let cmd_start = path_command_len(line)?;
 
         let file_part = &line[cmd_start..];
         let (dir_path, partial_name) = if let Some(last_slash) = file_part.rfind('/') {
@@ -247,12 +1054,9 @@ impl FastGPTHelper {
     }
 
     fn complete_file_path(&self, line: &str, pos: usize) -> RustylineResult<(usize, Vec<Pair>)> {
-        let cmd_start = if line.starts_with("/add-file ") {
-            "/add-file ".len()
-        } else if line.starts_with("/remove-file ") {
-            "/remove-file ".len()
-        } else {
-            return Ok((0, vec![]));
+        let cmd_start = match path_command_len(line) {
+            Some(len) => len,
+            None => return Ok((0, vec![])),
         };
 
         if pos < cmd_start {
@@ -287,6 +1091,8 @@ impl FastGPTHelper {
 
                     let replacement = if entry.path().is_dir() {
                         format!("{}/", full_path)
+                    } else if line.starts_with("/add-") || line.starts_with("/save-response ") {
+                        shell_words::quote(&full_path).into_owned()
                     } else {
                         full_path
                     };
@@ -305,139 +1111,454 @@ impl FastGPTHelper {
 }
 
 impl Session {
-    fn new(api_key: String, cache: bool, json_mode: bool, show_references: bool) -> Self {
+    fn new(api_key: String, options: SessionOptions) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
             history: Vec::new(),
-            client: Client::new(),
-            api_key,
-            cache,
-            json_mode,
-            show_references,
+            client: Client::new(api_key),
+            settings: options.settings,
+            output: options.output,
+            show_references: options.show_references,
+            local_cache: options.local_cache,
+            cache_ttl: options.cache_ttl,
+            offline: options.offline,
+            max_wait: options.max_wait,
+            demo: options.demo,
+            ephemeral: options.ephemeral,
+            directory_depth: options.directory_depth,
+            directory_max_files: options.directory_max_files,
+            context_extensions: options.context_extensions,
+            large_file_bytes: options.large_file_bytes,
+            truncation: options.truncation,
+            documents: options.documents,
+            live_contexts: options.live_contexts,
+            watch_contexts: options.watch_contexts,
+            redact_secrets: options.redact_secrets,
+            minify_contexts: options.minify_contexts,
+            tokenizer: options.tokenizer,
+            save_sessions: options.save_sessions,
+            budget: options.budget,
+            filters: options.filters,
+            localizer: options.localizer,
+            pager: options.pager,
+            paging: options.paging,
+            prompt: options.prompt,
+            editor: options.editor,
+            confirm_before_send: options.confirm_before_send,
+            persona: options.persona,
+            aliases: options.aliases,
+            metadata: BTreeMap::new(),
             file_contexts: Vec::new(),
+            usage: usage::Rollup::default(),
+            cached_answers: 0,
+            last_window: None,
+            last_query: None,
+            last_response: None,
+            refresh_cache: false,
+            clipboard: None,
         }
     }
 
-    fn build_contextual_query(&self, current_query: &str) -> String {
-        let mut context = String::new();
+    fn build_contextual_query(&self, current_query: &str) -> (String, ContextWindow) {
+        let instructions: Vec<&str> = self.settings.system_prompt.iter()
+            .map(String::as_str)
+            .chain(self.persona.iter().map(|persona| persona.prompt.as_str()))
+            .collect();
         
-        if !self.file_contexts.is_empty() {
-            context.push_str("File contexts:\n");
-            for file_ctx in &self.file_contexts {
-                context.push_str(&format!("\n--- File: {} ---\n", file_ctx.path.display()));
-                context.push_str(&file_ctx.content);
-                context.push_str("\n--- End of file ---\n\n");
-            }
-        }
+        let enabled: Vec<&FileContext> = self.file_contexts.iter().filter(|f| f.enabled).collect();
+        let names: Vec<String> = enabled.iter().map(|f| f.path.display().to_string()).collect();
+        let contents: Vec<Cow<str>> = enabled.iter()
+            .map(|f| match self.redact_secrets {
+                true => Cow::Owned(secrets::redact(&f.content).0),
+                false => Cow::Borrowed(f.content.as_str()),
+            })
+            .collect();
+        let files: Vec<prompt::File> = enabled.iter().enumerate()
+            .map(|(i, f)| prompt::File { name: &names[i], label: f.label.as_deref(), content: &contents[i] })
+            .collect();
+        let history: Vec<prompt::Turn> = self.history.iter()
+            .map(|entry| prompt::Turn { query: &entry.query, answer: &entry.response })
+            .collect();
         
-        if !self.history.is_empty() {
-            context.push_str("Previous conversation context:\n");
-            for (i, entry) in self.history.iter().take(5).enumerate() {
-                context.push_str(&format!("Q{}: {}\nA{}: {}\n\n", i + 1, entry.query, i + 1, entry.response));
-            }
-        }
+        let count = |text: &str| self.tokenizer.count(text);
+        let budget = prompt::Budget { tokens: self.settings.context_budget, count: &count };
+        let budget = self.settings.enforce_context_budget.then_some(&budget);
+        let (context, built) = prompt::build(current_query, &instructions, &files, &history, budget);
         
-        context.push_str(&format!("Current question: {}", current_query));
-        context
+        let window = ContextWindow {
+            files: built.files.iter().map(|&i| enabled[i].path.clone()).collect(),
+            skipped_files: built.skipped_files.iter().map(|&i| enabled[i].path.clone()).collect(),
+            history: built.history,
+            history_total: built.history_total,
+        };
+        (context, window)
     }
 
     async fn ask_question(&mut self, query: &str) -> Result<FastGPTResponse> {
-        let contextual_query = self.build_contextual_query(query);
-        
-        let request_body = FastGPTRequest {
-            query: contextual_query,
-            cache: self.cache,
-            web_search: true,
+        if self.live_contexts {
+            self.refresh_live_contexts();
+        }
+        let (contextual_query, window) = self.build_contextual_query(query);
+        self.last_window = Some(window);
+
+        let fastgpt_response = self.send_query(&contextual_query).await?;
+        self.record_exchange(query, &contextual_query, &fastgpt_response, Vec::new());
+        self.last_query = Some(query.to_string());
+
+        Ok(fastgpt_response)
+    }
+
+    async fn retry_last_question(&mut self, no_cache: bool) -> Result<(String, FastGPTResponse)> {
+        if self.offline {
+            anyhow::bail!("/retry needs a new answer from the API and is not available offline");
+        }
+        let query = self.last_query.clone().context("Nothing to retry yet: ask a question first")?;
+
+        let cache = if no_cache { std::mem::replace(&mut self.settings.cache, false) } else { self.settings.cache };
+        let refresh_cache = std::mem::replace(&mut self.refresh_cache, true);
+        let result = self.replace_last_question(&query).await;
+        self.settings.cache = cache;
+        self.refresh_cache = refresh_cache;
+
+        result.map(|response| (query, response))
+    }
+
+    async fn replace_last_question(&mut self, query: &str) -> Result<FastGPTResponse> {
+        let replaced = match (self.history.last(), &self.last_query) {
+            (Some(entry), Some(last_query)) if &entry.query == last_query => self.history.pop(),
+            _ => None,
         };
 
-        let response = self.client
-            .post("https://kagi.com/api/v0/fastgpt")
-            .header("Authorization", format!("Bot {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .context("Failed to send request to FastGPT API")?;
+        let result = self.ask_question(query).await;
+        if result.is_err() {
+            self.history.extend(replaced);
+        }
+        result
+    }
+
+    async fn merge_answers(&mut self, numbers: &[usize]) -> Result<(String, FastGPTResponse)> {
+        if numbers.len() < 2 {
+            anyhow::bail!("Usage: /merge <n> <m> ... (at least two answers, numbered as in /history)");
+        }
+
+        let mut prompt = String::from(
+            "Combine the following answers into one coherent document. Remove duplicated information, \
+             reconcile any contradictions (say which claim is better supported), and keep the structure clear.\n\n",
+        );
+        for &number in numbers {
+            let entry = number
+                .checked_sub(1)
+                .and_then(|i| self.history.get(i))
+                .context(format!("No answer {} in history (there are {})", number, self.history.len()))?;
+            prompt.push_str(&format!("--- Answer {} (question: {}) ---\n{}\n\n", number, entry.query, entry.response));
+        }
+
+        let label = numbers.iter().map(|n| format!("Q{}", n)).collect::<Vec<_>>().join(", ");
+        let query = format!("Merge of {}", label);
+        self.last_window = None;
 
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("API request failed with status {}: {}", status, error_text);
+        let fastgpt_response = self.send_query(&prompt).await?;
+        self.record_exchange(&query, &prompt, &fastgpt_response, numbers.to_vec());
+
+        Ok((query, fastgpt_response))
+    }
+
+    async fn verify_last_answer(&mut self) -> Result<(String, String, FastGPTResponse)> {
+        if self.offline {
+            anyhow::bail!("/verify needs a fresh web search and is not available offline");
         }
+        let number = self.history.len();
+        let entry = self.history.last().context("No answer to verify yet: ask a question first")?;
+        let original = entry.response.clone();
+
+        let prompt = format!(
+            "Fact-check the following answer to the question \"{}\" against current web sources. \
+             Reply with the full answer text, unchanged except for claims that are wrong, outdated or unsupported, \
+             which you should correct.\n\nAnswer:\n{}",
+            entry.query, entry.response
+        );
 
-        let fastgpt_response: FastGPTResponse = response
-            .json()
-            .await
-            .context("Failed to parse response from FastGPT API")?;
+        let cache = std::mem::replace(&mut self.settings.cache, false);
+        let result = self.send_query(&prompt).await;
+        self.settings.cache = cache;
+        let response = result?;
 
+        let query = format!("Verification of Q{}", number);
+        self.last_window = None;
+        self.record_exchange(&query, &prompt, &response, vec![number]);
+        Ok((query, original, response))
+    }
+
+    fn record_exchange(&mut self, query: &str, prompt: &str, response: &FastGPTResponse, sources: Vec<usize>) {
         self.history.push(ConversationEntry {
             query: query.to_string(),
-            response: fastgpt_response.data.output.clone(),
+            response: response.data.output.clone(),
+            references: response.data.references.clone(),
+            context_files: self.last_window.as_ref().map(|window| window.files.clone()).unwrap_or_default(),
+            sources,
         });
+        self.last_response = Some(response.clone());
 
-        Ok(fastgpt_response)
+        if self.save_sessions {
+            if let Err(e) = sessions::append_exchange(&self.id, query, prompt, response) {
+                eprintln!("{} {}", "Warning:".bright_yellow().bold(), e);
+            }
+        }
     }
 
-    async fn check_balance(&self) -> Result<f64> {
+    async fn send_query(&mut self, contextual_query: &str) -> Result<FastGPTResponse> {
+        let mut response = self.fetch_response(contextual_query).await?;
+        if !self.filters.is_empty() {
+            response.data.output = filters::apply(&self.filters, &response.data.output);
+        }
+        if let Some(localizer) = &self.localizer {
+            response.data.output = localizer.apply(&response.data.output);
+        }
+        Ok(response)
+    }
+
+    async fn fetch_response(&mut self, contextual_query: &str) -> Result<FastGPTResponse> {
+        if self.demo {
+            let response = demo::response(contextual_query);
+            self.usage.add_query(response.data.tokens, response.meta.ms);
+            return Ok(response);
+        }
+
+        let cache_key = cache::cache_key(contextual_query, true);
+
+        if self.offline || (self.local_cache && self.settings.cache && !self.refresh_cache) {
+            let ttl = if self.offline { None } else { self.cache_ttl };
+            if let Some(cached_response) = cache::lookup(&cache_key, ttl) {
+                self.cached_answers += 1;
+                self.client.emit(&fastgpt::Event::CacheHit { query: contextual_query.to_string() });
+                return Ok(cached_response);
+            }
+        }
+
+        if self.offline {
+            anyhow::bail!("No cached answer for this question (offline mode)");
+        }
+
+        if let Some(budget) = &self.budget {
+            if budget.hard_stop {
+                let spent = usage::month_to_date()?;
+                if budget.used_fraction(&spent) >= 1.0 {
+                    return Err(BudgetExhausted { spent: budget.describe(&spent) }.into());
+                }
+            }
+        }
+        
         let request_body = FastGPTRequest {
-            query: "ping".to_string(),
-            cache: true,
-            web_search: false,
+            query: contextual_query.to_string(),
+            cache: self.settings.cache,
+            web_search: self.settings.web_search,
         };
 
-        let response = self.client
-            .post("https://kagi.com/api/v0/fastgpt")
-            .header("Authorization", format!("Bot {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .context("Failed to send balance check request to FastGPT API")?;
+        let call = CallOptions { timeout: self.max_wait, ..Default::default() };
+        let fastgpt_response = match (self.client.query_with(&request_body, &call).await, self.max_wait) {
+            (Ok(response), _) => response,
+            (Err(fastgpt::Error::Timeout { .. }), Some(max_wait)) => return self.max_wait_fallback(&cache_key, max_wait),
+            (Err(e), _) => return Err(e.into()),
+        };
 
-        let response_text = response.text().await.unwrap_or_default();
-        
-        if let Ok(fastgpt_response) = serde_json::from_str::<FastGPTResponse>(&response_text) {
-            if let Some(balance) = fastgpt_response.meta.api_balance {
-                return Ok(balance);
+        self.usage.add_query(fastgpt_response.data.tokens, fastgpt_response.meta.ms);
+        if !self.ephemeral {
+            if let Err(e) = usage::record(&self.id, fastgpt_response.data.tokens, fastgpt_response.meta.ms) {
+                eprintln!("{} {}", "Warning:".bright_yellow().bold(), e);
             }
         }
-        
-        if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&response_text) {
-            if let Some(meta) = json_value.get("meta") {
-                if let Some(balance) = meta.get("api_balance") {
-                    if let Some(balance_f64) = balance.as_f64() {
-                        return Ok(balance_f64);
-                    }
+
+        if let Some(budget) = &self.budget {
+            if let Ok(spent) = usage::month_to_date() {
+                let used = budget.used_fraction(&spent);
+                if used >= usage::BUDGET_WARNING_THRESHOLD {
+                    eprintln!("{} {:.0}% of your monthly budget used ({})",
+                        "Budget warning:".on_yellow().black().bold(), used * 100.0, budget.describe(&spent));
                 }
             }
         }
 
-        anyhow::bail!("API balance not available in response: {}", response_text)
-    }
+        if self.local_cache {
+            if let Err(e) = cache::store(&cache_key, &request_body.query, &fastgpt_response) {
+                eprintln!("{} {}", "Warning:".bright_yellow().bold(), e);
+            }
+        }
+
+        Ok(fastgpt_response)
+    }
+
+    fn max_wait_fallback(&mut self, cache_key: &str, max_wait: Duration) -> Result<FastGPTResponse> {
+        let cached = if self.local_cache { cache::lookup(cache_key, None) } else { None };
+        let Some(response) = cached else {
+            return Err(MaxWaitExceeded { secs: max_wait.as_secs_f64() }.into());
+        };
+
+        self.cached_answers += 1;
+        eprintln!("{} FastGPT did not answer within {}s; using the answer cached at {}.",
+            "Warning:".bright_yellow().bold(), max_wait.as_secs_f64(),
+            response.cached_at.map(cache::format_timestamp).unwrap_or_default());
+        Ok(response)
+    }
+
+    async fn check_balance(&self) -> Result<f64> {
+        if self.demo {
+            return Ok(demo::DEMO_BALANCE);
+        }
+        if self.offline {
+            anyhow::bail!("Balance is not available in offline mode");
+        }
+
+        Ok(self.client.balance().await?)
+    }
+
+    fn open_reference(&self, number: usize) -> Result<()> {
+        let references = self.last_response
+            .as_ref()
+            .map(|response| response.data.references.as_slice())
+            .unwrap_or_default();
+        if references.is_empty() {
+            anyhow::bail!("The last answer has no references");
+        }
+        let reference = number
+            .checked_sub(1)
+            .and_then(|i| references.get(i))
+            .context(format!("No reference {} (the last answer has {})", number, references.len()))?;
+
+        webbrowser::open(&reference.url)
+            .context(format!("Failed to open {}", reference.url))?;
+        println!("{} {}", "Opened:".bright_green(), reference.url.blue().underline());
+        Ok(())
+    }
+
+    fn save_response(&self, path: &Path, append: bool) -> Result<()> {
+        let (Some(entry), Some(response)) = (self.history.last(), &self.last_response) else {
+            anyhow::bail!("No answer to save yet: ask a question first");
+        };
+        let content = format!("## {}\n\n{}\n", entry.query, output::render_markdown(response, self.show_references));
+
+        if append && path.exists() {
+            let mut file = fs::OpenOptions::new()
+                .append(true)
+                .open(path)
+                .context(format!("Failed to open file: {}", path.display()))?;
+            std::io::Write::write_all(&mut file, format!("\n{}", content).as_bytes())
+                .context(format!("Failed to write file: {}", path.display()))?;
+        } else {
+            fs::write(path, content)
+                .context(format!("Failed to write file: {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn clipboard(&mut self) -> Result<&mut arboard::Clipboard> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(arboard::Clipboard::new().context("Failed to access the clipboard")?);
+        }
+        self.clipboard.as_mut().context("Failed to access the clipboard")
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        self.clipboard()?.set_text(text).context("Failed to copy to the clipboard")
+    }
+
+    fn add_clipboard_context(&mut self, name: Option<&str>, options: AddFileOptions) -> Result<FileContext> {
+        let text = self.clipboard()?.get_text().context("The clipboard has no text")?;
+        if text.trim().is_empty() {
+            anyhow::bail!("The clipboard is empty");
+        }
+
+        let path = match name {
+            Some(name) => PathBuf::from(name),
+            None => (1..)
+                .map(|n| PathBuf::from(if n == 1 { "clipboard".to_string() } else { format!("clipboard-{}", n) }))
+                .find(|path| !path.exists() && !self.file_contexts.iter().any(|f| &f.path == path))
+                .unwrap_or_default(),
+        };
+        self.add_text_context(path, ContextSource::Clipboard, text, options)
+    }
+
+    fn add_text_context(&mut self, path: PathBuf, source: ContextSource, text: String, options: AddFileOptions) -> Result<FileContext> {
+        if self.file_contexts.iter().any(|f| f.path == path) {
+            anyhow::bail!("Context already added: {}", path.display());
+        }
+        if text.trim().is_empty() {
+            anyhow::bail!("{} is empty", path.display());
+        }
+        let file_context = self.file_context(path, source, text, options.label, options.truncate, options.minify);
+        self.file_contexts.push(file_context.clone());
+        Ok(file_context)
+    }
+
+    fn copy_code_block(&mut self, number: usize) -> Result<()> {
+        let entry = self.history.last().context("No answer yet: ask a question first")?;
+        let mut blocks = markdown::code_blocks(&entry.response);
+        if blocks.is_empty() {
+            anyhow::bail!("The last answer has no code blocks");
+        }
+        let total = blocks.len();
+        let block = number
+            .checked_sub(1)
+            .filter(|&i| i < total)
+            .map(|i| blocks.swap_remove(i))
+            .context(format!("No code block {} (the last answer has {})", number, total))?;
+
+        self.copy_to_clipboard(&block.code)?;
+
+        let language = if block.language.is_empty() { "code" } else { block.language.as_str() };
+        println!("{} block {} of {} ({}, {} lines):", "Copied".bright_green(), number.to_string().bright_cyan(), total, language, block.code.lines().count());
+        println!("{}", block.code.trim_end().dimmed());
+        Ok(())
+    }
+
+    fn render_prompt(&self) -> String {
+        self.prompt
+            .replace("{n}", &(self.history.len() + 1).to_string())
+            .replace("{tokens}", &self.usage.tokens.to_string())
+            .replace("{files}", &self.file_contexts.iter().filter(|file| file.enabled).count().to_string())
+    }
 
     fn clear_history(&mut self) {
         self.history.clear();
+        self.last_query = None;
+        self.last_response = None;
+        self.print_banner();
+        println!("{}", "Conversation history cleared and screen reset.".bright_yellow());
+    }
+
+    fn print_banner(&self) {
+        if !std::io::stdout().is_terminal() {
+            return;
+        }
+        
         print!("\x1B[2J\x1B[3J\x1B[H");
         std::io::Write::flush(&mut std::io::stdout()).unwrap();
-        println!("{}", "=".repeat(80).bright_blue());
-        println!("{}", "Kagi FastGPT CLI".bright_green().bold());
+        println!("{}", "=".repeat(80).paint(Role::Banner));
+        println!("{}", "Kagi FastGPT CLI".paint(Role::Banner).bold());
         println!("{} {}", "Session ID:".dimmed(), self.id.bright_cyan());
-        println!("{}", "=".repeat(80).bright_blue());
+        if self.offline {
+            println!("{}", "Offline mode: answering from the local cache only".bright_yellow());
+        }
+        if self.demo {
+            println!("{}", "Demo mode: canned answers, no API key or quota used".bright_yellow());
+        }
+        if self.ephemeral {
+            println!("{}", "No writable data directory: history is kept in memory only".bright_yellow());
+        }
+        if let Some(system_prompt) = &self.settings.system_prompt {
+            let preview: String = system_prompt.chars().take(60).collect();
+            let ellipsis = if system_prompt.chars().count() > 60 { "..." } else { "" };
+            println!("{} {}{}", "System prompt:".dimmed(), preview.bright_cyan(), ellipsis.bright_cyan());
+        }
+        if let Some(persona) = &self.persona {
+            println!("{} {}", "Persona:".dimmed(), persona.source.display().to_string().bright_cyan());
+        }
+        println!("{}", "=".repeat(80).paint(Role::Banner));
         println!();
         println!("{}", "Commands:".bright_yellow().bold());
-        println!("  {} - Exit the session", "/exit or /quit".bright_cyan());
-        println!("  {} - Clear conversation history and screen", "/clear".bright_cyan());
-        println!("  {} - Show conversation history", "/history".bright_cyan());
-        println!("  {} - Add file(s) or directory to context", "/add-file <path>".bright_cyan());
-        println!("  {} - Remove file from context", "/remove-file <path>".bright_cyan());
-        println!("  {} - List all files in context", "/list-files".bright_cyan());
-        println!("  {} - Clear all file contexts", "/clear-files".bright_cyan());
-        println!("  {} - Check API balance", "/balance".bright_cyan());
-        println!("  {} - Show this help", "/help".bright_cyan());
+        print_commands(&self.aliases);
         println!();
         println!("{} Just start typing your question!", "Tip:".bright_magenta().bold());
         println!();
-        println!("{}", "Conversation history cleared and screen reset.".bright_yellow());
     }
 
     fn show_history(&self) {
@@ -446,8 +1567,8 @@ impl Session {
             return;
         }
 
-        println!("{}", "Conversation History:".bright_blue().bold());
-        println!("{}", "=".repeat(50).bright_blue());
+        println!("{}", "Conversation History:".paint(Role::Banner).bold());
+        println!("{}", "=".repeat(50).paint(Role::Banner));
         
         for (i, entry) in self.history.iter().enumerate() {
             println!("{}. {}: {}", 
@@ -463,7 +1584,54 @@ impl Session {
         }
     }
 
-    fn add_file_context(&mut self, file_path: &str) -> Result<()> {
+    fn active_pager(&self) -> Option<&str> {
+        if self.paging {
+            Some(&self.pager)
+        } else {
+            None
+        }
+    }
+
+    fn show_usage(&self) {
+        println!();
+        println!("{}", "Session usage:".paint(Role::Banner).bold());
+        println!("  {} {}", "Queries:".dimmed(), (self.usage.queries + self.cached_answers).to_string().bright_cyan());
+        println!("  {} {}", "API calls:".dimmed(), self.usage.queries.to_string().bright_cyan());
+        println!("  {} {}", "Cached answers:".dimmed(), self.cached_answers.to_string().bright_cyan());
+        println!("  {} {}", "Tokens:".dimmed(), self.usage.tokens.to_string().bright_magenta());
+        println!("  {} {}", "API time:".dimmed(), format!("{}ms", self.usage.total_ms).bright_magenta());
+        println!("  {} {}", "Estimated cost:".dimmed(), format!("${:.3}", self.usage.estimated_cost()).bright_green());
+    }
+
+    fn print_summary(&self) {
+        if self.history.is_empty() && self.file_contexts.is_empty() {
+            return;
+        }
+        
+        println!();
+        println!("{}", "Session summary:".paint(Role::Banner).bold());
+        println!("  {} {}", "Questions:".dimmed(), self.history.len().to_string().bright_cyan());
+        println!("  {} {}", "Tokens:".dimmed(), self.usage.tokens.to_string().bright_magenta());
+        println!("  {} {}", "API time:".dimmed(), format!("{}ms", self.usage.total_ms).bright_magenta());
+        
+        if !self.file_contexts.is_empty() {
+            println!("  {}", "Files in context:".dimmed());
+            for file_ctx in &self.file_contexts {
+                println!("    {}", file_ctx.path.display().to_string().bright_cyan());
+            }
+        }
+        
+        if self.save_sessions {
+            if let Ok(path) = sessions::session_path(&self.id) {
+                if path.exists() {
+                    println!("  {} {}", "Transcript:".dimmed(), path.display().to_string().bright_green());
+                }
+            }
+        }
+        println!();
+    }
+
+    fn add_file_context(&mut self, file_path: &str, options: AddFileOptions) -> Result<usize> {
         let path = Path::new(file_path);
         
         if !path.exists() {
@@ -471,66 +1639,420 @@ impl Session {
         }
         
         if path.is_dir() {
-            return self.add_directory_context(path);
+            return self.add_directory_context(path, options);
+        }
+        if let Some(kind) = archives::kind_of(path) {
+            return self.add_archive_context(path, kind, options);
         }
         
-        let content = fs::read_to_string(path)
-            .context(format!("Failed to read file: {}", file_path))?;
-        
-        
-        let size = content.len();
-        let file_context = FileContext {
-            path: path.to_path_buf(),
-            content,
-            size,
+        let content = match read_file_text(path, &self.documents)? {
+            FileText::Text(content) => content,
+            FileText::NotUtf8(bytes) if options.lossy => String::from_utf8_lossy(&bytes).into_owned(),
+            FileText::NotUtf8(_) => anyhow::bail!("{} is not valid UTF-8; add it with --lossy to replace the invalid bytes", file_path),
+            FileText::Binary => anyhow::bail!("{} looks like a binary file (it contains NUL bytes); only text files can be added", file_path),
         };
         
         if self.file_contexts.iter().any(|f| f.path == path) {
             anyhow::bail!("File already added: {}", file_path);
         }
         
+        let file_context = self.file_context(path.to_path_buf(), ContextSource::File, content, options.label, options.truncate, options.minify);
         self.file_contexts.push(file_context);
-        Ok(())
+        Ok(1)
+    }
+
+    fn file_context(&self, path: PathBuf, source: ContextSource, content: String, label: Option<String>, truncate: Option<TruncateStrategy>, minify: Option<bool>) -> FileContext {
+        let size = content.len();
+        let minified = minify.unwrap_or(self.minify_contexts) && minify::is_supported(&path);
+        let content = match minified {
+            true => minify::minify(&path, &content).unwrap_or(content),
+            false => content,
+        };
+        let truncation = match truncate {
+            Some(strategy) => Some(Truncation { strategy, ..self.truncation }),
+            None if size > self.large_file_bytes => Some(self.truncation),
+            None => None,
+        }.filter(|truncation| truncation.strategy != TruncateStrategy::Full);
+
+        let (content, elided_lines) = match truncation {
+            Some(truncation) => truncation.apply(content),
+            None => (content, 0),
+        };
+        if elided_lines > 0 {
+            eprintln!("{} {} is {}; {} omitted (use --truncate full to add all of it)",
+                "Note:".bright_yellow(), path.display(), retention::format_size(size as u64),
+                if elided_lines == 1 { "part of 1 line".to_string() } else { format!("{} lines", elided_lines) });
+        }
+        self.report_secrets(&path, &content);
+
+        FileContext {
+            path,
+            source,
+            content,
+            size,
+            label,
+            enabled: true,
+            declined_change: None,
+            truncation,
+            elided_lines,
+            minified,
+        }
     }
     
-    fn add_directory_context(&mut self, dir_path: &Path) -> Result<()> {
-        let entries = fs::read_dir(dir_path)
-            .context(format!("Failed to read directory: {}", dir_path.display()))?;
+    fn report_secrets(&self, path: &Path, content: &str) {
+        let (_, findings) = secrets::redact(content);
+        if findings.is_empty() {
+            return;
+        }
+        let found: Vec<String> = findings.iter().map(|finding| format!("{} on line {}", finding.kind, finding.line)).collect();
+        let action = if self.redact_secrets {
+            "they will be redacted before sending"
+        } else {
+            "they will be sent as is because redact_secrets is off"
+        };
+        eprintln!("{} {} looks like it contains secrets ({}); {}",
+            "Warning:".bright_yellow(), path.display(), found.join(", "), action);
+    }
+
+    fn duplicate_of_last_answer(&self) -> Option<(usize, f32)> {
+        let (last, earlier) = self.history.split_last()?;
+        let normalize = |text: &str| output::remove_reference_numbers(&html_escape::decode_html_entities(text)).trim().to_string();
+        let answer = normalize(&last.response);
+
+        earlier
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.response.len().abs_diff(last.response.len()) * 5 <= last.response.len())
+            .map(|(i, entry)| (i + 1, similar::TextDiff::from_words(normalize(&entry.response).as_str(), answer.as_str()).ratio()))
+            .filter(|(_, similarity)| *similarity >= DUPLICATE_SIMILARITY)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    fn current_content(&self, file: &FileContext) -> Option<(String, usize)> {
+        if file.source != ContextSource::File {
+            return None;
+        }
+        let current = match read_file_text(&file.path, &self.documents).ok()? {
+            FileText::Text(text) => text,
+            FileText::NotUtf8(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            FileText::Binary => return None,
+        };
+        let current = match file.minified {
+            true => minify::minify(&file.path, &current).unwrap_or(current),
+            false => current,
+        };
+        Some(match file.truncation {
+            Some(truncation) => truncation.apply(current),
+            None => (current, 0),
+        })
+    }
+
+    fn changed_files(&self) -> Vec<(usize, String, usize)> {
+        self.file_contexts
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| file.enabled)
+            .filter_map(|(i, file)| {
+                let (current, elided) = self.current_content(file)?;
+                let changed = current != file.content && file.declined_change != Some(content_hash(&current));
+                changed.then_some((i, current, elided))
+            })
+            .collect()
+    }
+
+    fn refresh_live_contexts(&mut self) {
+        for i in 0..self.file_contexts.len() {
+            let file = &self.file_contexts[i];
+            if !file.enabled {
+                continue;
+            }
+            let Some((current, elided)) = self.current_content(file) else {
+                continue;
+            };
+            if current == file.content {
+                continue;
+            }
+            self.report_secrets(&file.path, &current);
+
+            let file = &mut self.file_contexts[i];
+            let size = fs::metadata(&file.path).map_or(current.len(), |metadata| metadata.len() as usize);
+            eprintln!("{} {} {}", "Refreshed:".bright_green(), file.path.display().to_string().bright_cyan(),
+                format!("({} -> {} bytes)", file.size, size).dimmed());
+            file.size = size;
+            file.content = current;
+            file.elided_lines = elided;
+            file.declined_change = None;
+        }
+    }
+
+    fn add_archive_context(&mut self, archive: &Path, kind: archives::Kind, options: AddFileOptions) -> Result<usize> {
+        let max_files = options.max_files.unwrap_or(self.directory_max_files);
+        let any_extension = options.all_files || self.context_extensions.iter().any(|ext| ext == "*");
+        let mut skipped = SkippedFiles::default();
+        let mut files = Vec::new();
+        let mut stopped = false;
+
+        archives::visit(archive, kind, |entry, reader| {
+            let path = archive.join(&entry.name);
+            if !(any_extension || self.has_context_extension(&entry.name)) || self.file_contexts.iter().any(|f| f.path == path) {
+                return Ok(true);
+            }
+            if files.len() == max_files {
+                stopped = true;
+                return Ok(false);
+            }
+
+            let mut bytes = Vec::new();
+            reader.take(archives::MAX_ENTRY_BYTES + 1).read_to_end(&mut bytes)
+                .context(format!("Failed to read {} from the archive", entry.name.display()))?;
+            if entry.size > archives::MAX_ENTRY_BYTES || bytes.len() as u64 > archives::MAX_ENTRY_BYTES {
+                skipped.oversized += 1;
+            } else if let Some(content) = skipped.skip(decode_text(bytes), options.lossy) {
+                files.push((path, content));
+            }
+            Ok(true)
+        })?;
+
+        if stopped {
+            eprintln!("{} Stopped after {} files; use --max-files to add more", "Warning:".bright_yellow(), max_files);
+        }
+        skipped.report();
+        if files.is_empty() {
+            anyhow::bail!("No supported text files found in {} (add --all-files to include every extension)", archive.display());
+        }
+
+        let added_count = files.len();
+        for (path, content) in files {
+            let file_context = self.file_context(path, ContextSource::Archive, content, options.label.clone(), options.truncate, options.minify);
+            self.file_contexts.push(file_context);
+        }
+        Ok(added_count)
+    }
+
+    fn add_glob_context(&mut self, pattern: &str, options: AddFileOptions) -> Result<(usize, usize)> {
+        let max_files = options.max_files.unwrap_or(self.directory_max_files);
+        let match_options = glob::MatchOptions { require_literal_leading_dot: true, ..Default::default() };
+        let paths = glob::glob_with(pattern, match_options).context(format!("Invalid glob pattern: {}", pattern))?;
+
+        let mut matched = 0;
+        let mut added_count = 0;
+        let mut skipped = SkippedFiles::default();
+        for path in paths.filter_map(Result::ok).filter(|path| path.is_file()) {
+            matched += 1;
+            if self.file_contexts.iter().any(|f| f.path == path) {
+                continue;
+            }
+            let Some(content) = read_file_text(&path, &self.documents).ok().and_then(|text| skipped.skip(text, options.lossy)) else {
+                continue;
+            };
+            if added_count == max_files {
+                eprintln!("{} Stopped after {} files; use --max-files to add more", "Warning:".bright_yellow(), max_files);
+                break;
+            }
+            let file_context = self.file_context(path, ContextSource::File, content, options.label.clone(), options.truncate, options.minify);
+            self.file_contexts.push(file_context);
+            added_count += 1;
+        }
+        skipped.report();
+
+        if matched == 0 {
+            anyhow::bail!("No files match {}", pattern);
+        }
+
+        Ok((matched, added_count))
+    }
+
+    async fn add_url_context(&mut self, url: &str, options: AddFileOptions) -> Result<FileContext> {
+        let path = PathBuf::from(url);
+        if self.file_contexts.iter().any(|f| f.path == path) {
+            anyhow::bail!("URL already added: {}", url);
+        }
+
+        let page = webpage::fetch(self.client.http(), url).await?;
+        let label = options.label.or(page.title);
+        let file_context = self.file_context(path, ContextSource::Url, page.text, label, options.truncate, options.minify);
+        self.file_contexts.push(file_context.clone());
+        Ok(file_context)
+    }
+
+    fn has_context_extension(&self, path: &Path) -> bool {
+        let Some(extension) = path.extension() else {
+            return false;
+        };
+        let ext = extension.to_string_lossy().to_lowercase();
+        self.context_extensions.contains(&ext)
+    }
+
+    fn add_directory_context(&mut self, dir_path: &Path, options: AddFileOptions) -> Result<usize> {
+        let depth = match (options.depth, options.recursive) {
+            (Some(depth), _) => depth.max(1),
+            (None, true) => self.directory_depth,
+            (None, false) => 1,
+        };
+        let max_files = options.max_files.unwrap_or(self.directory_max_files);
+        let label = options.label;
+        let mut skipped = SkippedFiles::default();
+        let any_extension = options.all_files || self.context_extensions.iter().any(|ext| ext == "*");
+        let entries = ignore::WalkBuilder::new(dir_path)
+            .max_depth(Some(depth))
+            .standard_filters(false)
+            .parents(true)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .filter_entry(|entry| entry.depth() == 0 || !(entry.file_type().is_some_and(|kind| kind.is_dir()) && entry.file_name().to_string_lossy().starts_with('.')))
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
         
         let mut added_count = 0;
         for entry in entries {
             let dir_entry = entry.context("Failed to read directory entry")?;
-            let path = dir_entry.path();
+            let path = dir_entry.path().to_path_buf();
             
-            if path.is_file() {
-                if let Some(extension) = path.extension() {
-                    let ext = extension.to_string_lossy().to_lowercase();
-                    if matches!(ext.as_str(), "txt" | "md" | "rs" | "py" | "js" | "ts" | "html" | "css" | "json" | "xml" | "yml" | "yaml" | "toml" | "sh" | "bat") {
-                        if let Ok(content) = fs::read_to_string(&path) {
-                            let size = content.len();
-                            let file_context = FileContext {
-                                path: path.clone(),
-                                content,
-                                size,
-                            };
-                            
-                            if !self.file_contexts.iter().any(|f| f.path == path) {
-                                self.file_contexts.push(file_context);
-                                added_count += 1;
-                            }
+            if path.is_file() && (any_extension || self.has_context_extension(&path)) {
+                if let Some(content) = read_file_text(&path, &self.documents).ok().and_then(|text| skipped.skip(text, options.lossy)) {
+                    if !self.file_contexts.iter().any(|f| f.path == path) {
+                        if added_count == max_files {
+                            eprintln!("{} Stopped after {} files; use --max-files to add more", "Warning:".bright_yellow(), max_files);
+                            break;
                         }
+                        let file_context = self.file_context(path, ContextSource::File, content, label.clone(), options.truncate, options.minify);
+                        self.file_contexts.push(file_context);
+                        added_count += 1;
                     }
                 }
             }
         }
+        skipped.report();
         
         if added_count == 0 {
-            anyhow::bail!("No supported text files found in directory");
+            anyhow::bail!("No supported text files found in directory (add --all-files to include every extension)");
         }
         
-        Ok(())
+        Ok(added_count)
     }
     
+    fn add_command_context(&mut self, command: &str) -> Result<(FileContext, bool, Option<std::process::ExitStatus>)> {
+        let command = command.trim();
+        if command.is_empty() {
+            anyhow::bail!("Usage: /add-cmd <command>");
+        }
+        let output = run_shell_command(command)?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut text = format!("$ {}\n", command);
+        text.push_str(stdout.trim_end());
+        if !stderr.trim().is_empty() {
+            text.push_str(&format!("\n[stderr]\n{}", stderr.trim_end()));
+        }
+        if stdout.trim().is_empty() && stderr.trim().is_empty() {
+            text.push_str("(no output)");
+        }
+        if output.truncated {
+            text.push_str(&format!("\n[output truncated after {}]", retention::format_size(MAX_COMMAND_OUTPUT_BYTES as u64)));
+            eprintln!("{} The command printed more than {}; the rest of its output was dropped",
+                "Note:".bright_yellow(), retention::format_size(MAX_COMMAND_OUTPUT_BYTES as u64));
+        }
+        match output.status {
+            Some(status) => text.push_str(&format!("\n[{}]", status)),
+            None => text.push_str(&format!("\n[killed after {}s]", COMMAND_TIMEOUT.as_secs())),
+        }
+
+        let file = self.file_context(PathBuf::from(format!("$ {}", command)), ContextSource::Command, text, None, None, Some(false));
+        let replaced = match self.file_contexts.iter_mut().find(|existing| existing.path == file.path) {
+            Some(existing) => {
+                *existing = file.clone();
+                true
+            }
+            None => {
+                self.file_contexts.push(file.clone());
+                false
+            }
+        };
+        Ok((file, replaced, output.status))
+    }
+
+    fn add_diff_context(&mut self, args: &str) -> Result<(FileContext, bool)> {
+        let mut staged = false;
+        let mut reference = None;
+        for arg in args.split_whitespace() {
+            match arg {
+                "--staged" | "--cached" => staged = true,
+                _ if arg.starts_with('-') => anyhow::bail!("Unknown /add-diff option: {}", arg),
+                _ if reference.is_some() => anyhow::bail!("/add-diff takes at most one ref"),
+                _ => reference = Some(arg),
+            }
+        }
+
+        let diff = git::diff(staged, reference)?;
+        if diff.trim().is_empty() {
+            match (staged, reference) {
+                (true, _) => anyhow::bail!("No staged changes"),
+                (false, Some(reference)) => anyhow::bail!("No changes against {}", reference),
+                (false, None) => anyhow::bail!("No unstaged changes (use /add-diff --staged for staged ones)"),
+            }
+        }
+
+        let name = ["git-diff"]
+            .into_iter()
+            .chain(staged.then_some("--staged"))
+            .chain(reference)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let file = self.file_context(PathBuf::from(name), ContextSource::Diff, diff, None, None, Some(false));
+        match self.file_contexts.iter_mut().find(|existing| existing.path == file.path) {
+            Some(existing) => {
+                *existing = file.clone();
+                Ok((file, true))
+            }
+            None => {
+                self.file_contexts.push(file.clone());
+                Ok((file, false))
+            }
+        }
+    }
+
+    fn add_repo_map_context(&mut self, dir_path: &Path, options: AddFileOptions) -> Result<(FileContext, usize)> {
+        if !dir_path.is_dir() {
+            anyhow::bail!("Not a directory: {}", dir_path.display());
+        }
+        let entries = ignore::WalkBuilder::new(dir_path)
+            .max_depth(options.depth)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+
+        let max_files = options.max_files.unwrap_or(self.directory_max_files);
+        let mut map = format!("Outline of {} (declarations only, bodies omitted; numbers are line numbers)\n", dir_path.display());
+        let mut mapped = 0;
+        for entry in entries {
+            let entry = entry.context("Failed to read directory entry")?;
+            let path = entry.path();
+            if !path.is_file() || !repomap::is_supported(path) {
+                continue;
+            }
+            let Ok(FileText::Text(text)) = read_file_text(path, &self.documents) else {
+                continue;
+            };
+            let Some(declarations) = repomap::outline(path, &text).filter(|declarations| !declarations.is_empty()) else {
+                continue;
+            };
+            if mapped == max_files {
+                eprintln!("{} Stopped after {} files; use --max-files to map more", "Warning:".bright_yellow(), mapped);
+                break;
+            }
+            let relative = path.strip_prefix(dir_path).unwrap_or(path);
+            map.push_str(&format!("\n{}\n{}\n", relative.display(), declarations.join("\n")));
+            mapped += 1;
+        }
+        if mapped == 0 {
+            anyhow::bail!("No declarations found in {}", dir_path.display());
+        }
+
+        let path = PathBuf::from(format!("repo-map:{}", dir_path.display()));
+        let file = self.add_text_context(path, ContextSource::RepoMap, map, AddFileOptions { minify: Some(false), ..options })?;
+        Ok((file, mapped))
+    }
+
     fn remove_file_context(&mut self, file_path: &str) -> Result<()> {
         let path = Path::new(file_path);
         let initial_len = self.file_contexts.len();
@@ -544,6 +2066,36 @@ impl Session {
         Ok(())
     }
     
+    fn set_file_context_enabled(&mut self, file_path: &str, enabled: bool) -> Result<()> {
+        let path = Path::new(file_path);
+        let mut found = false;
+        
+        for file_ctx in self.file_contexts.iter_mut().filter(|f| f.path == path || f.path.starts_with(path)) {
+            file_ctx.enabled = enabled;
+            found = true;
+        }
+        
+        if !found {
+            anyhow::bail!("File not found in context: {}", file_path);
+        }
+        
+        Ok(())
+    }
+    
+    fn move_file_context(&mut self, from: usize, to: usize) -> Result<()> {
+        let count = self.file_contexts.len();
+        if from == 0 || from > count {
+            anyhow::bail!("No context entry #{} (there are {} entries)", from, count);
+        }
+        if to == 0 || to > count {
+            anyhow::bail!("Position {} is out of range (1-{})", to, count);
+        }
+        
+        let entry = self.file_contexts.remove(from - 1);
+        self.file_contexts.insert(to - 1, entry);
+        Ok(())
+    }
+    
     fn clear_file_contexts(&mut self) {
         self.file_contexts.clear();
     }
@@ -555,21 +2107,102 @@ impl Session {
             return;
         }
         
-        println!("{} {} files", "Files:".bright_blue().bold(), self.file_contexts.len().to_string().bright_cyan());
+        println!("{} {} files", "Files:".paint(Role::Banner).bold(), self.file_contexts.len().to_string().bright_cyan());
         
-        let total_size: usize = self.file_contexts.iter().map(|f| f.size).sum();
+        let mut directories: Vec<(PathBuf, Vec<usize>)> = Vec::new();
+        for (i, file_ctx) in self.file_contexts.iter().enumerate() {
+            let dir = file_ctx.path.parent().map(Path::to_path_buf).unwrap_or_default();
+            match directories.iter_mut().find(|(d, _)| *d == dir) {
+                Some((_, indices)) => indices.push(i),
+                None => directories.push((dir, vec![i])),
+            }
+        }
         
-        for file_ctx in &self.file_contexts {
-            println!("  {} {}", 
-                file_ctx.path.display().to_string().bright_cyan(),
-                format!("({} bytes)", file_ctx.size).dimmed()
-            );
+        for (dir, indices) in &directories {
+            let dir_name = if dir.as_os_str().is_empty() { ".".to_string() } else { dir.display().to_string() };
+            println!("  {}/", dir_name.bright_blue());
+            
+            for (n, &i) in indices.iter().enumerate() {
+                let file_ctx = &self.file_contexts[i];
+                let branch = if n + 1 == indices.len() { "└──" } else { "├──" };
+                let name = file_ctx.path.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| file_ctx.path.display().to_string());
+                let tokens = self.tokenizer.count(&file_ctx.content);
+                
+                println!("  {} {}. {} {}{}", 
+                    branch.dimmed(),
+                    (i + 1).to_string().dimmed(),
+                    if file_ctx.enabled {
+                        name.bright_cyan()
+                    } else {
+                        name.dimmed().strikethrough()
+                    },
+                    format!("({} bytes, {}{}~{} tokens, {:.1}% of budget)",
+                        file_ctx.size,
+                        if file_ctx.minified { "minified, " } else { "" },
+                        match file_ctx.elided_lines {
+                            0 => String::new(),
+                            1 => "1 line omitted, ".to_string(),
+                            elided => format!("{} lines omitted, ", elided),
+                        },
+                        tokens,
+                        budget_share(tokens, self.settings.context_budget)).dimmed(),
+                    match &file_ctx.label {
+                        Some(label) => format!(" {}", format!("[{}]", label).bright_magenta()),
+                        None => String::new(),
+                    }
+                );
+            }
         }
         
-        println!("  {} {}", "Total:".dimmed(), format!("{} bytes", total_size).bright_green());
+        let enabled: Vec<&FileContext> = self.file_contexts.iter().filter(|f| f.enabled).collect();
+        let total_size: usize = enabled.iter().map(|f| f.size).sum();
+        let total_tokens: usize = enabled.iter().map(|f| self.tokenizer.count(&f.content)).sum();
+        
+        println!("  {} {}", "Total:".dimmed(), format!("{} bytes, ~{} tokens", total_size, total_tokens).bright_green());
+        let budget_text = format!("{:.1}% of {} tokens", budget_share(total_tokens, self.settings.context_budget), self.settings.context_budget);
+        println!("  {} {}", "Budget:".dimmed(), if total_tokens > self.settings.context_budget { budget_text.bright_red() } else { budget_text.bright_green() });
+        
+        let disabled = self.file_contexts.len() - enabled.len();
+        if disabled > 0 {
+            println!("  {} {}", "Disabled:".dimmed(), format!("{} (not sent with queries)", disabled).bright_yellow());
+        }
     }
 }
 
+fn budget_share(tokens: usize, budget: usize) -> f64 {
+    if budget == 0 {
+        return 0.0;
+    }
+    tokens as f64 * 100.0 / budget as f64
+}
+
+fn get_data_dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir()
+        .context("Could not determine data directory")?;
+
+    let app_data_dir = data_dir.join("fastgpt");
+    fs::create_dir_all(&app_data_dir)
+        .context("Failed to create data directory")?;
+
+    Ok(app_data_dir)
+}
+
+fn data_dir_writable() -> bool {
+    let Ok(dir) = get_data_dir() else {
+        return false;
+    };
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    let writable = fs::write(&probe, b"").is_ok();
+    let _ = fs::remove_file(&probe);
+    writable
+}
+
+fn readline_history_path() -> Option<PathBuf> {
+    get_data_dir().ok().map(|dir| dir.join("history.txt"))
+}
+
 fn get_config_path() -> Result<PathBuf> {
     let config_dir = config_dir()
         .context("Could not determine config directory")?;
@@ -582,7 +2215,9 @@ fn get_config_path() -> Result<PathBuf> {
 }
 
 fn load_config() -> Result<Config> {
-    let config_path = get_config_path()?;
+    let Ok(config_path) = get_config_path() else {
+        return Ok(Config::default());
+    };
     
     if !config_path.exists() {
         return Ok(Config::default());
@@ -597,24 +2232,77 @@ fn load_config() -> Result<Config> {
     Ok(config)
 }
 
+fn find_project_config() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+fn load_persona() -> Result<Option<Persona>> {
+    let Some(path) = find_project_config() else {
+        return Ok(None);
+    };
+
+    let content = fs::read_to_string(&path)
+        .context(format!("Failed to read project config: {}", path.display()))?;
+    let project: ProjectConfig = toml::from_str(&content)
+        .context(format!("Failed to parse project config: {}", path.display()))?;
+
+    Ok(project.persona
+        .filter(|prompt| !prompt.trim().is_empty())
+        .map(|prompt| Persona { source: path, prompt: prompt.trim().to_string() }))
+}
+
 fn save_config(config: &Config) -> Result<()> {
     let config_path = get_config_path()?;
     
     let config_content = toml::to_string_pretty(config)
         .context("Failed to serialize config")?;
     
-    fs::write(&config_path, config_content)
+    write_atomic(&config_path, config_content.as_bytes())
         .context("Failed to write config file")?;
     
     Ok(())
 }
 
-async fn interactive_config_setup() -> Result<()> {
-    let mut rl: Editor<(), _> = Editor::new()?;
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_name = path.file_name().context(format!("Invalid file path: {}", path.display()))?;
+    let temp_path = dir.join(format!(".{}.tmp-{}", file_name.to_string_lossy(), std::process::id()));
+
+    let result = (|| -> Result<()> {
+        let mut file = fs::File::create(&temp_path)
+            .context(format!("Failed to create file: {}", temp_path.display()))?;
+        if let Ok(metadata) = fs::metadata(path) {
+            let _ = file.set_permissions(metadata.permissions());
+        }
+        std::io::Write::write_all(&mut file, contents)
+            .context(format!("Failed to write file: {}", temp_path.display()))?;
+        file.sync_all()
+            .context(format!("Failed to sync file: {}", temp_path.display()))?;
+        fs::rename(&temp_path, path)
+            .context(format!("Failed to replace file: {}", path.display()))
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+async fn interactive_config_setup() -> Result<()> {
+    let mut rl: Editor<(), _> = Editor::new()?;
     
-    println!("{}", "=".repeat(60).bright_blue());
-    println!("{}", "Kagi FastGPT CLI Configuration".bright_green().bold());
-    println!("{}", "=".repeat(60).bright_blue());
+    println!("{}", "=".repeat(60).paint(Role::Banner));
+    println!("{}", "Kagi FastGPT CLI Configuration".paint(Role::Banner).bold());
+    println!("{}", "=".repeat(60).paint(Role::Banner));
     println!();
     
     println!("{}", "Let's set up your FastGPT CLI configuration!".bright_cyan());
@@ -625,7 +2313,7 @@ async fn interactive_config_setup() -> Result<()> {
             Ok(input) => {
                 let key = input.trim();
                 if key.is_empty() {
-                    println!("{}", "API key cannot be empty. Please try again.".bright_red());
+                    println!("{}", "API key cannot be empty. Please try again.".paint(Role::Error));
                     continue;
                 }
                 if key.len() < 10 {
@@ -653,7 +2341,7 @@ async fn interactive_config_setup() -> Result<()> {
                 return Ok(());
             }
             Err(err) => {
-                println!("{} {:?}", "Error reading input:".bright_red(), err);
+                println!("{} {:?}", "Error reading input:".paint(Role::Error), err);
                 continue;
             }
         }
@@ -685,23 +2373,22 @@ async fn interactive_config_setup() -> Result<()> {
                 return Ok(());
             }
             Err(err) => {
-                println!("{} {:?}", "Error reading input:".bright_red(), err);
+                println!("{} {:?}", "Error reading input:".paint(Role::Error), err);
                 continue;
             }
         }
     };
     
-    let config = Config {
-        api_key: Some(api_key),
-        show_references: Some(show_references),
-    };
+    let mut config = load_config().unwrap_or_default();
+    config.api_key = Some(api_key);
+    config.show_references = Some(show_references);
     
     save_config(&config)?;
     
     println!();
     println!("{}", "Configuration saved successfully!".bright_green().bold());
     println!();
-    println!("{}", "Your settings:".bright_blue().bold());
+    println!("{}", "Your settings:".paint(Role::Banner).bold());
     println!("  {} {}", "API Key:".dimmed(), "Set (hidden for security)".bright_green());
     println!("  {} {}", "Show References:".dimmed(), 
         if show_references { "Enabled".bright_green() } else { "Disabled".bright_red() });
@@ -714,7 +2401,167 @@ async fn interactive_config_setup() -> Result<()> {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let mut cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) if e.kind() == clap::error::ErrorKind::UnknownArgument => {
+            let _ = e.print();
+            eprintln!("{} to ask a question that starts with a subcommand name, use `fastgpt ask <question>` or `fastgpt -- <question>`",
+                "tip:".bright_green());
+            std::process::exit(e.exit_code());
+        }
+        Err(e) => e.exit(),
+    };
+
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    if cli.no_color || no_color_env || !output_format(&cli).is_styled() {
+        colored::control::set_override(false);
+    }
+    if let Ok(config) = load_config() {
+        match theme::Theme::from_config(&config.theme.unwrap_or_default()) {
+            Ok(theme) => theme::init(theme),
+            Err(e) => eprintln!("{} {:#}; using the default theme", "Warning:".bright_yellow(), e),
+        }
+    }
+
+    if let Some(Commands::Ask { query }) = &mut cli.command {
+        cli.query = std::mem::take(query);
+        cli.command = None;
+    }
+
+    if let Some(command) = cli.command.take() {
+        return match command {
+            Commands::Ask { .. } => unreachable!("ask is handled as a one-shot query"),
+            Commands::Cache { action } => run_cache_command(action),
+            Commands::Assets { action: AssetsAction::Dump { dir, force } } => dump_assets(dir, force),
+            Commands::Backup { file, include_key } => {
+                let count = backup::create(&file, include_key)?;
+                println!("{} {} files to {}", "Backed up".bright_green(), count.to_string().bright_cyan(), file.display().to_string().bright_cyan());
+                if include_key {
+                    println!("{}", "The archive contains your API key; keep it private.".dimmed());
+                } else {
+                    println!("{}", "The API key was left out; pass --include-key to back it up too.".dimmed());
+                }
+                Ok(())
+            }
+            Commands::Restore { file, force } => {
+                let summary = backup::restore(&file, force)?;
+                println!("{} {} files from {}", "Restored".bright_green(), summary.files.to_string().bright_cyan(), file.display().to_string().bright_cyan());
+                for path in &summary.skipped {
+                    println!("{} {} {}", "Skipped".bright_yellow(), path.display().to_string().bright_cyan(), "(exists, use --force to overwrite)".dimmed());
+                }
+                Ok(())
+            }
+            Commands::Gc { dry_run, sessions, cache, max_disk } => run_gc(dry_run, sessions, cache, max_disk),
+            Commands::Replay { session_id, to } => {
+                let config = load_config()?;
+                let api_key = resolve_api_key(&cli, &config)?;
+                let options = session_options(&cli, &config)?;
+                replay_session(api_key, options, &session_id, to).await
+            }
+            Commands::Ab { prompt_a, prompt_b, inputs, report } => {
+                let config = load_config()?;
+                let api_key = resolve_api_key(&cli, &config)?;
+                let options = session_options(&cli, &config)?;
+                let mut session = Session::new(api_key, SessionOptions { save_sessions: false, local_cache: false, ..options });
+                let content = ab::run(&mut session, &prompt_a, &prompt_b, &inputs).await?;
+                write_report(&content, report)
+            }
+            Commands::Research { topic, depth, time_limit, report } => {
+                let time_limit = time_limit.as_deref().map(cache::parse_duration).transpose()?.map(std::time::Duration::from_secs);
+                let config = load_config()?;
+                let api_key = resolve_api_key(&cli, &config)?;
+                let options = session_options(&cli, &config)?;
+                let mut session = Session::new(api_key, SessionOptions { save_sessions: false, ..options });
+                let content = research::run(&mut session, &topic, depth, time_limit).await?;
+                write_report(&content, report)
+            }
+            Commands::Batch { inputs, output, checkpoint, resume } => {
+                let config = load_config()?;
+                let api_key = resolve_api_key(&cli, &config)?;
+                let options = session_options(&cli, &config)?;
+                let mut session = Session::new(api_key, SessionOptions { save_sessions: false, ..options });
+                run_batch(&mut session, inputs, output, checkpoint, resume).await
+            }
+            Commands::Template { action } => run_template_command(action),
+            Commands::Q { template: None, .. } => {
+                list_templates();
+                Ok(())
+            }
+            Commands::Q { template: Some(name), args } => {
+                let template = templates::find(&name)
+                    .context(format!("Unknown template '{}'. Run `fastgpt q` to list templates.", name))?;
+                let query = template.render(&args)?;
+                let config = load_config()?;
+                let api_key = resolve_api_key(&cli, &config)?;
+                let options = session_options(&cli, &config)?;
+                run_one_shot(api_key, options, &query, &cli.contexts, cli.table).await
+            }
+            Commands::CommitMsg { file, source } => {
+                if matches!(source.as_deref(), Some("message" | "merge" | "squash" | "commit")) {
+                    return Ok(());
+                }
+                let result = async {
+                    let config = load_config()?;
+                    let api_key = resolve_api_key(&cli, &config)?;
+                    let options = session_options(&cli, &config)?;
+                    run_commit_msg(api_key, options, file.as_deref()).await
+                }
+                .await;
+                match (result, &file) {
+                    (Err(e), Some(path)) => {
+                        eprintln!("{} fastgpt commit-msg: {:#}; {} was left unchanged", "Warning:".bright_yellow(), e, path.display());
+                        Ok(())
+                    }
+                    (result, _) => result,
+                }
+            }
+            Commands::News { topic, since } => {
+                let prompt = news::digest_query(&topic, &since)?;
+                let config = load_config()?;
+                let api_key = resolve_api_key(&cli, &config)?;
+                let options = session_options(&cli, &config)?;
+                let mut session = Session::new(api_key, options);
+                let response = session.ask_question(&prompt).await?;
+                let title = format!("News: {} (since {})", topic, news::since_date(&since)?.format("%Y-%m-%d"));
+                output::print_response(&response, &title, session.output, session.show_references, None)
+            }
+            #[cfg(feature = "gui")]
+            Commands::Gui => {
+                let config = load_config()?;
+                let api_key = resolve_api_key(&cli, &config)?;
+                let options = session_options(&cli, &config)?;
+                gui::run(Session::new(api_key, options))
+            }
+            Commands::Tutorial => {
+                let config = load_config()?;
+                let api_key = resolve_api_key(&cli, &config)?;
+                let options = session_options(&cli, &config)?;
+                let sample_dir = tempfile::Builder::new().prefix("fastgpt-tutorial-").tempdir()
+                    .context("Failed to create a directory for the tutorial's sample files")?;
+                let tutorial = tutorial::Tutorial::new(sample_dir.path())?;
+                let session = Session::new(api_key, SessionOptions { save_sessions: false, ..options });
+                run_interactive_session(session, Some(tutorial)).await
+            }
+            Commands::Import { file, conversation } => {
+                let imported = import::load(&file, conversation.as_deref())?;
+                let config = load_config()?;
+                let api_key = resolve_api_key(&cli, &config)?;
+                let options = session_options(&cli, &config)?;
+                let mut session = Session::new(api_key, options);
+                println!("{} {} from {}",
+                    "Imported".bright_green(),
+                    format!("{} {}", imported.entries.len(), if imported.entries.len() == 1 { "exchange" } else { "exchanges" }).bright_cyan(),
+                    imported.title.unwrap_or_else(|| file.display().to_string()).bright_cyan());
+                session.history = imported.entries;
+                run_interactive_session(session, None).await
+            }
+            Commands::Schedule { action } => run_schedule_command(&cli, action).await,
+            Commands::Ratings { action: RatingsAction::Export { format, output } } => export_ratings(format, output),
+            Commands::Sessions { action: SessionsAction::List { filters } } => list_sessions(&filters),
+            Commands::Usage { days, months } => show_usage(days, months),
+            Commands::Report { week: _, days, format, output } => write_usage_report(days.unwrap_or(7), format, output),
+        };
+    }
 
     if cli.reset_api_key {
         let config = Config::default();
@@ -729,10 +2576,8 @@ async fn main() -> Result<()> {
     }
 
     if let Some(api_key) = cli.set_api_key {
-        let config = Config {
-            api_key: Some(api_key.clone()),
-            show_references: None,
-        };
+        let mut config = load_config().unwrap_or_default();
+        config.api_key = Some(api_key.clone());
         save_config(&config)?;
         println!("{}", "API key has been saved successfully!".bright_green());
         return Ok(());
@@ -756,262 +2601,1853 @@ async fn main() -> Result<()> {
                 } else {
                     "*".repeat(key.len())
                 };
-                println!("{} {}", "Current API key:".bright_blue(), masked_key.bright_cyan());
+                println!("{} {}", "Current API key:".paint(Role::Banner), masked_key.bright_cyan());
             }
             None => println!("{}", "No API key is currently set.".bright_yellow()),
         }
         return Ok(());
     }
 
-    let api_key = config.api_key
-        .context("No API key found. Set one with: fastgpt --set-api-key YOUR_KEY")?;
-    
-    let show_references = config.show_references.unwrap_or(true);
+    let api_key = resolve_api_key(&cli, &config)?;
+    let options = session_options(&cli, &config)?;
+
+    if let Some(retention) = config.retention.as_ref().filter(|_| !options.ephemeral) {
+        if let Err(e) = retention::run_if_due(retention) {
+            eprintln!("{} {}", "Warning: automatic cleanup failed:".bright_yellow(), e);
+        }
+    }
 
-    run_interactive_session(api_key, cli.cache, cli.json, show_references).await?;
+    if !cli.query.is_empty() {
+        let query = cli.query.join(" ");
+        let format = options.output;
+        return match run_one_shot(api_key, options, &query, &cli.contexts, cli.table).await {
+            Err(e) if e.downcast_ref::<MaxWaitExceeded>().is_some() => {
+                report_timeout(&e, format);
+                std::process::exit(TIMEOUT_EXIT_CODE);
+            }
+            result => result,
+        };
+    }
+
+    if !std::io::stdin().is_terminal() && !std::io::stdout().is_terminal() {
+        anyhow::bail!("No query given. Without a terminal, pass the question as an argument: fastgpt \"your question\"");
+    }
+
+    let mut session = Session::new(api_key, options);
+    attach_contexts(&mut session, &cli.contexts, false)?;
+    run_interactive_session(session, None).await?;
 
     Ok(())
 }
 
-async fn run_interactive_session(api_key: String, cache: bool, json_mode: bool, show_references: bool) -> Result<()> {
-    let mut session = Session::new(api_key, cache, json_mode, show_references);
-    let mut rl: Editor<FastGPTHelper, _> = Editor::new()?;
-    rl.set_helper(Some(FastGPTHelper::default()));
+fn output_format(cli: &Cli) -> OutputFormat {
+    match cli.output {
+        Some(format) => format,
+        None if cli.json => OutputFormat::Json,
+        None if !std::io::stdout().is_terminal() => OutputFormat::Plain,
+        None => OutputFormat::Pretty,
+    }
+}
 
-    print!("\x1B[2J\x1B[3J\x1B[H");
-    std::io::Write::flush(&mut std::io::stdout()).unwrap();
-    println!("{}", "=".repeat(80).bright_blue());
-    println!("{}", "Kagi FastGPT CLI".bright_green().bold());
-    println!("{} {}", "Session ID:".dimmed(), session.id.bright_cyan());
-    println!("{}", "=".repeat(80).bright_blue());
-    println!();
-    println!("{}", "Commands:".bright_yellow().bold());
-    println!("  {} - Exit the session", "/exit or /quit".bright_cyan());
-    println!("  {} - Clear conversation history and screen", "/clear".bright_cyan());
-    println!("  {} - Show conversation history", "/history".bright_cyan());
-    println!("  {} - Add file(s) or directory to context", "/add-file <path>".bright_cyan());
-    println!("  {} - Remove file from context", "/remove-file <path>".bright_cyan());
-    println!("  {} - List all files in context", "/list-files".bright_cyan());
-    println!("  {} - Clear all file contexts", "/clear-files".bright_cyan());
-    println!("  {} - Check API balance", "/balance".bright_cyan());
-    println!("  {} - Show this help", "/help".bright_cyan());
-    println!();
-    println!("{} Just start typing your question!", "Tip:".bright_magenta().bold());
-    println!();
+async fn run_one_shot(api_key: String, options: SessionOptions, query: &str, contexts: &[String], table: bool) -> Result<()> {
+    let mut session = Session::new(api_key, options);
+    attach_contexts(&mut session, contexts, true)?;
+    let query = &snippets::expand(query, &snippets::load()?);
 
-    loop {
-        match rl.readline("❯ ") {
-            Ok(line) => {
-                let input = line.trim();
-                
-                if input.is_empty() {
-                    continue;
-                }
+    if table {
+        let response = session.ask_question(&table::table_query(query)).await?;
+        return match table::parse(&response.data.output) {
+            Some(table) if std::io::stdout().is_terminal() => {
+                println!("{}", table.render());
+                Ok(())
+            }
+            Some(table) => {
+                print!("{}", table.to_csv());
+                Ok(())
+            }
+            None => {
+                eprintln!("{}", "Warning: the answer did not contain a table".bright_yellow());
+                output::print_response(&response, query, session.output, session.show_references, None)
+            }
+        };
+    }
 
-                rl.add_history_entry(input).ok();
+    let response = session.ask_question(query).await?;
+    output::print_response(&response, query, session.output, session.show_references, None)
+}
 
-                match input {
-                    "/exit" | "/quit" => {
-                        println!("{}", "Goodbye!".bright_green());
-                        break;
-                    }
-                    "/clear" => {
-                        session.clear_history();
-                        continue;
-                    }
-                    "/history" => {
-                        session.show_history();
-                        println!();
-                        continue;
-                    }
-                    "/help" => {
-                        println!("{}", "Available commands:".bright_yellow().bold());
-                        println!("  {} - Exit the session", "/exit or /quit".bright_cyan());
-                        println!("  {} - Clear conversation history and screen", "/clear".bright_cyan());
-                        println!("  {} - Show conversation history", "/history".bright_cyan());
-                        println!("  {} - Add file(s) or directory to context", "/add-file <path>".bright_cyan());
-                        println!("  {} - Remove file from context", "/remove-file <path>".bright_cyan());
-                        println!("  {} - List all files in context", "/list-files".bright_cyan());
-                        println!("  {} - Clear all file contexts", "/clear-files".bright_cyan());
-                        println!("  {} - Check API balance", "/balance".bright_cyan());
-                        println!("  {} - Show this help", "/help".bright_cyan());
-                        println!();
-                        continue;
-                    }
-                    _ if input.starts_with("/add-file ") => {
-                        let file_path = input.trim_start_matches("/add-file ").trim();
-                        if file_path.is_empty() {
-                            println!("{} Please specify a file path: /add-file <path>", "Error:".bright_red().bold());
-                        } else {
-                            match session.add_file_context(file_path) {
-                                Ok(()) => {
-                                    println!();
-                                    println!("{} {}", "Added:".bright_green(), file_path.bright_cyan());
-                                }
-                                Err(e) => {
-                                    println!();
-                                    println!("{} {}", "Error:".bright_red().bold(), e);
-                                }
-                            }
-                        }
-                        println!();
-                        continue;
-                    }
-                    _ if input.starts_with("/remove-file ") => {
-                        let file_path = input.trim_start_matches("/remove-file ").trim();
-                        if file_path.is_empty() {
-                            println!("{} Please specify a file path: /remove-file <path>", "Error:".bright_red().bold());
-                        } else {
-                            match session.remove_file_context(file_path) {
-                                Ok(()) => {
-                                    println!();
-                                    println!("{} {}", "Removed:".bright_yellow(), file_path.bright_cyan());
-                                }
-                                Err(e) => {
-                                    println!();
-                                    println!("{} {}", "Error:".bright_red().bold(), e);
-                                }
-                            }
-                        }
-                        println!();
-                        continue;
-                    }
-                    "/list-files" => {
-                        session.list_file_contexts();
-                        println!();
-                        continue;
-                    }
-                    "/clear-files" => {
-                        session.clear_file_contexts();
-                        println!();
-                        println!("{} All file contexts cleared.", "Cleared:".bright_yellow());
-                        println!();
-                        continue;
-                    }
-                    "/balance" => {
-                        match session.check_balance().await {
-                            Ok(balance) => {
-                                println!();
-                                println!("{} ${:.3}", "Balance:".dimmed(), balance.to_string().bright_green());
-                            }
-                            Err(e) => {
-                                println!();
-                                println!("{} Failed to check balance: {}", "Error:".bright_red().bold(), e);
-                            }
-                        }
-                        println!();
-                        continue;
-                    }
-                    _ if input.starts_with('/') => {
-                        println!();
-                        println!("{} Unknown command: {}. Type /help for available commands.", 
-                            "Error:".bright_red().bold(), input.bright_red());
-                        println!();
-                        continue;
-                    }
-                    _ => {
-                        match session.ask_question(input).await {
-                            Ok(response) => {
-                                if session.json_mode {
-                                    println!("{}", serde_json::to_string_pretty(&response)?);
-                                } else {
-                                    print_formatted_response(&response, input, session.show_references);
-                                }
-                                println!();
-                            }
-                            Err(e) => {
-                                println!();
-                                println!("{} {}", "Error:".bright_red().bold(), e);
-                                println!();
-                            }
-                        }
-                    }
+async fn run_commit_msg(api_key: String, options: SessionOptions, file: Option<&Path>) -> Result<()> {
+    let diff = git::diff(true, None)?;
+    if diff.trim().is_empty() {
+        anyhow::bail!("No staged changes to describe (stage them with git add first)");
+    }
+
+    let mut session = Session::new(api_key, options);
+    session.add_text_context(PathBuf::from("git-diff --staged"), ContextSource::Diff, diff, AddFileOptions::default())?;
+    let response = session.ask_question(git::COMMIT_MESSAGE_QUERY).await?;
+    let message = git::clean_commit_message(&response.data.output);
+    if message.is_empty() {
+        anyhow::bail!("FastGPT returned an empty commit message");
+    }
+
+    match file {
+        Some(path) => {
+            let existing = fs::read_to_string(path).unwrap_or_default();
+            fs::write(path, format!("{}\n{}", message, existing))
+                .context(format!("Failed to write commit message: {}", path.display()))?;
+            eprintln!("{} {}", "Wrote commit message to".bright_green(), path.display());
+        }
+        None => println!("{}", message),
+    }
+    Ok(())
+}
+
+fn attach_contexts(session: &mut Session, contexts: &[String], one_shot: bool) -> Result<()> {
+    for context in contexts {
+        match context.as_str() {
+            "-" if !one_shot => anyhow::bail!("--context - reads stdin, which only works with a one-shot query"),
+            "-" => {
+                if std::io::stdin().is_terminal() {
+                    anyhow::bail!("--context - reads stdin, but nothing is piped in");
                 }
+                let text = std::io::read_to_string(std::io::stdin()).context("Failed to read stdin")?;
+                session.add_text_context(PathBuf::from("stdin"), ContextSource::Stdin, text, AddFileOptions::default())?;
             }
-            Err(ReadlineError::Interrupted) => {
-                println!("{}", "Use /exit or /quit to exit.".bright_yellow());
-            }
-            Err(ReadlineError::Eof) => {
-                println!("{}", "Goodbye!".bright_green());
-                break;
+            pattern if is_glob(pattern) => {
+                session.add_glob_context(pattern, AddFileOptions::default())?;
             }
-            Err(err) => {
-                println!("{} {:?}", "Error:".bright_red().bold(), err);
-                break;
+            path => {
+                session.add_file_context(path, AddFileOptions { recursive: true, ..Default::default() })?;
             }
         }
     }
-
     Ok(())
 }
 
-fn format_markdown_text(text: &str) -> String {
-    let decoded = decode_html_entities(text).to_string();
-    
-    let bold_regex = Regex::new(r"\*\*(.*?)\*\*").unwrap();
-    let italic_regex = Regex::new(r"\*(.*?)\*").unwrap();
-    let code_regex = Regex::new(r"`(.*?)`").unwrap();
-    
-    let mut formatted = decoded;
-    
-    formatted = bold_regex.replace_all(&formatted, |caps: &regex::Captures| {
-        caps[1].bright_white().bold().to_string()
-    }).to_string();
-    
-    formatted = italic_regex.replace_all(&formatted, |caps: &regex::Captures| {
-        caps[1].italic().to_string()
-    }).to_string();
-    
-    formatted = code_regex.replace_all(&formatted, |caps: &regex::Captures| {
-        caps[1].on_bright_black().bright_white().to_string()
-    }).to_string();
-    
-    formatted
+fn report_timeout(error: &anyhow::Error, format: OutputFormat) {
+    match format {
+        OutputFormat::Json | OutputFormat::Jsonl => println!("{}", serde_json::json!({
+            "error": {
+                "kind": "timeout",
+                "message": error.to_string(),
+                "max_wait": error.downcast_ref::<MaxWaitExceeded>().map(|e| e.secs),
+            }
+        })),
+        _ => eprintln!("{} {}", "Error:".paint(Role::Error).bold(), error),
+    }
 }
 
-fn remove_reference_numbers(text: &str) -> String {
-    let reference_regex = Regex::new(r"【\d+】").unwrap();
-    reference_regex.replace_all(text, "").to_string()
+fn resolve_api_key(cli: &Cli, config: &Config) -> Result<String> {
+    let api_key = std::env::var(API_KEY_ENV).ok()
+        .filter(|key| !key.trim().is_empty())
+        .or_else(|| config.api_key.clone());
+    if cli.offline || cli.demo {
+        return Ok(api_key.unwrap_or_default());
+    }
+    api_key.context(format!("No API key found. Set one with: fastgpt --set-api-key YOUR_KEY (or the {} environment variable)", API_KEY_ENV))
 }
 
-fn print_formatted_response(response: &FastGPTResponse, query: &str, show_references: bool) {
-    println!();
-    println!("{} {}", ">".bright_blue().bold(), query.bright_white());
-    println!();
-    
-    let output_text = if show_references {
-        response.data.output.clone()
-    } else {
-        remove_reference_numbers(&response.data.output)
+fn editor_settings(config: &Config) -> Result<EditorSettings> {
+    let edit_mode = match config.editor_mode.as_deref() {
+        None | Some("emacs") => EditMode::Emacs,
+        Some("vi") => EditMode::Vi,
+        Some(other) => anyhow::bail!("Invalid editor_mode '{}' in config (use \"emacs\" or \"vi\")", other),
     };
-    
-    println!("{}", format_markdown_text(&output_text));
+    let completion_type = match config.completion_type.as_deref() {
+        None | Some("circular") => CompletionType::Circular,
+        Some("list") => CompletionType::List,
+        Some(other) => anyhow::bail!("Invalid completion_type '{}' in config (use \"circular\" or \"list\")", other),
+    };
+    let bell_style = match config.bell_style.as_deref() {
+        None | Some("audible") => BellStyle::Audible,
+        Some("visible") => BellStyle::Visible,
+        Some("none") => BellStyle::None,
+        Some(other) => anyhow::bail!("Invalid bell_style '{}' in config (use \"audible\", \"visible\" or \"none\")", other),
+    };
+    Ok(EditorSettings { edit_mode, completion_type, bell_style })
+}
+
+fn session_options(cli: &Cli, config: &Config) -> Result<SessionOptions> {
+    let ephemeral = !data_dir_writable();
+    Ok(SessionOptions {
+        settings: fastgpt::Settings {
+            cache: cli.cache,
+            web_search: true,
+            system_prompt: cli.system.clone()
+                .or_else(|| config.system_prompt.clone())
+                .map(|prompt| prompt.trim().to_string())
+                .filter(|prompt| !prompt.is_empty()),
+            context_budget: config.context_budget.unwrap_or(DEFAULT_CONTEXT_BUDGET),
+            enforce_context_budget: config.enforce_context_budget.unwrap_or(false),
+        },
+        output: output_format(cli),
+        show_references: config.show_references.unwrap_or(true),
+        local_cache: !cli.no_local_cache && !cli.demo && !ephemeral,
+        cache_ttl: config_cache_ttl(config)?,
+        offline: cli.offline,
+        max_wait: match cli.max_wait {
+            Some(secs) => match Duration::try_from_secs_f64(secs) {
+                Ok(duration) if secs > 0.0 => Some(duration),
+                _ => anyhow::bail!("--max-wait must be a positive number of seconds, got {}", secs),
+            },
+            None => None,
+        },
+        demo: cli.demo,
+        ephemeral,
+        directory_depth: config.directory_depth.unwrap_or(DEFAULT_DIRECTORY_DEPTH).max(1),
+        directory_max_files: config.directory_max_files.unwrap_or(DEFAULT_DIRECTORY_MAX_FILES).max(1),
+        context_extensions: config.context_extensions.as_deref()
+            .map(|extensions| extensions.iter().map(|ext| ext.trim().trim_start_matches('.').to_lowercase()).collect())
+            .unwrap_or_else(|| DEFAULT_CONTEXT_EXTENSIONS.iter().map(|ext| ext.to_string()).collect()),
+        large_file_bytes: config.large_file_bytes.unwrap_or(DEFAULT_LARGE_FILE_BYTES),
+        truncation: Truncation {
+            strategy: TruncateStrategy::parse(config.large_file_strategy.as_deref().unwrap_or("ends"))
+                .context("Invalid large_file_strategy in config")?,
+            head_lines: config.large_file_head_lines.unwrap_or(DEFAULT_HEAD_LINES),
+            tail_lines: config.large_file_tail_lines.unwrap_or(DEFAULT_TAIL_LINES),
+            max_bytes: config.large_file_bytes.unwrap_or(DEFAULT_LARGE_FILE_BYTES),
+        },
+        documents: documents::Options {
+            notebook_outputs: config.notebook_outputs.unwrap_or(false),
+        },
+        live_contexts: config.live_contexts.unwrap_or(false),
+        watch_contexts: config.watch_contexts.unwrap_or(false),
+        redact_secrets: config.redact_secrets.unwrap_or(true),
+        minify_contexts: config.minify_contexts.unwrap_or(false),
+        tokenizer: tokens::Tokenizer::new(config.tokenizer.as_deref(), config.tokenizer_command.as_deref())?,
+        save_sessions: !cli.demo && !ephemeral && (cli.save || config.save_sessions.unwrap_or(false)),
+        budget: if config.monthly_budget_tokens.is_some() || config.monthly_budget_cost.is_some() {
+            Some(usage::Budget {
+                tokens: config.monthly_budget_tokens,
+                cost: config.monthly_budget_cost,
+                hard_stop: config.budget_hard_stop.unwrap_or(false),
+            })
+        } else {
+            None
+        },
+        filters: filters::compile(&config.filters)?,
+        localizer: config.localize.as_ref().map(localize::Localizer::new).transpose()?,
+        pager: config.pager.clone()
+            .or_else(|| std::env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()))
+            .unwrap_or_else(|| DEFAULT_PAGER.to_string()),
+        paging: config.paging.unwrap_or(true),
+        prompt: config.prompt.clone().unwrap_or_else(|| DEFAULT_PROMPT.to_string()),
+        editor: editor_settings(config)?,
+        confirm_before_send: config.confirm_before_send.unwrap_or(false),
+        persona: load_persona()?,
+        aliases: command_aliases(config),
+    })
+}
+
+async fn replay_session(api_key: String, options: SessionOptions, session_id: &str, to: Option<usize>) -> Result<()> {
+    sessions::session_path(session_id)?;
+    let saved = match sessions::load(session_id) {
+        Ok(saved) => saved,
+        Err(e) => anyhow::bail!("{:#}. Sessions are only saved when saving is on: pass --save, use /save on in the REPL, \
+            or set save_sessions = true in config.toml before the conversation you want to replay.", e),
+    };
+    if saved.exchanges.is_empty() {
+        anyhow::bail!("Session {} has no exchanges to replay", session_id);
+    }
+
+    let last = to.unwrap_or(saved.exchanges.len());
+    if last == 0 || last > saved.exchanges.len() {
+        anyhow::bail!("Exchange {} is out of range (session has {} exchanges)", last, saved.exchanges.len());
+    }
+
+    let show_references = options.show_references;
+    let format = options.output;
+    let mut session = Session::new(api_key, SessionOptions { save_sessions: false, ..options });
+
+    if format.is_human_readable() {
+        println!("{} {} {}", "Replaying session".paint(Role::Banner).bold(), saved.id.bright_cyan(),
+            format!("(exchanges 1-{} of {})", last, saved.exchanges.len()).dimmed());
+    }
+
+    for (i, exchange) in saved.exchanges.iter().take(last).enumerate() {
+        let response = session.send_query(&exchange.prompt).await
+            .context(format!("Replay failed at exchange {}", i + 1))?;
+
+        if !format.is_human_readable() {
+            output::print_response(&response, &exchange.query, format, show_references, None)?;
+            continue;
+        }
 
-    if show_references && !response.data.references.is_empty() {
         println!();
-        println!("{}", "References:".bright_yellow().bold());
-        for (i, reference) in response.data.references.iter().enumerate() {
-            println!("  {}. {}", (i + 1).to_string().bright_cyan(), format_markdown_text(&reference.title).bright_white());
-            println!("     {}", reference.url.blue().underline());
-            if !reference.snippet.is_empty() {
-                println!("     {}", format_markdown_text(&reference.snippet).dimmed());
+        println!("{} {}", format!("[{}/{}]", i + 1, last).dimmed(), "Replayed answer:".bright_yellow().bold());
+        print_formatted_response(&response, &exchange.query, show_references, None);
+
+        if i + 1 == last {
+            println!();
+            if response.data.output == exchange.response.data.output {
+                println!("{}", "Replayed answer is identical to the recorded one.".bright_green());
+            } else {
+                println!("{} {}", "Recorded answer".bright_yellow().bold(),
+                    format!("({}):", sessions::format_time(exchange.timestamp)).dimmed());
+                println!("{}", format_markdown_text(&exchange.response.data.output).dimmed());
             }
         }
     }
 
-    println!();
-    println!("{}", format!(
-        "{} {} • {} {} • {} {}ms{}",
-        "Tokens:".dimmed(),
-        response.data.tokens.to_string().bright_magenta(),
-        "Node:".dimmed(),
-        response.meta.node.bright_magenta(),
-        "Time:".dimmed(),
-        response.meta.ms.to_string().bright_magenta(),
-        if let Some(balance) = response.meta.api_balance {
-            format!(" • {} ${:.3}", "Balance:".dimmed(), balance.to_string().bright_green())
-        } else {
-            String::new()
+    Ok(())
+}
+
+fn config_cache_ttl(config: &Config) -> Result<Option<u64>> {
+    match config.cache_ttl.as_deref() {
+        Some("never") => Ok(None),
+        Some(ttl) => Ok(Some(cache::parse_duration(ttl).context("Invalid cache_ttl in config")?)),
+        None => Ok(Some(cache::DEFAULT_TTL_SECS)),
+    }
+}
+
+fn dump_assets(dir: Option<PathBuf>, force: bool) -> Result<()> {
+    let target = match dir {
+        Some(dir) => dir,
+        None => assets::override_dir().context("Could not determine config directory")?,
+    };
+
+    let (written, skipped) = assets::dump(&target, force)?;
+    for path in &written {
+        println!("{} {}", "Wrote".bright_green(), path.display().to_string().bright_cyan());
+    }
+    for path in &skipped {
+        println!("{} {} {}", "Skipped".bright_yellow(), path.display().to_string().bright_cyan(), "(exists, use --force to overwrite)".dimmed());
+    }
+    if Some(&target) == assets::override_dir().as_ref() {
+        println!("{}", "Edited files in this directory take precedence over the embedded assets.".dimmed());
+    } else {
+        let override_dir = assets::override_dir().map(|dir| dir.display().to_string()).unwrap_or_default();
+        println!("{} {} {}", "Copy edited files into".dimmed(), override_dir.bright_cyan(), "to use them instead of the embedded assets.".dimmed());
+    }
+    Ok(())
+}
+
+fn run_gc(dry_run: bool, sessions: Option<String>, cache: Option<String>, max_disk: Option<String>) -> Result<()> {
+    let config = load_config()?.retention.unwrap_or_default();
+    let policy = retention::Policy::from_config(&retention::RetentionConfig {
+        sessions: sessions.or(config.sessions),
+        cache: cache.or(config.cache),
+        max_disk: max_disk.or(config.max_disk),
+        auto: None,
+    })?;
+    if policy.is_empty() {
+        println!("{}", "No retention policy configured; add a [retention] section to the config or pass --sessions, --cache or --max-disk.".bright_yellow());
+        return Ok(());
+    }
+
+    let report = retention::run(&policy, dry_run)?;
+    for file in &report.removed {
+        let kind = match file.kind {
+            retention::Kind::Session => "session",
+            retention::Kind::Cache => "cache",
+        };
+        println!("  {} {} {}", format!("[{}]", kind).dimmed(), file.path.display().to_string().bright_cyan(),
+            format!("({}, last used {})", retention::format_size(file.size), sessions::format_time(file.last_used)).dimmed());
+    }
+    println!("{} {} sessions and {} cached responses, {} freed; {} kept.",
+        if dry_run { "Would remove" } else { "Removed" }.bright_yellow(),
+        report.count(retention::Kind::Session).to_string().bright_cyan(),
+        report.count(retention::Kind::Cache).to_string().bright_cyan(),
+        retention::format_size(report.freed()).bright_green(),
+        retention::format_size(report.kept_size).bright_green());
+    Ok(())
+}
+
+fn run_cache_command(action: CacheAction) -> Result<()> {
+    match action {
+        CacheAction::Clear => {
+            let removed = cache::clear()?;
+            println!("{} {} cached responses.", "Cleared".bright_yellow(), removed.to_string().bright_cyan());
         }
-    ).dimmed());
-}
\ No newline at end of file
+        CacheAction::Stats => {
+            let ttl = config_cache_ttl(&load_config()?)?;
+            let stats = cache::stats(ttl)?;
+            println!("{} {}", "Location:".dimmed(), cache::get_cache_dir()?.display().to_string().bright_cyan());
+            println!("{} {}", "Entries:".dimmed(), stats.entries.to_string().bright_cyan());
+            println!("{} {}", "Expired:".dimmed(), stats.expired.to_string().bright_yellow());
+            println!("{} {}", "Size:".dimmed(), format!("{} bytes", stats.total_size).bright_green());
+        }
+        CacheAction::Purge { older_than } => {
+            let max_age = match older_than {
+                Some(age) => cache::parse_duration(&age)?,
+                None => match config_cache_ttl(&load_config()?)? {
+                    Some(ttl) => ttl,
+                    None => {
+                        println!("{}", "No cache TTL configured; use --older-than to choose an age.".bright_yellow());
+                        return Ok(());
+                    }
+                },
+            };
+            let removed = cache::purge(max_age)?;
+            println!("{} {} cached responses.", "Purged".bright_yellow(), removed.to_string().bright_cyan());
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_schedule_command(cli: &Cli, action: ScheduleAction) -> Result<()> {
+    match action {
+        ScheduleAction::Add { spec } => {
+            let (cron, query) = schedule::split_spec(&spec)?;
+            let id = schedule::update(|schedule| Ok(schedule.add(cron, query)))?;
+            println!("{} {}", "Scheduled query".bright_green(), id.to_string().bright_cyan());
+        }
+        ScheduleAction::List => {
+            let schedule = schedule::load()?;
+            if schedule.jobs.is_empty() {
+                println!("{}", "No scheduled queries.".dimmed());
+            }
+            for job in &schedule.jobs {
+                let last_run = job.last_run.map(sessions::format_time).unwrap_or_else(|| "never".to_string());
+                println!("{} {} {}", format!("[{}]", job.id).bright_cyan(), job.cron.bright_magenta(), job.query.paint(Role::Query));
+                println!("    {} {}", "Last run:".dimmed(), last_run.dimmed());
+            }
+        }
+        ScheduleAction::Remove { id } => {
+            schedule::update(|schedule| schedule.remove(id))?;
+            println!("{} {}", "Removed scheduled query".bright_yellow(), id.to_string().bright_cyan());
+        }
+        ScheduleAction::Run { all } => {
+            let config = load_config()?;
+            let api_key = resolve_api_key(cli, &config)?;
+            let options = session_options(cli, &config)?;
+            let mut session = Session::new(api_key, SessionOptions { save_sessions: false, local_cache: false, ..options });
+            let mut ran = schedule::load()?;
+            let result = schedule::run(&mut session, &mut ran, all).await;
+            schedule::update(|schedule| {
+                schedule.record_runs(ran);
+                Ok(())
+            })?;
+            result?;
+        }
+    }
+
+    Ok(())
+}
+
+fn list_templates() {
+    println!("{}", "Templates:".bright_yellow().bold());
+    for template in templates::all() {
+        let source = match template.source {
+            templates::Source::Builtin => String::new(),
+            templates::Source::Customized => format!(" {}", "(customized)".dimmed()),
+            templates::Source::Custom => format!(" {}", "(custom)".dimmed()),
+        };
+        println!("  {} - {}{}", template.usage().bright_cyan(), template.description, source);
+    }
+}
+
+fn edit_template(initial: &str) -> Result<String> {
+    let mut text = initial.to_string();
+    loop {
+        text = edit_text(&text)?;
+        if text.is_empty() {
+            anyhow::bail!("Empty template, nothing saved");
+        }
+        match templates::parse(&text) {
+            Ok(()) => return Ok(text),
+            Err(e) => {
+                println!("{} {:#}", "Error:".paint(Role::Error).bold(), e);
+                print!("Re-open the editor to fix it? [Y/n]: ");
+                std::io::Write::flush(&mut std::io::stdout())?;
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                if answer.trim().eq_ignore_ascii_case("n") {
+                    anyhow::bail!("Template not saved");
+                }
+            }
+        }
+    }
+}
+
+fn run_template_command(action: TemplateAction) -> Result<()> {
+    match action {
+        TemplateAction::List => list_templates(),
+        TemplateAction::Add { name, description, body, edit } => {
+            templates::validate_name(&name)?;
+            if templates::find(&name).is_some() {
+                anyhow::bail!("Template '{}' already exists; use `fastgpt template edit {}`", name, name);
+            }
+            let content = match (&description, &body) {
+                (Some(description), Some(body)) if !edit => templates::to_toml(description, body)?,
+                _ => edit_template(&templates::to_toml(
+                    description.as_deref().unwrap_or("What this template asks"),
+                    body.as_deref().unwrap_or("Question with {{param}} placeholders and {{optional|default}} values"),
+                )?)?,
+            };
+            let path = templates::save(&name, &content)?;
+            println!("{} {} {}", "Added template".bright_green(), name.bright_cyan(), format!("({})", path.display()).dimmed());
+        }
+        TemplateAction::Edit { name } => {
+            let content = templates::content(&name)
+                .context(format!("Unknown template '{}'. Run `fastgpt template list` to list templates.", name))?;
+            let edited = edit_template(&content)?;
+            if edited == content.trim() {
+                println!("{}", "No changes.".bright_yellow());
+                return Ok(());
+            }
+            let path = templates::save(&name, &edited)?;
+            println!("{} {} {}", "Saved template".bright_green(), name.bright_cyan(), format!("({})", path.display()).dimmed());
+        }
+        TemplateAction::Remove { name } => match templates::remove(&name)? {
+            templates::Source::Customized => println!("{} {}", "Restored the built-in template".bright_yellow(), name.bright_cyan()),
+            _ => println!("{} {}", "Removed template".bright_yellow(), name.bright_cyan()),
+        },
+    }
+    Ok(())
+}
+
+fn write_report(content: &str, report: Option<PathBuf>) -> Result<()> {
+    match report {
+        Some(path) => {
+            fs::write(&path, content)
+                .context(format!("Failed to write report: {}", path.display()))?;
+            println!("{} {}", "Report written to".bright_green(), path.display().to_string().bright_cyan());
+        }
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn list_sessions(filters: &[String]) -> Result<()> {
+    let filters = filters
+        .iter()
+        .map(|filter| sessions::parse_metadata(filter))
+        .collect::<Result<Vec<_>>>()?;
+    let matching: Vec<_> = sessions::load_all()?
+        .into_iter()
+        .filter(|session| filters.iter().all(|(key, value)| session.metadata.get(key) == Some(value)))
+        .collect();
+
+    if matching.is_empty() {
+        let message = if filters.is_empty() {
+            "No saved sessions. Sessions are saved only with --save, /save on or save_sessions = true in config.toml."
+        } else {
+            "No saved sessions match."
+        };
+        println!("{}", message.dimmed());
+        return Ok(());
+    }
+
+    for session in &matching {
+        let first_query = session.exchanges.first().map(|exchange| exchange.query.as_str()).unwrap_or("");
+        let preview: String = first_query.chars().take(60).collect();
+        let ellipsis = if first_query.chars().count() > 60 { "..." } else { "" };
+        println!("{} {} {}{}",
+            session.id.bright_cyan(),
+            format!("({}, {} exchanges)", sessions::format_time(session.created_at), session.exchanges.len()).dimmed(),
+            preview,
+            ellipsis);
+        if !session.metadata.is_empty() {
+            let metadata: Vec<String> = session.metadata.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+            println!("  {}", metadata.join(" ").bright_magenta());
+        }
+    }
+
+    Ok(())
+}
+
+fn export_ratings(format: ExportFormat, output: Option<PathBuf>) -> Result<()> {
+    let sessions = sessions::load_all()?;
+    let mut rows = Vec::new();
+
+    for session in &sessions {
+        for (i, exchange) in session.exchanges.iter().enumerate() {
+            if let Some(rating) = &exchange.rating {
+                rows.push(serde_json::json!({
+                    "session_id": session.id,
+                    "exchange": i + 1,
+                    "timestamp": sessions::format_time(exchange.timestamp),
+                    "score": rating.score,
+                    "comment": rating.comment.clone().unwrap_or_default(),
+                    "query": exchange.query,
+                    "tokens": exchange.response.data.tokens,
+                    "references": exchange.response.data.references.len(),
+                }));
+            }
+        }
+    }
+
+    let content = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&rows)?,
+        ExportFormat::Csv => {
+            let columns = ["session_id", "exchange", "timestamp", "score", "comment", "query", "tokens", "references"];
+            let mut csv = columns.join(",");
+            csv.push('\n');
+            for row in &rows {
+                let fields: Vec<String> = columns
+                    .iter()
+                    .map(|column| match &row[column] {
+                        serde_json::Value::String(value) => csv_field(value),
+                        value => value.to_string(),
+                    })
+                    .collect();
+                csv.push_str(&fields.join(","));
+                csv.push('\n');
+            }
+            csv
+        }
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(&path, content)
+                .context(format!("Failed to write ratings export: {}", path.display()))?;
+            println!("{} {} ratings to {}", "Exported".bright_green(), rows.len().to_string().bright_cyan(), path.display().to_string().bright_cyan());
+        }
+        None => print!("{}", content),
+    }
+
+    Ok(())
+}
+
+fn show_usage(days: usize, months: usize) -> Result<()> {
+    let records = usage::load_records()?;
+    if records.is_empty() {
+        println!("{}", "No usage recorded yet.".dimmed());
+        return Ok(());
+    }
+
+    let print_rollups = |title: &str, rollups: std::collections::BTreeMap<String, usage::Rollup>, limit: usize| {
+        println!("{}", title.paint(Role::Banner).bold());
+        println!("  {:<12} {:>8} {:>10} {:>10} {:>9}",
+            "Period".dimmed(), "Queries".dimmed(), "Tokens".dimmed(), "Avg ms".dimmed(), "Cost".dimmed());
+        let skip = rollups.len().saturating_sub(limit);
+        for (period, rollup) in rollups.iter().skip(skip) {
+            println!("  {:<12} {:>8} {:>10} {:>10} {:>9}",
+                period.bright_cyan(),
+                rollup.queries,
+                rollup.tokens.to_string().bright_magenta(),
+                rollup.average_ms(),
+                format!("${:.3}", rollup.estimated_cost()).bright_green());
+        }
+        println!();
+    };
+
+    print_rollups("Daily usage:", usage::rollup_by(&records, "%Y-%m-%d"), days);
+    print_rollups("Monthly usage:", usage::rollup_by(&records, "%Y-%m"), months);
+
+    let total = usage::total(&records);
+    println!("{} {} queries • {} tokens • {} avg • {} estimated",
+        "Total:".bright_yellow().bold(),
+        total.queries.to_string().bright_cyan(),
+        total.tokens.to_string().bright_magenta(),
+        format!("{}ms", total.average_ms()).bright_magenta(),
+        format!("${:.3}", total.estimated_cost()).bright_green());
+    println!("{}", format!("Cost estimated at ${} per query; responses served from Kagi's cache are free, so the actual cost may be lower.", usage::COST_PER_QUERY).dimmed());
+
+    Ok(())
+}
+
+async fn run_batch(session: &mut Session, inputs: Option<PathBuf>, output: Option<PathBuf>, checkpoint: Option<PathBuf>, resume: Option<PathBuf>) -> Result<()> {
+    let (checkpoint_path, state) = match (resume, inputs) {
+        (Some(path), _) => {
+            let state = batch::load_checkpoint(&path)?;
+            (path, state)
+        }
+        (None, Some(inputs)) => {
+            let path = checkpoint.unwrap_or_else(|| batch::default_checkpoint_path(&inputs));
+            if path.exists() {
+                anyhow::bail!("A checkpoint from an earlier run exists: continue it with `fastgpt batch --resume {}` or delete it to start over", path.display());
+            }
+            if let Some(output) = &output {
+                fs::write(output, b"").context(format!("Failed to create batch output: {}", output.display()))?;
+            }
+            let inputs = std::path::absolute(&inputs).context(format!("Invalid inputs path: {}", inputs.display()))?;
+            let output = output.as_deref().map(std::path::absolute).transpose().context("Invalid output path")?;
+            (path, batch::Checkpoint { inputs, output, completed: Vec::new() })
+        }
+        (None, None) => anyhow::bail!("Give an inputs file or --resume <checkpoint>"),
+    };
+
+    match batch::run(session, &checkpoint_path, state).await? {
+        batch::Outcome::Finished { answered, failed: 0 } => {
+            eprintln!("{} {} queries answered", "Done:".bright_green(), answered.to_string().bright_cyan());
+        }
+        batch::Outcome::Finished { answered, failed } => {
+            eprintln!("{} {} queries answered, {} failed", "Done:".bright_yellow(), answered.to_string().bright_cyan(), failed.to_string().bright_red());
+            eprintln!("{}", format!("Retry the failed queries with: fastgpt batch --resume {}", checkpoint_path.display()).dimmed());
+        }
+        batch::Outcome::Stopped { checkpoint, remaining, reason } => {
+            eprintln!("{} {} ({} queries left)", "Stopped:".bright_yellow(), reason, remaining);
+            eprintln!("{}", format!("Continue with: fastgpt batch --resume {}", checkpoint.display()).dimmed());
+            std::process::exit(batch::INTERRUPTED_EXIT_CODE);
+        }
+    }
+
+    Ok(())
+}
+
+fn write_usage_report(days: u64, format: ReportFormat, output: Option<PathBuf>) -> Result<()> {
+    let report = report::build(days)?;
+    let content = match format {
+        ReportFormat::Markdown => report.markdown(),
+        ReportFormat::Html => report.html(),
+    };
+
+    write_report(&content, output)
+}
+
+fn run_meta_command(args: &str, session: &mut Session) -> Result<()> {
+    let (action, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+
+    match action {
+        "" | "list" => {
+            if session.metadata.is_empty() {
+                println!("{}", "No metadata on this session. Use /meta set <key=value>.".dimmed());
+                return Ok(());
+            }
+            println!("{}", "Session metadata:".bright_yellow().bold());
+            for (key, value) in &session.metadata {
+                println!("  {} = {}", key.bright_cyan(), value);
+            }
+            return Ok(());
+        }
+        "set" => {
+            let pairs = rest
+                .split_whitespace()
+                .map(sessions::parse_metadata)
+                .collect::<Result<Vec<_>>>()?;
+            if pairs.is_empty() {
+                anyhow::bail!("Usage: /meta set <key=value> [key=value ...]");
+            }
+            for (key, value) in pairs {
+                println!("{} {} = {}", "Set:".bright_green(), key.bright_cyan(), value);
+                session.metadata.insert(key, value);
+            }
+        }
+        "unset" => {
+            let keys: Vec<&str> = rest.split_whitespace().collect();
+            if keys.is_empty() {
+                anyhow::bail!("Usage: /meta unset <key> [key ...]");
+            }
+            for key in keys {
+                if session.metadata.remove(key).is_none() {
+                    anyhow::bail!("No metadata key {}", key);
+                }
+                println!("{} {}", "Removed:".bright_yellow(), key.bright_cyan());
+            }
+        }
+        _ => anyhow::bail!("Usage: /meta [list|set <key=value>...|unset <key>...]"),
+    }
+
+    if session.save_sessions {
+        sessions::set_metadata(&session.id, &session.metadata)?;
+    } else {
+        println!("{}", "Saving is off, so this metadata is kept for this session only (turn it on with /save on).".dimmed());
+    }
+    Ok(())
+}
+
+fn run_snippets_command(args: &str, snippets: &mut snippets::Snippets, last_query: Option<&str>) -> Result<()> {
+    let (action, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let (name, text) = rest.trim().split_once(char::is_whitespace).unwrap_or((rest.trim(), ""));
+    let text = text.trim();
+
+    match action {
+        "" | "list" => {
+            if snippets.is_empty() {
+                println!("{}", "No snippets saved. Use /snippets save <name> <text>.".dimmed());
+                return Ok(());
+            }
+            println!("{}", "Snippets:".bright_yellow().bold());
+            for (name, snippet) in snippets.iter() {
+                let preview: String = snippet.chars().take(60).collect();
+                let ellipsis = if snippet.chars().count() > 60 { "..." } else { "" };
+                println!("  {} {}{}", format!("#{}", name).bright_cyan(), preview.dimmed(), ellipsis.dimmed());
+            }
+        }
+        "save" => {
+            if !snippets::is_valid_name(name) {
+                anyhow::bail!("Usage: /snippets save <name> [text] (names start with a letter and use letters, digits, - or _)");
+            }
+            let snippet = match (text, last_query) {
+                ("", Some(query)) => query.to_string(),
+                ("", None) => anyhow::bail!("Nothing to save: give the snippet text or ask a question first"),
+                (text, _) => text.to_string(),
+            };
+            snippets.insert(name.to_string(), snippet);
+            snippets::save(snippets)?;
+            println!("{} {}", "Saved snippet:".bright_green(), format!("#{}", name).bright_cyan());
+        }
+        "show" => {
+            let snippet = snippets.get(name).context(format!("No snippet named {}", name))?;
+            println!("{}", format!("#{}", name).bright_cyan());
+            println!("{}", snippet);
+        }
+        "remove" => {
+            if snippets.remove(name).is_none() {
+                anyhow::bail!("No snippet named {}", name);
+            }
+            snippets::save(snippets)?;
+            println!("{} {}", "Removed snippet:".bright_yellow(), format!("#{}", name).bright_cyan());
+        }
+        _ => anyhow::bail!("Usage: /snippets [list|save <name> [text]|show <name>|remove <name>]"),
+    }
+
+    Ok(())
+}
+
+fn command_aliases(config: &Config) -> BTreeMap<String, String> {
+    config.aliases
+        .iter()
+        .map(|(alias, expansion)| (format!("/{}", alias.trim().trim_start_matches('/')), expansion.trim().to_string()))
+        .filter(|(alias, expansion)| alias.len() > 1 && !expansion.is_empty() && !COMMANDS.iter().any(|cmd| cmd.trim_end() == alias))
+        .collect()
+}
+
+fn expand_alias(aliases: &BTreeMap<String, String>, input: &str) -> Option<String> {
+    let (word, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    let expansion = aliases.get(word)?;
+    Some(if rest.is_empty() { expansion.clone() } else { format!("{} {}", expansion, rest) })
+}
+
+fn closest_command(input: &str) -> Option<&'static str> {
+    let word = input.split_whitespace().next()?;
+    if !word.starts_with('/') || COMMANDS.iter().any(|cmd| cmd.trim_end() == word) {
+        return None;
+    }
+
+    COMMANDS
+        .iter()
+        .map(|cmd| cmd.trim_end())
+        .map(|cmd| (strsim::damerau_levenshtein(word, cmd), cmd))
+        .filter(|(distance, cmd)| *distance <= 2.max(cmd.len() / 4))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, cmd)| cmd)
+}
+
+fn confirm_correction(rl: &mut Editor<FastGPTHelper, DefaultHistory>, input: &str, command: &str) -> Option<String> {
+    let rest = input.split_once(char::is_whitespace).map(|(_, rest)| rest.trim_start());
+    let corrected = match rest {
+        Some(rest) => format!("{} {}", command, rest),
+        None => command.to_string(),
+    };
+
+    println!("{} {}", "Did you mean".bright_yellow(), format!("{}?", command).bright_cyan());
+    match rl.readline("Press Enter to run it, or type n to cancel: ") {
+        Ok(answer) if answer.trim().is_empty() || answer.trim().eq_ignore_ascii_case("y") => Some(corrected),
+        _ => None,
+    }
+}
+
+fn confirm_send(session: &Session, rl: &mut Editor<FastGPTHelper, DefaultHistory>, input: &str) -> bool {
+    let (prompt, window) = session.build_contextual_query(input);
+    
+    println!();
+    println!("{}", "About to send:".bright_yellow().bold());
+    println!("{} {}", "Query:".dimmed(), input.paint(Role::Query));
+    print_context_window(&window);
+    println!("{} {}", "Prompt size:".dimmed(), format!("~{} tokens", session.tokenizer.count(&prompt)).bright_magenta());
+    
+    match rl.readline("Press Enter to send, or type n to cancel: ") {
+        Ok(answer) => answer.trim().is_empty(),
+        Err(_) => false,
+    }
+}
+
+fn print_duplicate_notice(session: &Session, query: &str, number: usize, similarity: f32) {
+    println!();
+    println!("{} {}", ">".paint(Role::Banner).bold(), query.paint(Role::Query));
+    println!();
+    println!("{} This answer is {:.0}% the same as the answer to {}: {}",
+        "Duplicate:".bright_yellow().bold(), similarity * 100.0,
+        format!("Q{}", number).bright_cyan(), session.history[number - 1].query.paint(Role::Query));
+    println!("{}", "Not shown again; see it with /history, or /retry --no-cache for a fresh answer.".dimmed());
+}
+
+fn content_hash(content: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct CommandOutput {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: Option<std::process::ExitStatus>,
+    truncated: bool,
+}
+
+fn run_shell_command(command: &str) -> Result<CommandOutput> {
+    use std::process::{Command, Stdio};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(format!("Failed to run: {}", command))?;
+
+    let truncated = Arc::new(AtomicBool::new(false));
+    let capture = |mut pipe: Box<dyn Read + Send>| {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let (shared, truncated) = (buffer.clone(), truncated.clone());
+        let reader = std::thread::spawn(move || {
+            let mut chunk = [0; 8192];
+            while let Ok(read @ 1..) = pipe.read(&mut chunk) {
+                let mut buffer = shared.lock().unwrap();
+                let room = MAX_COMMAND_OUTPUT_BYTES.saturating_sub(buffer.len());
+                if read > room {
+                    truncated.store(true, Ordering::Relaxed);
+                }
+                buffer.extend_from_slice(&chunk[..read.min(room)]);
+            }
+        });
+        (buffer, reader)
+    };
+    let (stdout, stdout_reader) = capture(Box::new(child.stdout.take().context("Failed to capture the command's output")?));
+    let (stderr, stderr_reader) = capture(Box::new(child.stderr.take().context("Failed to capture the command's output")?));
+
+    let deadline = std::time::Instant::now() + COMMAND_TIMEOUT;
+    let mut status = None;
+    while status.is_none() || !(stdout_reader.is_finished() && stderr_reader.is_finished()) {
+        if status.is_none() {
+            status = child.try_wait().context(format!("Failed to wait for: {}", command))?;
+        }
+        if std::time::Instant::now() >= deadline {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    if status.is_none() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    let take = |buffer: Arc<Mutex<Vec<u8>>>| std::mem::take(&mut *buffer.lock().unwrap());
+    Ok(CommandOutput {
+        stdout: take(stdout),
+        stderr: take(stderr),
+        status,
+        truncated: truncated.load(Ordering::Relaxed),
+    })
+}
+
+fn review_file_changes(session: &mut Session, rl: &mut Editor<FastGPTHelper, DefaultHistory>) {
+    if session.live_contexts {
+        return;
+    }
+    for (i, current, elided) in session.changed_files() {
+        let file = &mut session.file_contexts[i];
+        println!();
+        println!("{} {}", "Changed since it was added:".bright_yellow().bold(), file.path.display().to_string().bright_cyan());
+        output::print_context_diff(&file.content, &current, CONTEXT_DIFF_LINES);
+
+        let refresh = match rl.readline("Refresh it in the context? [Y/n]: ") {
+            Ok(answer) => !answer.trim().eq_ignore_ascii_case("n"),
+            Err(_) => false,
+        };
+        if refresh {
+            file.size = fs::metadata(&file.path).map_or(current.len(), |metadata| metadata.len() as usize);
+            file.content = current;
+            file.elided_lines = elided;
+            file.declined_change = None;
+            println!("{}", "Context updated.".bright_green());
+        } else {
+            file.declined_change = Some(content_hash(&current));
+            println!("{}", "Keeping the version that was added.".dimmed());
+        }
+    }
+}
+
+fn confirm_paste(rl: &mut Editor<FastGPTHelper, DefaultHistory>, input: &str) -> bool {
+    println!("{} {} lines captured as a single question", "Pasted:".bright_yellow().bold(), input.lines().count());
+
+    match rl.readline("Press Enter to send, or type n to cancel: ") {
+        Ok(answer) => answer.trim().is_empty(),
+        Err(_) => false,
+    }
+}
+
+fn edit_text(text: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL").ok()
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+
+    let mut file = tempfile::Builder::new().prefix("fastgpt-edit-").suffix(".md").tempfile()
+        .context("Failed to create a temporary file to edit")?;
+    std::io::Write::write_all(&mut file, format!("{}\n", text).as_bytes())
+        .context(format!("Failed to write file: {}", file.path().display()))?;
+    let path = file.into_temp_path();
+
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().context("Editor command is empty")?;
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(&path)
+        .status()
+        .context(format!("Failed to start editor: {}", editor));
+
+    let edited = fs::read_to_string(&path);
+    if !status?.success() {
+        anyhow::bail!("Editor exited with an error");
+    }
+    Ok(edited.context(format!("Failed to read file: {}", path.display()))?.trim().to_string())
+}
+
+async fn run_interactive_session(mut session: Session, mut tutorial: Option<tutorial::Tutorial>) -> Result<()> {
+    let editor_config = rustyline::Config::builder()
+        .edit_mode(session.editor.edit_mode)
+        .completion_type(session.editor.completion_type)
+        .bell_style(session.editor.bell_style)
+        .bracketed_paste(true)
+        .max_history_size(READLINE_HISTORY_SIZE)?
+        .build();
+    let mut rl: Editor<FastGPTHelper, _> = Editor::with_config(editor_config)?;
+    rl.set_helper(Some(FastGPTHelper::new(session.aliases.clone())));
+
+    let history_path = if session.ephemeral || session.demo { None } else { readline_history_path() };
+    if let Some(path) = &history_path {
+        if path.exists() {
+            if let Err(e) = rl.load_history(path) {
+                eprintln!("{} Failed to load readline history: {}", "Warning:".bright_yellow(), e);
+            }
+        }
+    }
+
+    let mut last_table: Option<table::Table> = None;
+    let mut snippets = snippets::load().unwrap_or_else(|e| {
+        eprintln!("{} {}", "Warning:".bright_yellow(), e);
+        snippets::Snippets::new()
+    });
+
+    session.print_banner();
+    if let Some(tutorial) = &tutorial {
+        tutorial.print_intro();
+    }
+
+    let mut watcher = None;
+    loop {
+        if session.watch_contexts && watcher.is_none() {
+            match watch::ContextWatcher::new(rl.create_external_printer().ok()) {
+                Ok(created) => watcher = Some(created),
+                Err(e) => {
+                    eprintln!("{} {:#}", "Warning:".bright_yellow(), e);
+                    session.watch_contexts = false;
+                }
+            }
+        } else if !session.watch_contexts {
+            watcher = None;
+        }
+        if let Some(watcher) = &mut watcher {
+            let files = session.file_contexts.iter().filter(|file| file.enabled && file.source == ContextSource::File).map(|file| file.path.as_path());
+            watcher.sync(files, session.live_contexts);
+        }
+
+        if let Some(current) = &mut tutorial {
+            if current.is_finished() {
+                current.print_outro();
+                tutorial = None;
+            } else {
+                current.print_step();
+            }
+        }
+
+        if let Some(helper) = rl.helper_mut() {
+            helper.attached = session.file_contexts.iter().map(|f| f.path.display().to_string()).collect();
+        }
+        match rl.readline(&session.render_prompt()) {
+            Ok(line) => {
+                let input = line.trim();
+                
+                if input.is_empty() {
+                    continue;
+                }
+
+                rl.add_history_entry(input).ok();
+
+                let pasted = input.contains('\n');
+                if pasted && !session.confirm_before_send && !confirm_paste(&mut rl, input) {
+                    println!("{}", "Cancelled.".bright_yellow());
+                    println!();
+                    continue;
+                }
+
+                let expanded = expand_alias(&session.aliases, input).filter(|_| !pasted);
+                let input = expanded.as_deref().unwrap_or(input);
+
+                let corrected = closest_command(input)
+                    .filter(|_| !pasted)
+                    .and_then(|command| confirm_correction(&mut rl, input, command));
+                let input = corrected.as_deref().unwrap_or(input);
+
+                let composed = if input == "/compose" {
+                    match edit_text("") {
+                        Ok(text) if text.is_empty() => {
+                            println!("{}", "Empty prompt, nothing sent.".bright_yellow());
+                            println!();
+                            continue;
+                        }
+                        Ok(text) => {
+                            rl.add_history_entry(text.as_str()).ok();
+                            println!("{} {}", "Composed:".dimmed(), format!("{} lines", text.lines().count()).bright_cyan());
+                            Some(text)
+                        }
+                        Err(e) => {
+                            println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                            println!();
+                            continue;
+                        }
+                    }
+                } else {
+                    None
+                };
+                let input = composed.as_deref().unwrap_or(input);
+
+                if let Some(tutorial) = &mut tutorial {
+                    tutorial.observe(input);
+                }
+
+                match input {
+                    "/exit" | "/quit" => {
+                        session.print_summary();
+                        println!("{}", "Goodbye!".bright_green());
+                        break;
+                    }
+                    "/clear" => {
+                        session.clear_history();
+                        continue;
+                    }
+                    "/history" => {
+                        session.show_history();
+                        println!();
+                        continue;
+                    }
+                    "/help" => {
+                        println!("{}", "Available commands:".bright_yellow().bold());
+                        print_commands(&session.aliases);
+                        println!();
+                        continue;
+                    }
+                    _ if input == "/add-file" || input == "/pick" || input.starts_with("/pick ") => {
+                        let root = input.strip_prefix("/pick").map(str::trim).filter(|dir| !dir.is_empty()).unwrap_or(".");
+                        println!();
+                        match picker::pick(Path::new(root)) {
+                            Ok(paths) if paths.is_empty() => println!("{}", "Nothing selected.".dimmed()),
+                            Ok(paths) => {
+                                for path in paths {
+                                    let path = path.display().to_string();
+                                    match session.add_file_context(&path, AddFileOptions::default()) {
+                                        Ok(_) => println!("{} {}", "Added:".bright_green(), path.bright_cyan()),
+                                        Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                                    }
+                                }
+                            }
+                            Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input == "/add-clipboard" || input.starts_with("/add-clipboard ") => {
+                        let added = parse_add_file_args(input.trim_start_matches("/add-clipboard"))
+                            .and_then(|(name, options)| session.add_clipboard_context(Some(name.as_str()).filter(|name| !name.is_empty()), options));
+                        match added {
+                            Ok(file) => println!("{} {} {}", "Added:".bright_green(), file.path.display().to_string().bright_cyan(),
+                                format!("({} lines, ~{} tokens)", file.content.lines().count(), session.tokenizer.count(&file.content)).dimmed()),
+                            Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                        }
+                        continue;
+                    }
+                    _ if input == "/add-cmd" || input.starts_with("/add-cmd ") => {
+                        match session.add_command_context(input.trim_start_matches("/add-cmd")) {
+                            Ok((file, replaced, status)) => {
+                                println!("{} {} {}", if replaced { "Updated:" } else { "Added:" }.bright_green(),
+                                    file.path.display().to_string().bright_cyan(),
+                                    format!("({} lines, ~{} tokens)", file.content.lines().count(), session.tokenizer.count(&file.content)).dimmed());
+                                match status {
+                                    Some(status) if !status.success() =>
+                                        println!("{} The command failed ({}); its output was added anyway", "Warning:".bright_yellow(), status),
+                                    Some(_) => {}
+                                    None => println!("{} The command was still running after {}s and was killed; its output so far was added",
+                                        "Warning:".bright_yellow(), COMMAND_TIMEOUT.as_secs()),
+                                }
+                            }
+                            Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                        }
+                        continue;
+                    }
+                    _ if input == "/add-diff" || input.starts_with("/add-diff ") => {
+                        match session.add_diff_context(input.trim_start_matches("/add-diff")) {
+                            Ok((file, replaced)) => println!("{} {} {}", if replaced { "Updated:" } else { "Added:" }.bright_green(),
+                                file.path.display().to_string().bright_cyan(),
+                                format!("({} changed, {} lines, ~{} tokens)",
+                                    match git::changed_files(&file.content) { 1 => "1 file".to_string(), n => format!("{} files", n) },
+                                    file.content.lines().count(), session.tokenizer.count(&file.content)).dimmed()),
+                            Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                        }
+                        continue;
+                    }
+                    _ if input == "/add-repo-map" || input.starts_with("/add-repo-map ") => {
+                        let added = parse_add_file_args(input.trim_start_matches("/add-repo-map"))
+                            .and_then(|(dir, options)| session.add_repo_map_context(Path::new(if dir.is_empty() { "." } else { &dir }), options));
+                        match added {
+                            Ok((file, mapped)) => println!("{} {} {}", "Added:".bright_green(), file.path.display().to_string().bright_cyan(),
+                                format!("({} files, {} declarations, ~{} tokens)", mapped,
+                                    file.content.lines().filter(|line| line.starts_with(' ')).count(),
+                                    session.tokenizer.count(&file.content)).dimmed()),
+                            Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                        }
+                        continue;
+                    }
+                    _ if input == "/add-url" || input.starts_with("/add-url ") => {
+                        match parse_add_file_args(input.trim_start_matches("/add-url")) {
+                            Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                            Ok((url, _)) if url.is_empty() => println!("{} Please specify a URL: /add-url <url> [--as \"label\"]", "Error:".paint(Role::Error).bold()),
+                            Ok((url, options)) => match session.add_url_context(&url, options).await {
+                                Ok(file) => {
+                                    let title = file.label.map(|label| format!(" ({})", label)).unwrap_or_default();
+                                    println!("{} {}{} {}", "Added:".bright_green(), url.bright_cyan(), title,
+                                        format!("~{} tokens", session.tokenizer.count(&file.content)).dimmed());
+                                }
+                                Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                            },
+                        }
+                        continue;
+                    }
+                    _ if input.starts_with("/add-file ") => {
+                        match parse_add_file_args(input.trim_start_matches("/add-file ")) {
+                            Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                            Ok((path, _)) if path.is_empty() => println!("{} Please specify a file path: /add-file <path> [--as \"label\"] [--recursive] [--depth N] [--max-files N] [--lossy] [--truncate head|tail|ends|full] [--all-files]", "Error:".paint(Role::Error).bold()),
+                            Ok((pattern, options)) if is_glob(&pattern) => match session.add_glob_context(&pattern, options) {
+                                Ok((matched, added)) => {
+                                    println!();
+                                    println!("{} {} {}", "Added:".bright_green(), pattern.bright_cyan(),
+                                        format!("({} matched, {} added)", matched, added).dimmed());
+                                }
+                                Err(e) => {
+                                    println!();
+                                    println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                }
+                            },
+                            Ok((file_path, options)) => match session.add_file_context(&file_path, options) {
+                                Ok(count) => {
+                                    println!();
+                                    if Path::new(&file_path).is_dir() || archives::kind_of(Path::new(&file_path)).is_some() {
+                                        println!("{} {} {}", "Added:".bright_green(), file_path.bright_cyan(), format!("({} files)", count).dimmed());
+                                    } else {
+                                        println!("{} {}", "Added:".bright_green(), file_path.bright_cyan());
+                                    }
+                                }
+                                Err(e) => {
+                                    println!();
+                                    println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                }
+                            }
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input.starts_with("/remove-file ") => {
+                        let file_path = input.trim_start_matches("/remove-file ").trim();
+                        if file_path.is_empty() {
+                            println!("{} Please specify a file path: /remove-file <path>", "Error:".paint(Role::Error).bold());
+                        } else {
+                            match session.remove_file_context(file_path) {
+                                Ok(()) => {
+                                    println!();
+                                    println!("{} {}", "Removed:".bright_yellow(), file_path.bright_cyan());
+                                }
+                                Err(e) => {
+                                    println!();
+                                    println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                }
+                            }
+                        }
+                        println!();
+                        continue;
+                    }
+                    "/list-files" => {
+                        session.list_file_contexts();
+                        println!();
+                        continue;
+                    }
+                    _ if input.starts_with("/disable-file ") || input.starts_with("/enable-file ") => {
+                        let enable = input.starts_with("/enable-file ");
+                        let file_path = input.split_once(' ').map(|(_, path)| path.trim()).unwrap_or_default();
+                        if file_path.is_empty() {
+                            println!("{} Please specify a file path", "Error:".paint(Role::Error).bold());
+                        } else {
+                            match session.set_file_context_enabled(file_path, enable) {
+                                Ok(()) => {
+                                    println!();
+                                    if enable {
+                                        println!("{} {}", "Enabled:".bright_green(), file_path.bright_cyan());
+                                    } else {
+                                        println!("{} {}", "Disabled:".bright_yellow(), file_path.bright_cyan());
+                                    }
+                                }
+                                Err(e) => {
+                                    println!();
+                                    println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                }
+                            }
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input.starts_with("/move-file ") => {
+                        let args: Vec<&str> = input.trim_start_matches("/move-file ").split_whitespace().collect();
+                        let positions = match args.as_slice() {
+                            [from, to] => from.parse::<usize>().ok().zip(to.parse::<usize>().ok()),
+                            _ => None,
+                        };
+                        match positions {
+                            Some((from, to)) => match session.move_file_context(from, to) {
+                                Ok(()) => {
+                                    println!();
+                                    println!("{} #{} to position {}", "Moved:".bright_green(), from.to_string().bright_cyan(), to.to_string().bright_cyan());
+                                }
+                                Err(e) => {
+                                    println!();
+                                    println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                }
+                            },
+                            None => println!("{} Usage: /move-file <n> <position>", "Error:".paint(Role::Error).bold()),
+                        }
+                        println!();
+                        continue;
+                    }
+                    "/clear-files" => {
+                        session.clear_file_contexts();
+                        println!();
+                        println!("{} All file contexts cleared.", "Cleared:".bright_yellow());
+                        println!();
+                        continue;
+                    }
+                    _ if input == "/rate" || input.starts_with("/rate ") => {
+                        let args = input.trim_start_matches("/rate").trim();
+                        let (score, comment) = match args.split_once(char::is_whitespace) {
+                            Some((score, comment)) => (score, Some(comment.trim().to_string())),
+                            None => (args, None),
+                        };
+                        println!();
+                        match score.parse::<u8>() {
+                            Ok(score @ 1..=5) if session.save_sessions => {
+                                match sessions::rate_last_exchange(&session.id, score, comment) {
+                                    Ok(exchange) => println!("{} exchange {} as {}/5", "Rated:".bright_green(), exchange.to_string().bright_cyan(), score.to_string().bright_cyan()),
+                                    Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                                }
+                            }
+                            Ok(1..=5) => println!("{} Ratings are stored with saved sessions, and saving is off (turn it on with /save on).", "Error:".paint(Role::Error).bold()),
+                            _ => println!("{} Usage: /rate <1-5> [comment]", "Error:".paint(Role::Error).bold()),
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input == "/meta" || input.starts_with("/meta ") => {
+                        println!();
+                        if let Err(e) = run_meta_command(input.trim_start_matches("/meta").trim(), &mut session) {
+                            println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input == "/snippets" || input.starts_with("/snippets ") => {
+                        let last_query = session.history.last().map(|entry| entry.query.as_str());
+                        println!();
+                        if let Err(e) = run_snippets_command(input.trim_start_matches("/snippets").trim(), &mut snippets, last_query) {
+                            println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input == "/merge" || input.starts_with("/merge ") => {
+                        let numbers: Result<Vec<usize>, _> = input.trim_start_matches("/merge").split_whitespace().map(str::parse).collect();
+                        let result = match numbers {
+                            Ok(numbers) => session.merge_answers(&numbers).await,
+                            Err(_) => Err(anyhow::anyhow!("Usage: /merge <n> <m> ... (answer numbers as in /history)")),
+                        };
+                        match result {
+                            Ok((query, response)) => {
+                                output::print_response(&response, &query, session.output, session.show_references, session.active_pager())?;
+                                println!();
+                            }
+                            Err(e) => {
+                                println!();
+                                println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                println!();
+                            }
+                        }
+                        continue;
+                    }
+                    _ if input == "/copy-code" || input.starts_with("/copy-code ") => {
+                        let arg = input.trim_start_matches("/copy-code").trim();
+                        println!();
+                        match if arg.is_empty() { Ok(1) } else { arg.parse::<usize>() } {
+                            Ok(number) => {
+                                if let Err(e) = session.copy_code_block(number) {
+                                    println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                }
+                            }
+                            Err(_) => println!("{} Usage: /copy-code [n]", "Error:".paint(Role::Error).bold()),
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input == "/save-response" || input.starts_with("/save-response ") => {
+                        println!();
+                        match parse_save_response_args(input.trim_start_matches("/save-response")) {
+                            Ok((path, _)) if path.is_empty() => {
+                                println!("{} Usage: /save-response <path> [--append]", "Error:".paint(Role::Error).bold());
+                            }
+                            Ok((path, append)) => match session.save_response(Path::new(&path), append) {
+                                Ok(()) => println!("{} {}", if append { "Appended to" } else { "Saved to" }.bright_green(), path.bright_cyan()),
+                                Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                            },
+                            Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input.starts_with("/table --csv") => {
+                        let path = input.trim_start_matches("/table --csv").trim();
+                        println!();
+                        match (&last_table, path.is_empty()) {
+                            (_, true) => println!("{} Usage: /table --csv <path>", "Error:".paint(Role::Error).bold()),
+                            (None, false) => println!("{} No table yet: ask one with /table <question>", "Error:".paint(Role::Error).bold()),
+                            (Some(table), false) => match fs::write(path, table.to_csv()) {
+                                Ok(()) => println!("{} {}", "Table written to".bright_green(), path.bright_cyan()),
+                                Err(e) => println!("{} Failed to write {}: {}", "Error:".paint(Role::Error).bold(), path, e),
+                            },
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input == "/table" || input.starts_with("/table ") => {
+                        let question = input.trim_start_matches("/table").trim();
+                        if question.is_empty() {
+                            println!();
+                            println!("{} Usage: /table <question> or /table --csv <path>", "Error:".paint(Role::Error).bold());
+                            println!();
+                            continue;
+                        }
+                        let question = snippets::expand(question, &snippets);
+                        match session.ask_question(&table::table_query(&question)).await {
+                            Ok(response) => match table::parse(&response.data.output) {
+                                Some(table) => {
+                                    println!();
+                                    println!("{} {}", ">".paint(Role::Banner).bold(), question.paint(Role::Query));
+                                    println!();
+                                    println!("{}", table.render());
+                                    println!();
+                                    println!("{}", format!("{} rows • /table --csv <path> to export", table.rows.len()).dimmed());
+                                    println!();
+                                    last_table = Some(table);
+                                }
+                                None => {
+                                    output::print_response(&response, &question, session.output, session.show_references, session.active_pager())?;
+                                    println!("{}", "The answer did not contain a table.".bright_yellow());
+                                    println!();
+                                }
+                            },
+                            Err(e) => {
+                                println!();
+                                println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                println!();
+                            }
+                        }
+                        continue;
+                    }
+                    _ if input == "/extract-code" || input.starts_with("/extract-code ") => {
+                        let dir = input.trim_start_matches("/extract-code").trim().trim_matches(|c| c == '"' || c == '\'');
+                        println!();
+                        match session.history.last() {
+                            _ if dir.is_empty() => println!("{} Usage: /extract-code <dir>", "Error:".paint(Role::Error).bold()),
+                            None => println!("{} No answer yet: ask a question first", "Error:".paint(Role::Error).bold()),
+                            Some(entry) => match extract::extract_code_blocks(&entry.response, Path::new(dir)) {
+                                Ok(paths) => {
+                                    println!("{} {} files:", "Extracted".bright_green(), paths.len().to_string().bright_cyan());
+                                    for path in paths {
+                                        println!("  {}", path.display().to_string().bright_cyan());
+                                    }
+                                }
+                                Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                            },
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input == "/export" || input.starts_with("/export ") => {
+                        let args = input.trim_start_matches("/export").trim();
+                        let (format, path) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                        let path = path.trim().trim_matches(|c| c == '"' || c == '\'');
+                        println!();
+                        let result = match format {
+                            _ if path.is_empty() => Err(anyhow::anyhow!("Usage: /export <html|openai|graph> <path>")),
+                            "html" => export::html(&session.history, &session.id),
+                            "openai" => export::openai(&session.history),
+                            "graph" => export::graph(&session.history, &session.file_contexts, &session.id),
+                            other => Err(anyhow::anyhow!("Unknown export format '{}' (supported: html, openai, graph)", other)),
+                        };
+                        match result.and_then(|content| fs::write(path, content).context(format!("Failed to write file: {}", path))) {
+                            Ok(()) => println!("{} {}", "Exported to".bright_green(), path.bright_cyan()),
+                            Err(e) => println!("{} {}", "Error:".paint(Role::Error).bold(), e),
+                        }
+                        println!();
+                        continue;
+                    }
+                    "/retry" | "/retry --no-cache" => {
+                        match session.retry_last_question(input.ends_with("--no-cache")).await {
+                            Ok((query, response)) => {
+                                output::print_response(&response, &query, session.output, session.show_references, session.active_pager())?;
+                                println!();
+                            }
+                            Err(e) => {
+                                println!();
+                                println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                println!();
+                            }
+                        }
+                        continue;
+                    }
+                    "/edit" => {
+                        let Some(last_query) = session.last_query.clone() else {
+                            println!("{} Nothing to edit yet: ask a question first", "Error:".paint(Role::Error).bold());
+                            println!();
+                            continue;
+                        };
+                        let query = match edit_text(&last_query) {
+                            Ok(query) if query.is_empty() || query == last_query => {
+                                println!("{}", "Question unchanged, nothing sent.".bright_yellow());
+                                println!();
+                                continue;
+                            }
+                            Ok(query) => query,
+                            Err(e) => {
+                                println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                println!();
+                                continue;
+                            }
+                        };
+                        rl.add_history_entry(query.as_str()).ok();
+                        match session.replace_last_question(&query).await {
+                            Ok(response) => {
+                                output::print_response(&response, &query, session.output, session.show_references, session.active_pager())?;
+                                println!();
+                            }
+                            Err(e) => {
+                                println!();
+                                println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                println!();
+                            }
+                        }
+                        continue;
+                    }
+                    "/verify" => {
+                        match session.verify_last_answer().await {
+                            Ok((query, original, response)) => {
+                                output::print_verification(&original, &response, &query, session.show_references);
+                                println!();
+                            }
+                            Err(e) => {
+                                println!();
+                                println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                println!();
+                            }
+                        }
+                        continue;
+                    }
+                    _ if input == "/open" || input.starts_with("/open ") => {
+                        println!();
+                        match input.trim_start_matches("/open").trim().parse::<usize>() {
+                            Ok(number) => {
+                                if let Err(e) = session.open_reference(number) {
+                                    println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                }
+                            }
+                            Err(_) => println!("{} Usage: /open <reference number>", "Error:".paint(Role::Error).bold()),
+                        }
+                        println!();
+                        continue;
+                    }
+                    "/confirm" | "/confirm on" | "/confirm off" => {
+                        match input {
+                            "/confirm on" => session.confirm_before_send = true,
+                            "/confirm off" => session.confirm_before_send = false,
+                            _ => {}
+                        }
+                        println!();
+                        if session.confirm_before_send {
+                            println!("{} on (queries are shown for confirmation before sending)", "Confirm:".bright_green());
+                        } else {
+                            println!("{} off", "Confirm:".bright_yellow());
+                        }
+                        println!();
+                        continue;
+                    }
+                    "/save" | "/save on" | "/save off" => {
+                        match input {
+                            "/save on" if session.demo || session.ephemeral => {
+                                println!("{} Sessions can't be saved in demo mode or when the data directory isn't writable", "Error:".paint(Role::Error).bold());
+                                continue;
+                            }
+                            "/save on" => session.save_sessions = true,
+                            "/save off" => session.save_sessions = false,
+                            _ => {}
+                        }
+                        println!();
+                        if session.save_sessions {
+                            println!("{} on (exchanges from now on are saved, including attached context)", "Saving:".bright_green());
+                        } else {
+                            println!("{} off (nothing from this session is written to disk)", "Saving:".bright_yellow());
+                        }
+                        println!();
+                        continue;
+                    }
+                    "/live" | "/live on" | "/live off" => {
+                        match input {
+                            "/live on" => session.live_contexts = true,
+                            "/live off" => session.live_contexts = false,
+                            _ => {}
+                        }
+                        println!();
+                        if session.live_contexts {
+                            println!("{} on (attached files are re-read before every question)", "Live contexts:".bright_green());
+                        } else {
+                            println!("{} off (changed files are shown for review before the next question)", "Live contexts:".bright_yellow());
+                        }
+                        println!();
+                        continue;
+                    }
+                    "/watch" | "/watch on" | "/watch off" => {
+                        match input {
+                            "/watch on" => session.watch_contexts = true,
+                            "/watch off" => session.watch_contexts = false,
+                            _ => {}
+                        }
+                        println!();
+                        if session.watch_contexts {
+                            println!("{} on (you'll be told when an attached file changes)", "Watching:".bright_green());
+                        } else {
+                            println!("{} off", "Watching:".bright_yellow());
+                        }
+                        println!();
+                        continue;
+                    }
+                    "/pager" | "/pager on" | "/pager off" => {
+                        match input {
+                            "/pager on" => session.paging = true,
+                            "/pager off" => session.paging = false,
+                            _ => {}
+                        }
+                        println!();
+                        if session.paging {
+                            println!("{} on ({})", "Pager:".bright_green(), session.pager.bright_cyan());
+                        } else {
+                            println!("{} off", "Pager:".bright_yellow());
+                        }
+                        println!();
+                        continue;
+                    }
+                    "/usage" => {
+                        session.show_usage();
+                        println!();
+                        continue;
+                    }
+                    "/balance" => {
+                        match session.check_balance().await {
+                            Ok(balance) => {
+                                println!();
+                                println!("{} ${:.3}", "Balance:".dimmed(), balance.to_string().bright_green());
+                            }
+                            Err(e) => {
+                                println!();
+                                println!("{} Failed to check balance: {}", "Error:".paint(Role::Error).bold(), e);
+                            }
+                        }
+                        println!();
+                        continue;
+                    }
+                    _ if input.starts_with('/') => {
+                        println!();
+                        println!("{} Unknown command: {}. Type /help for available commands.", 
+                            "Error:".paint(Role::Error).bold(), input.paint(Role::Error));
+                        println!();
+                        continue;
+                    }
+                    _ => {
+                        let expanded = snippets::expand(input, &snippets);
+                        let input = expanded.as_str();
+                        review_file_changes(&mut session, &mut rl);
+                        if let Some(watcher) = &watcher {
+                            watcher.clear_changes();
+                        }
+                        if session.confirm_before_send && !confirm_send(&session, &mut rl, input) {
+                            println!("{}", "Cancelled.".bright_yellow());
+                            println!();
+                            continue;
+                        }
+                        match session.ask_question(input).await {
+                            Ok(response) => {
+                                match session.duplicate_of_last_answer().filter(|_| session.output.is_human_readable()) {
+                                    Some((number, similarity)) => print_duplicate_notice(&session, input, number, similarity),
+                                    None => output::print_response(&response, input, session.output, session.show_references, session.active_pager())?,
+                                }
+                                if session.output.is_human_readable() {
+                                    if let Some(window) = &session.last_window {
+                                        print_context_window(window);
+                                    }
+                                }
+                                println!();
+                            }
+                            Err(e) => {
+                                println!();
+                                println!("{} {}", "Error:".paint(Role::Error).bold(), e);
+                                println!();
+                            }
+                        }
+                    }
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("{}", "Use /exit or /quit to exit.".bright_yellow());
+            }
+            Err(ReadlineError::Eof) => {
+                session.print_summary();
+                println!("{}", "Goodbye!".bright_green());
+                break;
+            }
+            Err(err) => {
+                println!("{} {:?}", "Error:".paint(Role::Error).bold(), err);
+                break;
+            }
+        }
+    }
+
+    if let Some(path) = &history_path {
+        if let Err(e) = rl.append_history(path) {
+            eprintln!("{} Failed to save readline history: {}", "Warning:".bright_yellow(), e);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_commands(aliases: &BTreeMap<String, String>) {
+    println!("  {} - Exit the session", "/exit or /quit".bright_cyan());
+    println!("  {} - Clear conversation history and screen", "/clear".bright_cyan());
+    println!("  {} - Show conversation history", "/history".bright_cyan());
+    println!("  {} - Add file(s) or directory to context", "/add-file <path> [--as label]".bright_cyan());
+    println!("  {} - Pick files to add with a fuzzy finder (also /add-file without a path)", "/pick [dir]".bright_cyan());
+    println!("  {} - Add the readable text of a web page to context", "/add-url <url> [--as label]".bright_cyan());
+    println!("  {} - Add the clipboard's text to context as a pseudo-file", "/add-clipboard [name] [--as label]".bright_cyan());
+    println!("  {} - Add an outline of a codebase's declarations to context", "/add-repo-map [dir]".bright_cyan());
+    println!("  {} - Add the output of git diff to context", "/add-diff [--staged] [ref]".bright_cyan());
+    println!("  {} - Run a shell command and add its output to context", "/add-cmd <command>".bright_cyan());
+    println!("  {} - Remove file from context", "/remove-file <path>".bright_cyan());
+    println!("  {} - List all files in context", "/list-files".bright_cyan());
+    println!("  {} - Move a context entry to a new position", "/move-file <n> <position>".bright_cyan());
+    println!("  {} - Exclude a file from queries but keep it listed", "/disable-file <path>".bright_cyan());
+    println!("  {} - Include a disabled file again", "/enable-file <path>".bright_cyan());
+    println!("  {} - Clear all file contexts", "/clear-files".bright_cyan());
+    println!("  {} - Check API balance", "/balance".bright_cyan());
+    println!("  {} - Show queries, tokens and cost for this session", "/usage".bright_cyan());
+    println!("  {} - Rate the last answer", "/rate <1-5> [comment]".bright_cyan());
+    println!("  {} - Show or tag this session with key=value metadata", "/meta [set <key=value>...|unset <key>...]".bright_cyan());
+    println!("  {} - Copy the nth code block (default: first) of the last answer", "/copy-code [n]".bright_cyan());
+    println!("  {} - Write the last answer as Markdown to a file", "/save-response <path> [--append]".bright_cyan());
+    println!("  {} - Ask for a table; export the last one with /table --csv <path>", "/table <question>".bright_cyan());
+    println!("  {} - Write each code block of the last answer to a file in dir", "/extract-code <dir>".bright_cyan());
+    println!("  {} - Export the conversation as a standalone HTML page", "/export html <path>".bright_cyan());
+    println!("  {} - Export the conversation as OpenAI chat messages JSON", "/export openai <path>".bright_cyan());
+    println!("  {} - Export the session structure as a Graphviz graph", "/export graph <path.dot>".bright_cyan());
+    println!("  {} - Ask the last question again and replace its answer", "/retry [--no-cache]".bright_cyan());
+    println!("  {} - Revise the last question in $EDITOR and resend it", "/edit".bright_cyan());
+    println!("  {} - Write a new question in $EDITOR and send it on save", "/compose".bright_cyan());
+    println!("  {} - Fact-check the last answer against a fresh web search", "/verify".bright_cyan());
+    println!("  {} - Combine earlier answers into one document", "/merge <n> <m> ...".bright_cyan());
+    println!("  {} - Open reference N of the last answer in the browser", "/open <n>".bright_cyan());
+    println!("  {} - Page answers taller than the terminal", "/pager [on|off]".bright_cyan());
+    println!("  {} - Confirm each query before it is sent", "/confirm [on|off]".bright_cyan());
+    println!("  {} - Save this session's exchanges to disk", "/save [on|off]".bright_cyan());
+    println!("  {} - Re-read attached files before every question", "/live [on|off]".bright_cyan());
+    println!("  {} - Report attached files that change on disk", "/watch [on|off]".bright_cyan());
+    println!("  {} - List, save, show or remove snippets; use #name in a question to insert one", "/snippets [save|show|remove]".bright_cyan());
+    println!("  {} - Show this help", "/help".bright_cyan());
+    for (alias, expansion) in aliases {
+        println!("  {} - Alias for {}", alias.bright_cyan(), expansion.bright_cyan());
+    }
+}
+
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '[']) && !Path::new(path).exists()
+}
+
+fn parse_add_file_args(args: &str) -> Result<(String, AddFileOptions)> {
+    let tokens = shell_words::split(args).context("Unmatched quote in arguments")?;
+    let mut path = None;
+    let mut options = AddFileOptions::default();
+    let mut i = 0;
+    while i < tokens.len() {
+        let value = |flag: &str, expected: &str| -> Result<&str> {
+            tokens.get(i + 1).map(String::as_str).context(format!("{} needs {}", flag, expected))
+        };
+        let number = |flag: &str| -> Result<usize> {
+            value(flag, "a number")?.parse().ok().context(format!("{} needs a number", flag))
+        };
+        match tokens[i].as_str() {
+            "-r" | "--recursive" => options.recursive = true,
+            "--depth" => {
+                options.depth = Some(number("--depth")?);
+                options.recursive = true;
+                i += 1;
+            }
+            "--max-files" => {
+                options.max_files = Some(number("--max-files")?);
+                i += 1;
+            }
+            "--lossy" => options.lossy = true,
+            "--all-files" => options.all_files = true,
+            "--minify" => options.minify = Some(true),
+            "--no-minify" => options.minify = Some(false),
+            "--truncate" => {
+                let strategy = value("--truncate", &format!("one of: {}", TruncateStrategy::NAMES.join(", ")))?;
+                options.truncate = Some(TruncateStrategy::parse(strategy)?);
+                i += 1;
+            }
+            "--as" => {
+                let label = value("--as", "a label")?.trim();
+                options.label = if label.is_empty() { None } else { Some(label.to_string()) };
+                i += 1;
+            }
+            flag if flag.starts_with('-') && flag.len() > 1 => anyhow::bail!("Unknown /add-file option: {}", flag),
+            token if path.is_none() => path = Some(token.to_string()),
+            token => anyhow::bail!("Unexpected argument '{}' (quote paths and labels that contain spaces)", token),
+        }
+        i += 1;
+    }
+
+    Ok((path.unwrap_or_default(), options))
+}
+
+fn parse_save_response_args(args: &str) -> Result<(String, bool)> {
+    let tokens = shell_words::split(args).context("Unmatched quote in arguments")?;
+    let mut path = None;
+    let mut append = false;
+    for token in tokens {
+        match token.as_str() {
+            "--append" => append = true,
+            flag if flag.starts_with('-') && flag.len() > 1 => anyhow::bail!("Unknown /save-response option: {}", flag),
+            _ if path.is_none() => path = Some(token),
+            _ => anyhow::bail!("Unexpected argument '{}' (quote paths that contain spaces)", token),
+        }
+    }
+
+    Ok((path.unwrap_or_default(), append))
+}
@@ -0,0 +1,412 @@
+use colored::*;
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::ops::Range;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use syntect::util::as_24_bit_terminal_escaped;
+use textwrap::core::display_width;
+
+use crate::theme::{self, Paint, Role};
+
+const LIST_INDENT: &str = "  ";
+
+pub struct CodeBlock {
+    pub language: String,
+    pub info: String,
+    pub code: String,
+}
+
+struct Table {
+    alignments: Vec<Alignment>,
+    rows: Vec<Vec<String>>,
+    row: Vec<String>,
+}
+
+#[derive(Default)]
+struct Renderer {
+    width: Option<usize>,
+    lines: Vec<String>,
+    inline: String,
+    strong: usize,
+    emphasis: usize,
+    strikethrough: usize,
+    link: Option<String>,
+    heading: Option<HeadingLevel>,
+    in_table_head: bool,
+    lists: Vec<Option<u64>>,
+    marker: Option<String>,
+    quote_depth: usize,
+    code: Option<(String, String)>,
+    table: Option<Table>,
+}
+
+pub fn render(text: &str, width: Option<usize>) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut renderer = Renderer {
+        width,
+        ..Default::default()
+    };
+    for event in Parser::new_ext(text, options) {
+        renderer.event(event);
+    }
+    renderer.flush();
+
+    while renderer.lines.last().is_some_and(|line| line.is_empty()) {
+        renderer.lines.pop();
+    }
+    renderer.lines.join("\n")
+}
+
+pub fn code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<CodeBlock> = None;
+
+    for event in Parser::new(text) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(language))) => {
+                current = Some(CodeBlock {
+                    language: language.split_whitespace().next().unwrap_or("").to_string(),
+                    info: language.to_string(),
+                    code: String::new(),
+                });
+            }
+            Event::Text(text) => {
+                if let Some(block) = &mut current {
+                    block.code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => blocks.extend(current.take()),
+            _ => {}
+        }
+    }
+
+    blocks
+}
+
+pub fn code_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut depth = 0;
+
+    for (event, range) in Parser::new(text).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                if depth == 0 {
+                    ranges.push(range);
+                }
+                depth += 1;
+            }
+            Event::End(TagEnd::CodeBlock) => depth -= 1,
+            Event::Code(_) if depth == 0 => ranges.push(range),
+            _ => {}
+        }
+    }
+
+    ranges
+}
+
+impl Renderer {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.code {
+                Some((_, code)) => code.push_str(&text),
+                None => self.push_text(&text),
+            },
+            Event::Code(code) => {
+                let styled = code.on_bright_black().bright_white().to_string();
+                self.inline.push_str(&styled);
+            }
+            Event::Html(html) | Event::InlineHtml(html) => self.push_text(html.trim_end_matches('\n')),
+            Event::SoftBreak | Event::HardBreak => self.inline.push('\n'),
+            Event::Rule => {
+                self.flush();
+                let width = self.width.unwrap_or(40).min(40);
+                self.lines.push("─".repeat(width).dimmed().to_string());
+                self.block_gap();
+            }
+            Event::TaskListMarker(done) => self.inline.push_str(if done { "[x] " } else { "[ ] " }),
+            Event::FootnoteReference(name) => self.push_text(&format!("[^{}]", name)),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading { level, .. } => {
+                self.flush();
+                self.heading = Some(level);
+                if !colored::control::SHOULD_COLORIZE.should_colorize() {
+                    self.inline.push_str(&format!("{} ", "#".repeat(level as usize)));
+                }
+            }
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+                let language = match kind {
+                    CodeBlockKind::Fenced(language) => language.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((language, String::new()));
+            }
+            Tag::List(start) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.marker = Some(marker);
+            }
+            Tag::Table(alignments) => {
+                self.flush();
+                self.table = Some(Table {
+                    alignments,
+                    rows: Vec::new(),
+                    row: Vec::new(),
+                });
+            }
+            Tag::TableHead => self.in_table_head = true,
+            Tag::Emphasis => self.emphasis += 1,
+            Tag::Strong => self.strong += 1,
+            Tag::Strikethrough => self.strikethrough += 1,
+            Tag::Link { dest_url, .. } => self.link = Some(dest_url.to_string()),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                self.flush();
+                if self.lists.is_empty() {
+                    self.block_gap();
+                }
+            }
+            TagEnd::Heading(_) => {
+                self.flush();
+                self.heading = None;
+                self.block_gap();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                if self.quote_depth == 0 {
+                    self.block_gap();
+                }
+            }
+            TagEnd::CodeBlock => {
+                if let Some((language, code)) = self.code.take() {
+                    let code_lines: Vec<&str> = code.lines().collect();
+                    let prefix = self.prefix(false);
+                    for line in highlight_code(&code_lines, &language).lines() {
+                        self.lines.push(format!("{}{}", prefix, line));
+                    }
+                }
+                if self.lists.is_empty() {
+                    self.block_gap();
+                }
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block_gap();
+                }
+            }
+            TagEnd::Item => self.flush(),
+            TagEnd::TableHead => {
+                self.end_table_row();
+                self.in_table_head = false;
+            }
+            TagEnd::TableRow => self.end_table_row(),
+            TagEnd::TableCell => {
+                let cell = std::mem::take(&mut self.inline);
+                if let Some(table) = &mut self.table {
+                    table.row.push(cell);
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    let prefix = self.prefix(false);
+                    for line in render_table(&table) {
+                        self.lines.push(format!("{}{}", prefix, line));
+                    }
+                }
+                self.block_gap();
+            }
+            TagEnd::Emphasis => self.emphasis = self.emphasis.saturating_sub(1),
+            TagEnd::Strong => self.strong = self.strong.saturating_sub(1),
+            TagEnd::Strikethrough => self.strikethrough = self.strikethrough.saturating_sub(1),
+            TagEnd::Link => {
+                if let Some(url) = self.link.take() {
+                    if !self.inline.contains(url.as_str()) {
+                        self.inline.push_str(&format!(" ({})", url).dimmed().to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let mut styled = text.normal();
+        if self.heading.is_some() || self.strong > 0 || self.in_table_head {
+            styled = styled.bold().paint(Role::Answer);
+        }
+        if self.heading == Some(HeadingLevel::H1) {
+            styled = styled.underline();
+        }
+        if self.emphasis > 0 {
+            styled = styled.italic();
+        }
+        if self.strikethrough > 0 {
+            styled = styled.strikethrough();
+        }
+        if self.link.is_some() {
+            styled = styled.blue().underline();
+        }
+        self.inline.push_str(&styled.to_string());
+    }
+
+    fn end_table_row(&mut self) {
+        if let Some(table) = &mut self.table {
+            let row = std::mem::take(&mut table.row);
+            table.rows.push(row);
+        }
+    }
+
+    fn block_gap(&mut self) {
+        if self.lines.last().is_some_and(|line| !line.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn prefix(&mut self, first_line: bool) -> String {
+        let mut prefix = "│ ".dimmed().to_string().repeat(self.quote_depth);
+        if !self.lists.is_empty() {
+            prefix.push_str(&LIST_INDENT.repeat(self.lists.len() - 1));
+            let marker = if first_line { self.marker.take() } else { None };
+            match marker {
+                Some(marker) => prefix.push_str(&marker.bright_cyan().to_string()),
+                None => prefix.push_str(&" ".repeat(self.marker_width())),
+            }
+        }
+        prefix
+    }
+
+    fn marker_width(&self) -> usize {
+        match self.lists.last() {
+            Some(Some(number)) => format!("{}. ", number.saturating_sub(1)).len(),
+            _ => 2,
+        }
+    }
+
+    fn flush(&mut self) {
+        let inline = std::mem::take(&mut self.inline);
+        if inline.trim().is_empty() && self.marker.is_none() {
+            return;
+        }
+
+        let initial_indent = self.prefix(true);
+        let subsequent_indent = self.prefix(false);
+        let text = inline.trim_matches('\n');
+        for (i, line) in text.split('\n').enumerate() {
+            let indent = if i == 0 { &initial_indent } else { &subsequent_indent };
+            match self.width {
+                Some(width) => {
+                    let options = textwrap::Options::new(width)
+                        .initial_indent(indent)
+                        .subsequent_indent(&subsequent_indent);
+                    self.lines.extend(textwrap::wrap(line, options).into_iter().map(|part| part.into_owned()));
+                }
+                None => self.lines.push(format!("{}{}", indent, line)),
+            }
+        }
+    }
+}
+
+fn render_table(table: &Table) -> Vec<String> {
+    let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            table.rows
+                .iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| display_width(cell))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut lines = Vec::new();
+    for (i, row) in table.rows.iter().enumerate() {
+        let cells: Vec<String> = (0..columns)
+            .map(|column| {
+                let cell = row.get(column).map(String::as_str).unwrap_or("");
+                let padding = widths[column].saturating_sub(display_width(cell));
+                match table.alignments.get(column) {
+                    Some(Alignment::Right) => format!("{}{}", " ".repeat(padding), cell),
+                    Some(Alignment::Center) => format!("{}{}{}", " ".repeat(padding / 2), cell, " ".repeat(padding - padding / 2)),
+                    _ => format!("{}{}", cell, " ".repeat(padding)),
+                }
+            })
+            .collect();
+        lines.push(cells.join(&" │ ".dimmed().to_string()));
+
+        if i == 0 {
+            let separator: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+            lines.push(separator.join("─┼─").dimmed().to_string());
+        }
+    }
+    lines
+}
+
+pub fn highlighting() -> &'static (SyntaxSet, ThemeSet) {
+    static HIGHLIGHTING: OnceLock<(SyntaxSet, ThemeSet)> = OnceLock::new();
+    HIGHLIGHTING.get_or_init(|| (SyntaxSet::load_defaults_newlines(), ThemeSet::load_defaults()))
+}
+
+fn highlight_code(code: &[&str], language: &str) -> String {
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        let mut block = vec![format!("```{}", language)];
+        block.extend(code.iter().map(|line| line.to_string()));
+        block.push("```".to_string());
+        return block.join("\n");
+    }
+
+    let (syntaxes, themes) = highlighting();
+    let syntax = syntaxes
+        .find_syntax_by_token(language)
+        .or_else(|| code.first().and_then(|line| syntaxes.find_syntax_by_first_line(line)))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, &themes.themes[theme::code_theme()]);
+
+    let label = if language.is_empty() { syntax.name.as_str() } else { language };
+    let mut block = vec![format!("{} {}", "┌".dimmed(), label.dimmed())];
+    for line in code {
+        let highlighted = match highlighter.highlight_line(&format!("{}\n", line), syntaxes) {
+            Ok(ranges) => format!("{}\x1b[0m", as_24_bit_terminal_escaped(&ranges, false).trim_end()),
+            Err(_) => line.to_string(),
+        };
+        block.push(format!("{} {}", "│".dimmed(), highlighted));
+    }
+    block.push(format!("{}", "└".dimmed()));
+
+    block.join("\n")
+}
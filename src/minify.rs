@@ -0,0 +1,134 @@
+use std::path::Path;
+
+struct Syntax {
+    line: &'static [&'static str],
+    block: Option<(&'static str, &'static str)>,
+    quotes: &'static str,
+    word_start: bool,
+}
+
+const C_LIKE: Syntax = Syntax { line: &["//"], block: Some(("/*", "*/")), quotes: "\"'`", word_start: false };
+const RUST: Syntax = Syntax { line: &["//"], block: Some(("/*", "*/")), quotes: "\"", word_start: false };
+const HASH: Syntax = Syntax { line: &["#"], block: None, quotes: "\"'", word_start: true };
+const DASHES: Syntax = Syntax { line: &["--"], block: None, quotes: "\"'", word_start: false };
+const CSS: Syntax = Syntax { line: &[], block: Some(("/*", "*/")), quotes: "\"'", word_start: false };
+const MARKUP: Syntax = Syntax { line: &[], block: Some(("<!--", "-->")), quotes: "", word_start: false };
+const BATCH: Syntax = Syntax { line: &["REM ", "rem ", "::"], block: None, quotes: "", word_start: true };
+const INI: Syntax = Syntax { line: &[";", "#"], block: None, quotes: "", word_start: true };
+
+fn syntax_of(path: &Path) -> Option<Syntax> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name == "makefile" || name == "dockerfile" {
+        return Some(HASH);
+    }
+    match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+        "rs" => Some(RUST),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "kt" | "kts"
+        | "go" | "swift" | "scala" | "dart" | "php" | "scss" | "less" => Some(C_LIKE),
+        "py" | "rb" | "sh" | "bash" | "zsh" | "fish" | "pl" | "r" | "toml" | "yml" | "yaml" | "nix" | "ex" | "exs"
+        | "cmake" | "ps1" => Some(HASH),
+        "sql" | "lua" | "hs" => Some(DASHES),
+        "css" => Some(CSS),
+        "html" | "htm" | "xml" | "svg" | "vue" | "md" => Some(MARKUP),
+        "bat" | "cmd" => Some(BATCH),
+        "ini" | "cfg" | "conf" => Some(INI),
+        _ => None,
+    }
+}
+
+pub fn is_supported(path: &Path) -> bool {
+    syntax_of(path).is_some()
+}
+
+fn strip_comments(text: &str, syntax: &Syntax) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut quote: Option<char> = None;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(open) = quote {
+            if c == '\\' {
+                let escaped = rest[1..].chars().next().map_or(1, |next| 1 + next.len_utf8());
+                out.push_str(&rest[..escaped]);
+                rest = &rest[escaped..];
+                continue;
+            }
+            if c == open || (c == '\n' && open != '`') {
+                quote = None;
+            }
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        if c == '\'' && !syntax.quotes.contains(c) {
+            let mut chars = rest[1..].chars();
+            if let (Some(literal), Some('\'')) = (chars.next(), chars.next()) {
+                let end = 2 + literal.len_utf8();
+                out.push_str(&rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+        }
+
+        let word_start = out.is_empty() || out.ends_with(char::is_whitespace);
+        if (word_start || !syntax.word_start) && syntax.line.iter().any(|marker| rest.starts_with(marker)) {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+            continue;
+        }
+        if let Some((open, close)) = syntax.block {
+            if let Some(after) = rest.strip_prefix(open) {
+                rest = after.find(close).map_or("", |end| &after[end + close.len()..]);
+                continue;
+            }
+        }
+        if syntax.quotes.contains(c) {
+            quote = Some(c);
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+pub fn minify(path: &Path, text: &str) -> Option<String> {
+    let syntax = syntax_of(path)?;
+    let stripped = strip_comments(text, &syntax);
+    let lines: Vec<&str> = stripped.lines().map(str::trim_end).filter(|line| !line.trim().is_empty()).collect();
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn min(name: &str, text: &str) -> String {
+        minify(Path::new(name), text).unwrap()
+    }
+
+    #[test]
+    fn strips_comments_and_blank_lines() {
+        let source = "// header\nfn main() { /* inline */\n\n    let x = 1; // trailing\n}\n";
+        assert_eq!(min("main.rs", source), "fn main() {\n    let x = 1;\n}");
+        assert_eq!(min("run.py", "# comment\nx = 1  # note\n"), "x = 1");
+        assert_eq!(min("page.html", "<p>a</p><!-- hidden\nstill hidden --><p>b</p>"), "<p>a</p><p>b</p>");
+        assert_eq!(min("query.sql", "-- pick\nSELECT 1; -- one\n"), "SELECT 1;");
+    }
+
+    #[test]
+    fn keeps_comment_markers_inside_strings() {
+        assert_eq!(min("main.rs", "let url = \"http://example.com\"; // x"), "let url = \"http://example.com\";");
+        assert_eq!(min("app.js", "const s = 'a /* b */ c';"), "const s = 'a /* b */ c';");
+        assert_eq!(min("main.rs", "let q = '\"'; // quote\nlet s = \"a\\\"//b\";"), "let q = '\"';\nlet s = \"a\\\"//b\";");
+        assert_eq!(min("run.sh", "echo \"#1\" # note\ncolor=#fff"), "echo \"#1\"\ncolor=#fff");
+    }
+
+    #[test]
+    fn recognizes_files_by_name_and_extension() {
+        assert!(is_supported(Path::new("Makefile")));
+        assert!(is_supported(Path::new("src/lib.RS")));
+        assert!(!is_supported(Path::new("notes.txt")));
+        assert!(minify(Path::new("data.bin"), "x").is_none());
+        assert_eq!(min("build.bat", "REM setup\n:: more\necho ok\n"), "echo ok");
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, Local, NaiveDate};
+
+use crate::cache;
+
+pub fn since_date(since: &str) -> Result<NaiveDate> {
+    let today = Local::now().date_naive();
+    match since.trim().to_lowercase().as_str() {
+        "today" => Ok(today),
+        "yesterday" => Ok(today - Duration::days(1)),
+        "last week" | "week" => Ok(today - Duration::days(7)),
+        "last month" | "month" => Ok(today - Duration::days(30)),
+        other => {
+            if let Ok(date) = NaiveDate::parse_from_str(other, "%Y-%m-%d") {
+                return Ok(date);
+            }
+            match cache::parse_duration(other) {
+                Ok(secs) => Duration::try_days((secs / 86_400).max(1) as i64)
+                    .and_then(|days| today.checked_sub_signed(days))
+                    .context(format!("--since '{}' is too far in the past", since)),
+                Err(_) => anyhow::bail!("Invalid --since '{}' (use today, yesterday, last week, a duration like 3d, or YYYY-MM-DD)", since),
+            }
+        }
+    }
+}
+
+pub fn digest_query(topic: &str, since: &str) -> Result<String> {
+    let today = Local::now().date_naive();
+    let since = since_date(since)?;
+
+    Ok(format!(
+        "Today is {}. Write a news digest about \"{}\" covering only news published since {} (inclusive). \
+         List the most important developments, newest first. For each item give its date (YYYY-MM-DD), \
+         a bold headline and a one or two sentence summary, and cite the sources. \
+         If nothing notable happened in that period, say so.",
+        today.format("%Y-%m-%d"),
+        topic,
+        since.format("%Y-%m-%d")
+    ))
+}
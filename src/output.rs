@@ -0,0 +1,316 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use colored::*;
+use html_escape::decode_html_entities;
+use regex::Regex;
+use std::io::{IsTerminal, Write};
+use similar::{ChangeTag, DiffTag, TextDiff};
+use std::process::{Command, Stdio};
+use terminal_size::{terminal_size, Height, Width};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{cache, markdown, ContextWindow, FastGPTResponse};
+use crate::theme::{Paint, Role};
+
+pub use fastgpt::format::{remove_reference_numbers, render_markdown};
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Pretty,
+    Plain,
+    Markdown,
+    Json,
+    Jsonl,
+}
+
+impl OutputFormat {
+    pub fn is_styled(self) -> bool {
+        self == OutputFormat::Pretty
+    }
+
+    pub fn is_human_readable(self) -> bool {
+        matches!(self, OutputFormat::Pretty | OutputFormat::Plain)
+    }
+}
+
+pub fn print_response(response: &FastGPTResponse, query: &str, format: OutputFormat, show_references: bool, pager: Option<&str>) -> Result<()> {
+    match format {
+        OutputFormat::Pretty | OutputFormat::Plain => print_formatted_response(response, query, show_references, pager),
+        OutputFormat::Markdown => println!("{}", render_markdown(response, show_references)),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(response)?),
+        OutputFormat::Jsonl => println!("{}", serde_json::to_string(&serde_json::json!({
+            "query": query,
+            "output": response.data.output,
+            "references": response.data.references,
+            "tokens": response.data.tokens,
+            "meta": response.meta,
+        }))?),
+    }
+    Ok(())
+}
+
+pub fn format_markdown_text(text: &str) -> String {
+    markdown::render(&decode_html_entities(text), None)
+}
+
+pub fn print_context_window(window: &ContextWindow) {
+    let history = match (window.history.first(), window.history.last()) {
+        (Some(first), Some(last)) if first == last => format!("Q{} ({} of {})", first + 1, window.history.len(), window.history_total),
+        (Some(first), Some(last)) => format!("Q{}-Q{} ({} of {})", first + 1, last + 1, window.history.len(), window.history_total),
+        _ if window.history_total > 0 => format!("none (0 of {})", window.history_total),
+        _ => "none".to_string(),
+    };
+    
+    let files = if window.files.is_empty() {
+        "none".to_string()
+    } else {
+        window.files.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ")
+    };
+    
+    println!("{}", format!(
+        "{} {} • {} {}",
+        "History:".dimmed(),
+        history.bright_magenta(),
+        "Files:".dimmed(),
+        files.bright_magenta()
+    ).dimmed());
+    
+    if !window.skipped_files.is_empty() {
+        let skipped = window.skipped_files.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ");
+        println!("{} {}", "Over budget, not sent:".dimmed(), skipped.bright_yellow());
+    }
+}
+
+pub fn print_formatted_response(response: &FastGPTResponse, query: &str, show_references: bool, pager: Option<&str>) {
+    let rendered = render_formatted_response(response, query, show_references);
+    match pager {
+        Some(pager) if exceeds_terminal_height(&rendered) => {
+            if let Err(e) = page(&rendered, pager) {
+                eprintln!("{} {}", "Pager failed:".bright_yellow(), e);
+                println!("{}", rendered);
+            }
+        }
+        _ => println!("{}", rendered),
+    }
+}
+
+fn exceeds_terminal_height(text: &str) -> bool {
+    if !std::io::stdout().is_terminal() {
+        return false;
+    }
+    match terminal_size() {
+        Some((_, Height(height))) => text.lines().count() + 2 > height as usize,
+        None => false,
+    }
+}
+
+fn page(text: &str, pager: &str) -> Result<()> {
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().context("Pager command is empty")?;
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context(format!("Failed to start pager: {}", pager))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = writeln!(stdin, "{}", text);
+    }
+    child.wait().context("Failed to wait for pager")?;
+    Ok(())
+}
+
+fn wrap_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    match terminal_size() {
+        Some((Width(width), _)) if width > 20 => Some(width as usize - 1),
+        _ => None,
+    }
+}
+
+fn wrap_text(text: &str, indent: &str, width: Option<usize>) -> String {
+    let Some(width) = width else {
+        return text
+            .split('\n')
+            .map(|line| format!("{}{}", indent, line))
+            .collect::<Vec<_>>()
+            .join("\n");
+    };
+
+    let list_item_regex = Regex::new(r"^(\s*(?:[-*•]|\d+[.)])\s+)").unwrap();
+    let mut wrapped = Vec::new();
+
+    for line in text.split('\n') {
+        if line.trim().is_empty() {
+            wrapped.push(String::new());
+            continue;
+        }
+
+        let hanging = match list_item_regex.find(line) {
+            Some(marker) => " ".repeat(marker.as_str().width()),
+            None => line.chars().take_while(|c| c.is_whitespace()).collect(),
+        };
+        let subsequent_indent = format!("{}{}", indent, hanging);
+        let options = textwrap::Options::new(width)
+            .initial_indent(indent)
+            .subsequent_indent(&subsequent_indent);
+
+        wrapped.extend(textwrap::wrap(line, options).into_iter().map(|part| part.into_owned()));
+    }
+
+    wrapped.join("\n")
+}
+
+fn supports_hyperlinks() -> bool {
+    if let Ok(force) = std::env::var("FORCE_HYPERLINK") {
+        return force != "0";
+    }
+    if !colored::control::SHOULD_COLORIZE.should_colorize() || !std::io::stdout().is_terminal() {
+        return false;
+    }
+
+    let env = |name: &str| std::env::var(name).unwrap_or_default();
+    let term_program = env("TERM_PROGRAM");
+    let term = env("TERM");
+
+    ["iTerm.app", "WezTerm", "vscode", "ghostty", "Hyper"].contains(&term_program.as_str())
+        || ["xterm-kitty", "alacritty", "foot", "xterm-ghostty"].iter().any(|name| term.starts_with(name))
+        || env("VTE_VERSION").parse::<u32>().is_ok_and(|version| version >= 5000)
+        || !env("WT_SESSION").is_empty()
+        || !env("KONSOLE_VERSION").is_empty()
+}
+
+fn hyperlink(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+fn link_citations(text: &str, response: &FastGPTResponse) -> String {
+    let reference_regex = Regex::new(r"【(\d+)】").unwrap();
+    reference_regex.replace_all(text, |caps: &regex::Captures| {
+        let reference = caps[1]
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| response.data.references.get(n.wrapping_sub(1)));
+        match reference {
+            Some(reference) => hyperlink(&reference.url, &caps[0]),
+            None => caps[0].to_string(),
+        }
+    }).to_string()
+}
+
+pub fn print_context_diff(previous: &str, current: &str, max_lines: usize) {
+    let diff = TextDiff::from_lines(previous, current);
+    let changes: Vec<_> = diff.iter_all_changes().filter(|change| change.tag() != ChangeTag::Equal).collect();
+
+    for change in changes.iter().take(max_lines) {
+        let line = change.to_string_lossy();
+        let line = line.trim_end_matches('\n');
+        match change.tag() {
+            ChangeTag::Delete => println!("  {}", format!("- {}", line).bright_red()),
+            ChangeTag::Insert => println!("  {}", format!("+ {}", line).bright_green()),
+            ChangeTag::Equal => {}
+        }
+    }
+    if changes.len() > max_lines {
+        println!("  {}", format!("... {} more changed lines", changes.len() - max_lines).dimmed());
+    }
+}
+
+pub fn print_verification(original: &str, response: &FastGPTResponse, query: &str, show_references: bool) {
+    let original = remove_reference_numbers(&decode_html_entities(original));
+    let revised = remove_reference_numbers(&decode_html_entities(&response.data.output));
+    let diff = TextDiff::from_words(original.as_str(), revised.as_str());
+
+    println!();
+    println!("{} {}", ">".paint(Role::Banner).bold(), query.paint(Role::Query));
+    println!();
+
+    let revisions = diff.ops().iter().filter(|op| op.tag() != DiffTag::Equal).count();
+    if revisions == 0 {
+        println!("{}", "No claims revised.".bright_green());
+    } else {
+        let mut annotated = String::new();
+        for change in diff.iter_all_changes() {
+            let text = change.value();
+            match change.tag() {
+                ChangeTag::Equal => annotated.push_str(text),
+                ChangeTag::Delete => annotated.push_str(&text.bright_red().strikethrough().to_string()),
+                ChangeTag::Insert => annotated.push_str(&text.bright_green().bold().to_string()),
+            }
+        }
+        println!("{}", wrap_text(&annotated, "", wrap_width()));
+        println!();
+        println!("{} {}", "Revised passages:".dimmed(), revisions.to_string().bright_yellow());
+    }
+
+    if show_references && !response.data.references.is_empty() {
+        println!();
+        println!("{}", "Sources checked:".paint(Role::References).bold());
+        for (i, reference) in response.data.references.iter().enumerate() {
+            println!("  {}. {} {}", (i + 1).to_string().bright_cyan(), format_markdown_text(&reference.title).paint(Role::Answer), reference.url.blue().underline());
+        }
+    }
+}
+
+pub fn render_formatted_response(response: &FastGPTResponse, query: &str, show_references: bool) -> String {
+    let width = wrap_width();
+    let mut lines = Vec::new();
+    lines.push(String::new());
+    lines.push(format!("{} {}", ">".paint(Role::Banner).bold(), query.paint(Role::Query)));
+    lines.push(String::new());
+
+    if let Some(cached_at) = response.cached_at {
+        lines.push(format!("{}", format!("[cached at {}]", cache::format_timestamp(cached_at)).bright_yellow()));
+        lines.push(String::new());
+    }
+    
+    let output_text = if show_references {
+        response.data.output.clone()
+    } else {
+        remove_reference_numbers(&response.data.output)
+    };
+    
+    let hyperlinks = supports_hyperlinks();
+    let answer = markdown::render(&decode_html_entities(&output_text), width);
+    lines.push(if hyperlinks { link_citations(&answer, response) } else { answer });
+
+    if show_references && !response.data.references.is_empty() {
+        lines.push(String::new());
+        lines.push(format!("{}", "References:".paint(Role::References).bold()));
+        for (i, reference) in response.data.references.iter().enumerate() {
+            let title = format_markdown_text(&reference.title).paint(Role::Answer).to_string();
+            let url = reference.url.blue().underline().to_string();
+            if hyperlinks {
+                lines.push(format!("  {}. {}", (i + 1).to_string().bright_cyan(), hyperlink(&reference.url, &title)));
+                lines.push(format!("     {}", hyperlink(&reference.url, &url)));
+            } else {
+                lines.push(format!("  {}. {}", (i + 1).to_string().bright_cyan(), title));
+                lines.push(format!("     {}", url));
+            }
+            if !reference.snippet.is_empty() {
+                lines.push(wrap_text(&format_markdown_text(&reference.snippet).dimmed().to_string(), "     ", width));
+            }
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(format!("{}", format!(
+        "{} {} • {} {} • {} {}ms{}",
+        "Tokens:".dimmed(),
+        response.data.tokens.to_string().bright_magenta(),
+        "Node:".dimmed(),
+        response.meta.node.bright_magenta(),
+        "Time:".dimmed(),
+        response.meta.ms.to_string().bright_magenta(),
+        if let Some(balance) = response.meta.api_balance {
+            format!(" • {} ${:.3}", "Balance:".dimmed(), balance.to_string().bright_green())
+        } else {
+            String::new()
+        }
+    ).dimmed()));
+
+    lines.join("\n")
+}
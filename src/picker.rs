@@ -0,0 +1,211 @@
+use anyhow::{Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Print, PrintStyledContent, Stylize};
+use crossterm::terminal::{self, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::collections::BTreeSet;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::IGNORE_FILE;
+
+const MAX_CANDIDATES: usize = 20_000;
+const HEADER_LINES: u16 = 2;
+
+struct Match {
+    index: usize,
+    score: i64,
+    positions: Vec<usize>,
+}
+
+struct Picker {
+    files: Vec<String>,
+    query: String,
+    matches: Vec<Match>,
+    selected: BTreeSet<usize>,
+    cursor: usize,
+    scroll: usize,
+    matcher: SkimMatcherV2,
+}
+
+fn candidates(root: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in ignore::WalkBuilder::new(root)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build()
+    {
+        let entry = entry.context("Failed to read directory entry")?;
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        let path = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        files.push(path.display().to_string());
+        if files.len() == MAX_CANDIDATES {
+            break;
+        }
+    }
+    Ok(files)
+}
+
+impl Picker {
+    fn new(files: Vec<String>) -> Self {
+        let mut picker = Picker {
+            files,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: BTreeSet::new(),
+            cursor: 0,
+            scroll: 0,
+            matcher: SkimMatcherV2::default(),
+        };
+        picker.update_matches();
+        picker
+    }
+
+    fn update_matches(&mut self) {
+        self.matches = self.files
+            .iter()
+            .enumerate()
+            .filter_map(|(index, file)| {
+                if self.query.is_empty() {
+                    return Some(Match { index, score: 0, positions: Vec::new() });
+                }
+                self.matcher
+                    .fuzzy_indices(file, &self.query)
+                    .map(|(score, positions)| Match { index, score, positions })
+            })
+            .collect();
+        self.matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.index.cmp(&b.index)));
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    fn move_cursor(&mut self, down: bool) {
+        if down {
+            self.cursor = (self.cursor + 1).min(self.matches.len().saturating_sub(1));
+        } else {
+            self.cursor = self.cursor.saturating_sub(1);
+        }
+    }
+
+    fn toggle(&mut self) {
+        if let Some(current) = self.matches.get(self.cursor) {
+            if !self.selected.remove(&current.index) {
+                self.selected.insert(current.index);
+            }
+            self.move_cursor(true);
+        }
+    }
+
+    fn chosen(&self) -> Vec<PathBuf> {
+        let indices: Vec<usize> = if self.selected.is_empty() {
+            self.matches.get(self.cursor).map(|current| current.index).into_iter().collect()
+        } else {
+            self.selected.iter().copied().collect()
+        };
+        indices.into_iter().map(|index| PathBuf::from(&self.files[index])).collect()
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = terminal::size().unwrap_or((80, 24));
+        let rows = height.saturating_sub(HEADER_LINES).max(1) as usize;
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + rows {
+            self.scroll = self.cursor + 1 - rows;
+        }
+
+        queue!(out, cursor::MoveTo(0, 0), terminal::Clear(ClearType::All))?;
+        queue!(out, PrintStyledContent("> ".cyan().bold()), Print(&self.query), cursor::MoveToNextLine(1))?;
+        queue!(out, PrintStyledContent(format!(
+            "  {}/{} files, {} selected  (Tab: select, Enter: add, Esc: cancel)",
+            self.matches.len(), self.files.len(), self.selected.len()
+        ).dark_grey()), cursor::MoveToNextLine(1))?;
+
+        for (row, current) in self.matches.iter().enumerate().skip(self.scroll).take(rows) {
+            let pointer = if row == self.cursor { ">" } else { " " };
+            let mark = if self.selected.contains(&current.index) { "●" } else { " " };
+            queue!(out, PrintStyledContent(format!("{}{} ", pointer, mark).cyan()))?;
+
+            for (i, c) in self.files[current.index].chars().take(width.saturating_sub(4) as usize).enumerate() {
+                if current.positions.contains(&i) {
+                    queue!(out, PrintStyledContent(c.yellow().bold()))?;
+                } else if row == self.cursor {
+                    queue!(out, PrintStyledContent(c.bold()))?;
+                } else {
+                    queue!(out, Print(c))?;
+                }
+            }
+            queue!(out, cursor::MoveToNextLine(1))?;
+        }
+
+        let query_width = self.query.chars().count() as u16;
+        queue!(out, cursor::MoveTo(2 + query_width, 0))?;
+        out.flush()
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> Option<Vec<PathBuf>> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(Vec::new()),
+            KeyCode::Char('c') | KeyCode::Char('g') if ctrl => return Some(Vec::new()),
+            KeyCode::Enter => return Some(self.chosen()),
+            KeyCode::Tab => self.toggle(),
+            KeyCode::Up | KeyCode::BackTab => self.move_cursor(false),
+            KeyCode::Char('p') | KeyCode::Char('k') if ctrl => self.move_cursor(false),
+            KeyCode::Down => self.move_cursor(true),
+            KeyCode::Char('n') | KeyCode::Char('j') if ctrl => self.move_cursor(true),
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.update_matches();
+            }
+            KeyCode::Backspace if self.query.pop().is_some() => self.update_matches(),
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.update_matches();
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+fn run(picker: &mut Picker, out: &mut impl Write) -> Result<Vec<PathBuf>> {
+    loop {
+        picker.draw(out)?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                if let Some(chosen) = picker.handle(key) {
+                    return Ok(chosen);
+                }
+            }
+        }
+    }
+}
+
+pub fn pick(root: &Path) -> Result<Vec<PathBuf>> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        anyhow::bail!("The file picker needs an interactive terminal");
+    }
+    let files = candidates(root)?;
+    if files.is_empty() {
+        anyhow::bail!("No files found under {}", root.display());
+    }
+
+    let mut picker = Picker::new(files);
+    let mut out = io::stdout();
+    terminal::enable_raw_mode().context("Failed to enable raw terminal mode")?;
+    let result = execute!(out, EnterAlternateScreen)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| run(&mut picker, &mut out));
+    let _ = execute!(out, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+
+    Ok(result?
+        .into_iter()
+        .map(|path| if root == Path::new(".") { path } else { root.join(path) })
+        .collect())
+}
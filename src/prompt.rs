@@ -0,0 +1,87 @@
+pub const MAX_HISTORY_ENTRIES: usize = 5;
+
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+pub struct File<'a> {
+    pub name: &'a str,
+    pub label: Option<&'a str>,
+    pub content: &'a str,
+}
+
+pub struct Turn<'a> {
+    pub query: &'a str,
+    pub answer: &'a str,
+}
+
+pub struct Budget<'a> {
+    pub tokens: usize,
+    pub count: &'a dyn Fn(&str) -> usize,
+}
+
+#[derive(Clone, Default)]
+pub struct Window {
+    pub files: Vec<usize>,
+    pub skipped_files: Vec<usize>,
+    pub history: Vec<usize>,
+    pub history_total: usize,
+}
+
+pub fn build(query: &str, instructions: &[&str], files: &[File], history: &[Turn], budget: Option<&Budget>) -> (String, Window) {
+    let mut prompt = String::new();
+    let mut window = Window { history_total: history.len(), ..Window::default() };
+    let count = |text: &str| budget.map_or(0, |budget| (budget.count)(text));
+    let mut remaining = budget.map_or(usize::MAX, |budget| budget.tokens.saturating_sub(count(query)));
+
+    if !instructions.is_empty() {
+        let instructions = instructions.join("\n");
+        prompt.push_str(&format!("Instructions: {}\n\n", instructions));
+        remaining = remaining.saturating_sub(count(&instructions));
+    }
+
+    for (i, file) in files.iter().enumerate() {
+        let tokens = count(file.content);
+        if tokens > remaining {
+            window.skipped_files.push(i);
+            continue;
+        }
+        remaining -= tokens;
+        window.files.push(i);
+    }
+    if !window.files.is_empty() {
+        prompt.push_str("File contexts:\n");
+        for &i in &window.files {
+            let file = &files[i];
+            match file.label {
+                Some(label) => prompt.push_str(&format!("\n--- File: {} ({}) ---\n", file.name, label)),
+                None => prompt.push_str(&format!("\n--- File: {} ---\n", file.name)),
+            }
+            prompt.push_str(file.content);
+            prompt.push_str("\n--- End of file ---\n\n");
+        }
+    }
+
+    if budget.is_some() {
+        for (i, turn) in history.iter().enumerate().rev().take(MAX_HISTORY_ENTRIES) {
+            let tokens = count(turn.query) + count(turn.answer);
+            if tokens > remaining {
+                break;
+            }
+            remaining -= tokens;
+            window.history.insert(0, i);
+        }
+    } else {
+        window.history = (0..history.len().min(MAX_HISTORY_ENTRIES)).collect();
+    }
+    if !window.history.is_empty() {
+        prompt.push_str("Previous conversation context:\n");
+        for &i in &window.history {
+            let turn = &history[i];
+            prompt.push_str(&format!("Q{}: {}\nA{}: {}\n\n", i + 1, turn.query, i + 1, turn.answer));
+        }
+    }
+
+    prompt.push_str(&format!("Current question: {}", query));
+    (prompt, window)
+}
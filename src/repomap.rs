@@ -0,0 +1,127 @@
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const MAX_LINE_CHARS: usize = 160;
+const CONTROL_KEYWORDS: &[&str] = &["if", "for", "while", "switch", "catch", "return", "else", "do", "foreach", "using", "sizeof"];
+
+#[derive(Clone, Copy)]
+enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    Go,
+    Jvm,
+    C,
+    Ruby,
+    Shell,
+    Lua,
+    Elixir,
+    Markdown,
+}
+
+fn language_of(path: &Path) -> Option<Language> {
+    match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+        "rs" => Some(Language::Rust),
+        "py" | "pyi" => Some(Language::Python),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" | "vue" | "svelte" => Some(Language::JavaScript),
+        "go" => Some(Language::Go),
+        "java" | "kt" | "kts" | "cs" | "swift" | "scala" | "dart" | "php" => Some(Language::Jvm),
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => Some(Language::C),
+        "rb" => Some(Language::Ruby),
+        "sh" | "bash" | "zsh" => Some(Language::Shell),
+        "lua" => Some(Language::Lua),
+        "ex" | "exs" => Some(Language::Elixir),
+        "md" | "markdown" => Some(Language::Markdown),
+        _ => None,
+    }
+}
+
+fn pattern(language: Language) -> &'static Regex {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        [
+            r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:async|const|unsafe|default|extern(?:\s+"[^"]*")?)\s+)*(?:fn|struct|enum|trait|impl|mod|type|union|macro_rules!)[\s<!(]|^\s*(?:pub(?:\([^)]*\))?\s+)?(?:const|static)\s+[A-Z_][A-Z0-9_]*\s*:"#,
+            r"^\s*(?:async\s+)?(?:def|class)\s+\w+",
+            r"^\s*(?:export\s+(?:default\s+)?)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?:function\*?\s+\w+|class\s+\w+|interface\s+\w+|type\s+\w+|enum\s+\w+|(?:const|let|var)\s+\w+\s*(?::[^=]+)?=\s*(?:async\s+)?(?:function|\([^)]*\)\s*(?::[^=]+)?=>|\w+\s*=>))|^\s+(?:(?:public|private|protected|static|async|get|set|readonly|override)\s+)*\*?\w+\s*(?:<[^>]*>)?\([^)]*\)\s*(?::\s*[^{]+)?\{\s*$",
+            r"^(?:func|type)\s",
+            r"^\s*(?:@\w+\s+)*(?:(?:public|private|protected|internal|static|final|abstract|sealed|override|open|data|async|virtual|partial|suspend|inline|readonly|synchronized|fileprivate|mutating)\s+)*(?:class|interface|enum|struct|record|object|trait|protocol|extension|fun|func|def|function)\s+\w+|^\s*(?:(?:public|private|protected|internal|static|final|abstract|override|virtual|async|synchronized)\s+)+[\w<>\[\],.?]+\s+\w+\s*\(",
+            r"^(?:typedef\s+)?(?:struct|class|enum|union|namespace)\s+\w+|^#define\s+\w+|^(?:template\s*<[^>]*>\s*)?[A-Za-z_][\w\s\*&:<>,]*[\s\*&][\*&]?[A-Za-z_~][\w:~]*\s*\(",
+            r"^\s*(?:def|class|module)\s",
+            r"^\s*(?:function\s+[\w-]+|[\w-]+\s*\(\)\s*\{?)",
+            r"^\s*(?:local\s+)?function\s",
+            r"^\s*(?:defp?|defmodule|defmacrop?|defprotocol|defimpl|defstruct)\b",
+            r"^#{1,6}\s",
+        ]
+        .into_iter()
+        .map(|pattern| Regex::new(pattern).unwrap())
+        .collect()
+    });
+    &patterns[language as usize]
+}
+
+pub fn is_supported(path: &Path) -> bool {
+    language_of(path).is_some()
+}
+
+fn tidy(line: &str) -> String {
+    let line = line.trim_end().trim_end_matches('{').trim_end();
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+pub fn outline(path: &Path, text: &str) -> Option<Vec<String>> {
+    let language = language_of(path)?;
+    let pattern = pattern(language);
+    let mut in_fence = false;
+
+    let declarations = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            if matches!(language, Language::Markdown) && line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                return false;
+            }
+            !in_fence && pattern.is_match(line)
+        })
+        .filter(|(_, line)| {
+            let first = line.split(|c: char| !c.is_alphanumeric() && c != '_').find(|word| !word.is_empty());
+            !first.is_some_and(|word| CONTROL_KEYWORDS.contains(&word))
+        })
+        .map(|(i, line)| format!("{:>5}: {}", i + 1, tidy(line)))
+        .collect();
+    Some(declarations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(name: &str, text: &str) -> Vec<String> {
+        outline(Path::new(name), text).unwrap()
+    }
+
+    #[test]
+    fn outlines_declarations_with_line_numbers() {
+        let source = "use std::fs;\n\npub struct Config {\n    name: String,\n}\n\nimpl Config {\n    pub async fn load() -> Self {\n        if true {\n        }\n    }\n}\n";
+        assert_eq!(lines("lib.rs", source), ["    3: pub struct Config", "    7: impl Config", "    8:     pub async fn load() -> Self"]);
+        assert_eq!(lines("app.py", "import os\n\nclass App:\n    async def run(self):\n        return 1\n"), ["    3: class App:", "    4:     async def run(self):"]);
+    }
+
+    #[test]
+    fn skips_control_flow_and_fenced_code() {
+        assert_eq!(lines("main.c", "int main(void) {\n    if (x) {\n    while (y) {\n}\n"), ["    1: int main(void)"]);
+        assert_eq!(lines("README.md", "# Title\n```\n# not a heading\n```\n## Usage\n"), ["    1: # Title", "    5: ## Usage"]);
+    }
+
+    #[test]
+    fn shortens_long_lines_and_ignores_unknown_files() {
+        let long = format!("fn {}() {{", "a".repeat(200));
+        assert!(lines("long.rs", &long)[0].ends_with("..."));
+        assert!(outline(Path::new("notes.txt"), "fn main() {}").is_none());
+        assert!(!is_supported(Path::new("Cargo.lock")));
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::{Context, Result};
+use chrono::{Days, Local, TimeZone};
+use html_escape::encode_text;
+use pulldown_cmark::{html, Event, Options, Parser};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{assets, sessions, usage};
+
+const TOPIC_LIMIT: usize = 8;
+const TOPIC_EXAMPLES: usize = 3;
+const MIN_KEYWORD_CHARS: usize = 3;
+const STYLE_ASSET: &str = "themes/export.css";
+
+const STOP_WORDS: &[&str] = &[
+    "about", "and", "are", "best", "between", "can", "could", "does", "explain", "for", "from", "get",
+    "give", "how", "into", "is", "its", "latest", "list", "make", "more", "most", "not", "or", "other",
+    "should", "show", "some", "tell", "than", "that", "the", "their", "them", "then", "there", "these",
+    "they", "this", "use", "using", "was", "way", "what", "when", "where", "which", "who", "why",
+    "will", "with", "would", "you", "your",
+];
+
+struct Topic {
+    keyword: String,
+    queries: Vec<String>,
+}
+
+#[derive(Default)]
+struct Day {
+    queries: usize,
+    usage: usage::Rollup,
+}
+
+pub struct Report {
+    days: u64,
+    start: i64,
+    end: i64,
+    queries: Vec<(i64, String)>,
+    usage: usage::Rollup,
+    daily: BTreeMap<String, Day>,
+    topics: Vec<Topic>,
+    unclustered: usize,
+}
+
+fn day_of(timestamp: i64) -> Option<String> {
+    Local.timestamp_opt(timestamp, 0).single().map(|time| time.format("%Y-%m-%d").to_string())
+}
+
+fn keywords(query: &str) -> BTreeSet<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric() && c != '+' && c != '#')
+        .map(|word| word.to_lowercase())
+        .filter(|word| word.chars().count() >= MIN_KEYWORD_CHARS)
+        .filter(|word| !word.chars().all(|c| c.is_ascii_digit()))
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+fn cluster(queries: &[(i64, String)]) -> (Vec<Topic>, usize) {
+    let mut remaining: Vec<(&String, BTreeSet<String>)> = queries
+        .iter()
+        .rev()
+        .map(|(_, query)| (query, keywords(query)))
+        .collect();
+    let mut topics = Vec::new();
+
+    while topics.len() < TOPIC_LIMIT {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for (_, words) in &remaining {
+            for word in words {
+                *counts.entry(word).or_default() += 1;
+            }
+        }
+        let Some((keyword, count)) = counts.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0))) else {
+            break;
+        };
+        if count < 2 {
+            break;
+        }
+
+        let keyword = keyword.to_string();
+        let (matched, rest): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|(_, words)| words.contains(&keyword));
+        remaining = rest;
+        topics.push(Topic {
+            keyword,
+            queries: matched.into_iter().map(|(query, _)| query.clone()).collect(),
+        });
+    }
+
+    (topics, remaining.len())
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn build(days: u64) -> Result<Report> {
+    if days == 0 {
+        anyhow::bail!("The report period must be at least one day");
+    }
+    let now = Local::now();
+    let first_day = now.date_naive()
+        .checked_sub_days(Days::new(days - 1))
+        .context("Report period is too long")?;
+    let start = Local
+        .from_local_datetime(&first_day.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(|time| time.timestamp())
+        .unwrap_or(0);
+    let end = now.timestamp();
+    let in_period = |timestamp: i64| timestamp >= start && timestamp <= end;
+
+    let mut daily: BTreeMap<String, Day> = BTreeMap::new();
+    let mut queries = Vec::new();
+    for session in sessions::load_all()? {
+        for exchange in session.exchanges.into_iter().filter(|exchange| in_period(exchange.timestamp)) {
+            if let Some(day) = day_of(exchange.timestamp) {
+                daily.entry(day).or_default().queries += 1;
+            }
+            queries.push((exchange.timestamp, exchange.query));
+        }
+    }
+    queries.sort_by_key(|(timestamp, _)| *timestamp);
+
+    let mut total = usage::Rollup::default();
+    for record in usage::load_records()?.iter().filter(|record| in_period(record.timestamp)) {
+        total.add_query(record.tokens, record.ms);
+        if let Some(day) = day_of(record.timestamp) {
+            daily.entry(day).or_default().usage.add_query(record.tokens, record.ms);
+        }
+    }
+
+    let (topics, unclustered) = cluster(&queries);
+    Ok(Report { days, start, end, queries, usage: total, daily, topics, unclustered })
+}
+
+impl Report {
+    fn title(&self) -> String {
+        match self.days {
+            7 => "FastGPT weekly report".to_string(),
+            1 => "FastGPT daily report".to_string(),
+            days => format!("FastGPT report for the last {} days", days),
+        }
+    }
+
+    pub fn markdown(&self) -> String {
+        let mut md = format!("# {}\n\n", self.title());
+        md.push_str(&format!("{} to {}\n\n", sessions::format_time(self.start), sessions::format_time(self.end)));
+
+        md.push_str("## Summary\n\n");
+        md.push_str("| | |\n|---|---:|\n");
+        md.push_str(&format!("| Queries asked | {} |\n", self.queries.len()));
+        md.push_str(&format!("| API queries | {} |\n", self.usage.queries));
+        md.push_str(&format!("| Tokens | {} |\n", self.usage.tokens));
+        md.push_str(&format!("| Average latency | {} ms |\n", self.usage.average_ms()));
+        md.push_str(&format!("| Estimated cost | ${:.3} |\n\n", self.usage.estimated_cost()));
+
+        if self.daily.is_empty() {
+            md.push_str("No queries in this period.\n");
+            return md;
+        }
+
+        md.push_str("## Daily Activity\n\n");
+        md.push_str("| Day | Queries | API queries | Tokens | Cost |\n|---|---:|---:|---:|---:|\n");
+        for (day, activity) in &self.daily {
+            md.push_str(&format!("| {} | {} | {} | {} | ${:.3} |\n",
+                day, activity.queries, activity.usage.queries, activity.usage.tokens, activity.usage.estimated_cost()));
+        }
+        md.push('\n');
+
+        if !self.queries.is_empty() {
+            md.push_str("## Topics\n\n");
+            for topic in &self.topics {
+                let mut examples: Vec<String> = Vec::new();
+                for query in topic.queries.iter().map(|query| one_line(query)) {
+                    if examples.len() < TOPIC_EXAMPLES && !examples.contains(&query) {
+                        examples.push(query);
+                    }
+                }
+                let examples: Vec<String> = examples
+                    .iter()
+                    .map(|query| format!("\"{}\"", query.replace('*', "\\*").replace('_', "\\_")))
+                    .collect();
+                md.push_str(&format!("- **{}** ({} queries): {}\n", topic.keyword, topic.queries.len(), examples.join(", ")));
+            }
+            if self.unclustered > 0 {
+                let label = if self.topics.is_empty() { "Unclustered" } else { "Other" };
+                md.push_str(&format!("- {} ({} queries)\n", label, self.unclustered));
+            }
+            md.push('\n');
+        }
+
+        md.push_str(&format!("_Cost estimated at ${} per API query; answers served from a cache are free._\n", usage::COST_PER_QUERY));
+        md
+    }
+
+    pub fn html(&self) -> String {
+        let mut options = Options::empty();
+        options.insert(Options::ENABLE_TABLES);
+        let mut body = String::new();
+        let markdown = self.markdown();
+        let events = Parser::new_ext(&markdown, options).map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        });
+        html::push_html(&mut body, events);
+
+        let mut page = String::new();
+        page.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        page.push_str(&format!("<title>{}</title>\n", encode_text(&self.title())));
+        page.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", assets::read(STYLE_ASSET).unwrap_or_default()));
+        page.push_str(&format!("<main class=\"report\">\n{}</main>\n", body));
+        page.push_str("</body>\n</html>\n");
+        page
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use colored::*;
+use regex::{Captures, Regex};
+use std::time::{Duration, Instant};
+
+use crate::{Reference, Session};
+use crate::theme::{Paint, Role};
+
+const FOLLOW_UP_PREFIX: &str = "Follow-up:";
+
+struct Finding {
+    question: String,
+    answer: String,
+    citations: Vec<usize>,
+}
+
+#[derive(Default)]
+struct References {
+    merged: Vec<Reference>,
+}
+
+impl References {
+    fn merge(&mut self, references: &[Reference]) -> Vec<usize> {
+        references
+            .iter()
+            .map(|reference| match self.merged.iter().position(|r| r.url == reference.url) {
+                Some(index) => index + 1,
+                None => {
+                    self.merged.push(reference.clone());
+                    self.merged.len()
+                }
+            })
+            .collect()
+    }
+}
+
+fn renumber_citations(text: &str, citations: &[usize]) -> String {
+    let reference_regex = Regex::new(r"【(\d+)】").unwrap();
+    reference_regex.replace_all(text, |caps: &Captures| {
+        match caps[1].parse::<usize>().ok().and_then(|n| citations.get(n.wrapping_sub(1))) {
+            Some(merged) => format!("[^{}]", merged),
+            None => String::new(),
+        }
+    }).to_string()
+}
+
+fn split_follow_up(output: &str) -> (String, Option<String>) {
+    match output.rfind(FOLLOW_UP_PREFIX) {
+        Some(index) => {
+            let question = output[index + FOLLOW_UP_PREFIX.len()..]
+                .trim()
+                .trim_matches(|c| c == '*' || c == '"')
+                .trim()
+                .to_string();
+            let answer = output[..index].trim_end().trim_end_matches("**").trim_end().to_string();
+            (answer, Some(question).filter(|question| !question.is_empty()))
+        }
+        None => (output.trim().to_string(), None),
+    }
+}
+
+fn question_prompt(topic: &str, question: &str) -> String {
+    format!(
+        "You are researching the topic: {}\n\nAnswer this question thoroughly: {}\n\nEnd your answer with a single line starting with \"{}\" containing the most useful next question to deepen the research.",
+        topic, question, FOLLOW_UP_PREFIX
+    )
+}
+
+fn synthesis_prompt(topic: &str, findings: &[Finding]) -> String {
+    let mut prompt = format!(
+        "Write a concise, well-structured research report on: {}\n\nBase it on these findings, reconciling any contradictions:\n\n",
+        topic
+    );
+    for (i, finding) in findings.iter().enumerate() {
+        prompt.push_str(&format!("Q{}: {}\nA{}: {}\n\n", i + 1, finding.question, i + 1, finding.answer));
+    }
+    prompt
+}
+
+pub async fn run(session: &mut Session, topic: &str, depth: usize, time_limit: Option<Duration>) -> Result<String> {
+    let started = Instant::now();
+    let mut references = References::default();
+    let mut findings = Vec::new();
+    let mut question = Some(topic.to_string());
+
+    for level in 1..=depth.max(1) {
+        let Some(current) = question.take() else {
+            break;
+        };
+        if time_limit.is_some_and(|limit| started.elapsed() >= limit) {
+            eprintln!("{}", "Time limit reached, skipping remaining follow-ups".bright_yellow());
+            break;
+        }
+
+        eprintln!("{} {}", format!("[{}/{}]", level, depth).dimmed(), current.paint(Role::Query));
+        let response = session.send_query(&question_prompt(topic, &current)).await?;
+        let (answer, follow_up) = split_follow_up(&response.data.output);
+        let citations = references.merge(&response.data.references);
+
+        findings.push(Finding {
+            question: current,
+            answer,
+            citations,
+        });
+        question = follow_up;
+    }
+
+    eprintln!("{}", "Synthesizing report...".dimmed());
+    let synthesis = session.send_query(&synthesis_prompt(topic, &findings)).await?;
+    let synthesis_citations = references.merge(&synthesis.data.references);
+
+    let mut report = String::new();
+    report.push_str(&format!("# Research: {}\n\n", topic));
+    report.push_str(&format!(
+        "- Questions asked: {}\n- Time: {}s\n\n",
+        findings.len(),
+        started.elapsed().as_secs()
+    ));
+    report.push_str("## Report\n\n");
+    report.push_str(&renumber_citations(synthesis.data.output.trim(), &synthesis_citations));
+    report.push_str("\n\n## Findings\n");
+    for (i, finding) in findings.iter().enumerate() {
+        report.push_str(&format!("\n### {}. {}\n\n", i + 1, finding.question));
+        report.push_str(&renumber_citations(&finding.answer, &finding.citations));
+        report.push('\n');
+    }
+
+    if !references.merged.is_empty() {
+        report.push_str("\n## References\n\n");
+        for (i, reference) in references.merged.iter().enumerate() {
+            report.push_str(&format!("[^{}]: [{}]({})\n", i + 1, reference.title, reference.url));
+        }
+    }
+
+    Ok(report)
+}
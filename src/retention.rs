@@ -0,0 +1,190 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::{cache, get_data_dir, sessions};
+
+const STAMP_FILE: &str = "gc-last-run";
+const AUTO_INTERVAL_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct RetentionConfig {
+    pub sessions: Option<String>,
+    pub cache: Option<String>,
+    pub max_disk: Option<String>,
+    pub auto: Option<bool>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Session,
+    Cache,
+}
+
+pub struct StoredFile {
+    pub kind: Kind,
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: i64,
+}
+
+pub struct Policy {
+    pub sessions: Option<u64>,
+    pub cache: Option<u64>,
+    pub max_disk: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub removed: Vec<StoredFile>,
+    pub kept_size: u64,
+}
+
+impl Report {
+    pub fn count(&self, kind: Kind) -> usize {
+        self.removed.iter().filter(|file| file.kind == kind).count()
+    }
+
+    pub fn freed(&self) -> u64 {
+        self.removed.iter().map(|file| file.size).sum()
+    }
+}
+
+impl Policy {
+    pub fn from_config(config: &RetentionConfig) -> Result<Policy> {
+        Ok(Policy {
+            sessions: config.sessions.as_deref().map(cache::parse_duration).transpose().context("Invalid retention.sessions in config")?,
+            cache: config.cache.as_deref().map(cache::parse_duration).transpose().context("Invalid retention.cache in config")?,
+            max_disk: config.max_disk.as_deref().map(parse_size).transpose().context("Invalid retention.max_disk in config")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_none() && self.cache.is_none() && self.max_disk.is_none()
+    }
+}
+
+pub fn parse_size(input: &str) -> Result<u64> {
+    let input = input.trim();
+    let split_at = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split_at);
+
+    let amount: f64 = amount
+        .parse()
+        .context(format!("Invalid size: {}", input))?;
+
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        _ => anyhow::bail!("Invalid size unit in '{}' (use B, KB, MB or GB)", input),
+    };
+
+    Ok((amount * multiplier as f64) as u64)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+pub fn modified_secs(path: &Path) -> i64 {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|age| age.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn expired(file: &StoredFile, policy: &Policy, now: i64) -> bool {
+    let max_age = match file.kind {
+        Kind::Session => policy.sessions,
+        Kind::Cache => policy.cache,
+    };
+    max_age.is_some_and(|max_age| now.saturating_sub(file.last_used) > max_age as i64)
+}
+
+pub fn run(policy: &Policy, dry_run: bool) -> Result<Report> {
+    let _sessions_lock = sessions::lock_exclusive()?;
+    let _cache_lock = cache::lock_exclusive()?;
+
+    let mut files = sessions::stored_files()?;
+    files.extend(cache::stored_files()?);
+    files.sort_by_key(|file| file.last_used);
+
+    let now = Local::now().timestamp();
+    let (mut removed, kept): (Vec<_>, Vec<_>) = files.into_iter().partition(|file| expired(file, policy, now));
+
+    let mut kept_size: u64 = kept.iter().map(|file| file.size).sum();
+    let mut kept = kept.into_iter();
+    if let Some(max_disk) = policy.max_disk {
+        while kept_size > max_disk {
+            let Some(file) = kept.next() else { break };
+            kept_size -= file.size;
+            removed.push(file);
+        }
+    }
+
+    if !dry_run {
+        for file in &removed {
+            fs::remove_file(&file.path)
+                .context(format!("Failed to remove {}", file.path.display()))?;
+        }
+    }
+
+    Ok(Report { removed, kept_size })
+}
+
+pub fn run_if_due(config: &RetentionConfig) -> Result<Option<Report>> {
+    let policy = Policy::from_config(config)?;
+    if !config.auto.unwrap_or(true) || policy.is_empty() {
+        return Ok(None);
+    }
+
+    let stamp = get_data_dir()?.join(STAMP_FILE);
+    if stamp.exists() && Local::now().timestamp() - modified_secs(&stamp) < AUTO_INTERVAL_SECS {
+        return Ok(None);
+    }
+    fs::write(&stamp, b"").context(format!("Failed to write {}", stamp.display()))?;
+
+    run(&policy, false).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes_with_units() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("2kb").unwrap(), 2048);
+        assert_eq!(parse_size(" 1.5 MB ").unwrap(), 1_572_864);
+        assert_eq!(parse_size("1G").unwrap(), 1 << 30);
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("10 TB").is_err());
+        assert!(parse_size("-5").is_err());
+        assert!(parse_size("1.2.3").is_err());
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(999), "999 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(10 * 1_048_576), "10.0 MB");
+    }
+}
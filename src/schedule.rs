@@ -0,0 +1,409 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::lock::{self, StoreLock};
+use crate::{get_data_dir, write_atomic, Session};
+use crate::theme::{Paint, Role};
+
+const MAX_SEARCH_DAYS: i64 = 10 * 366;
+
+#[derive(Serialize, Deserialize)]
+pub struct Job {
+    pub id: u32,
+    pub cron: String,
+    pub query: String,
+    pub created_at: i64,
+    pub last_run: Option<i64>,
+    pub last_output: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Schedule {
+    pub jobs: Vec<Job>,
+}
+
+#[derive(Serialize)]
+struct RunRecord<'a> {
+    timestamp: i64,
+    changed: bool,
+    output: &'a str,
+}
+
+struct CronField {
+    allowed: Vec<bool>,
+    any: bool,
+}
+
+pub struct Cron {
+    minute: CronField,
+    hour: CronField,
+    day: CronField,
+    month: CronField,
+    weekday: CronField,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<CronField> {
+    let mut allowed = vec![false; max as usize + 1];
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|&step| step > 0).context(format!("Invalid step in cron field: {}", field))?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                None => {
+                    let value = range.parse().context(format!("Invalid cron field: {}", field))?;
+                    (value, if part.contains('/') { max } else { value })
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            anyhow::bail!("Cron field out of range ({}-{}): {}", min, max, field);
+        }
+        for value in (start..=end).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+
+    let any = allowed[min as usize..].iter().all(|&allowed| allowed);
+    Ok(CronField { allowed, any })
+}
+
+impl CronField {
+    fn matches(&self, value: u32) -> bool {
+        self.allowed.get(value as usize).copied().unwrap_or(false)
+    }
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            anyhow::bail!("Cron expressions need 5 fields (minute hour day month weekday): {}", expression);
+        }
+
+        let mut weekday = parse_field(fields[4], 0, 7)?;
+        let sunday = weekday.allowed[0] || weekday.allowed[7];
+        weekday.allowed[0] = sunday;
+        weekday.allowed[7] = sunday;
+        weekday.any = weekday.allowed.iter().all(|&allowed| allowed);
+
+        Ok(Self {
+            minute: parse_field(fields[0], 0, 59)?,
+            hour: parse_field(fields[1], 0, 23)?,
+            day: parse_field(fields[2], 1, 31)?,
+            month: parse_field(fields[3], 1, 12)?,
+            weekday,
+        })
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.day.matches(date.day());
+        let weekday = self.weekday.matches(date.weekday().num_days_from_sunday());
+        match (self.day.any, self.weekday.any) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = time.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = time + Duration::days(MAX_SEARCH_DAYS);
+
+        while time <= limit {
+            if !self.month.matches(time.month()) {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.day_matches(time.date()) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !self.hour.matches(time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !self.minute.matches(time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn fired_between(&self, since: i64, now: NaiveDateTime) -> bool {
+        let Some(since) = Local.timestamp_opt(since, 0).single() else {
+            return true;
+        };
+        self.next_after(since.naive_local()).is_some_and(|next| next <= now)
+    }
+}
+
+pub fn split_spec(spec: &str) -> Result<(String, String)> {
+    let mut fields = Vec::new();
+    let mut rest = spec.trim();
+    for _ in 0..5 {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    if rest.is_empty() {
+        anyhow::bail!("Expected \"<minute> <hour> <day> <month> <weekday> <query>\", got: {}", spec);
+    }
+
+    let cron = fields.join(" ");
+    Cron::parse(&cron)?;
+    Ok((cron, rest.to_string()))
+}
+
+fn schedule_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("schedule.json"))
+}
+
+fn results_path(id: u32) -> Result<PathBuf> {
+    let dir = get_data_dir()?.join("schedule");
+    fs::create_dir_all(&dir)
+        .context("Failed to create schedule results directory")?;
+    Ok(dir.join(format!("{}.jsonl", id)))
+}
+
+pub fn load() -> Result<Schedule> {
+    let path = schedule_path()?;
+    if !path.exists() {
+        return Ok(Schedule::default());
+    }
+
+    let content = fs::read_to_string(&path)
+        .context(format!("Failed to read schedule: {}", path.display()))?;
+
+    serde_json::from_str(&content)
+        .context(format!("Failed to parse schedule: {}", path.display()))
+}
+
+fn save(schedule: &Schedule) -> Result<()> {
+    let path = schedule_path()?;
+    let content = serde_json::to_string_pretty(schedule)
+        .context("Failed to serialize schedule")?;
+
+    write_atomic(&path, content.as_bytes())
+        .context(format!("Failed to write schedule: {}", path.display()))
+}
+
+fn lock_exclusive() -> Result<StoreLock> {
+    lock::exclusive(&schedule_path()?.with_extension("json.lock"))
+}
+
+pub fn update<T>(change: impl FnOnce(&mut Schedule) -> Result<T>) -> Result<T> {
+    let _lock = lock_exclusive()?;
+    let mut schedule = load()?;
+    let result = change(&mut schedule)?;
+    save(&schedule)?;
+    Ok(result)
+}
+
+impl Schedule {
+    pub fn add(&mut self, cron: String, query: String) -> u32 {
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            id,
+            cron,
+            query,
+            created_at: Local::now().timestamp(),
+            last_run: None,
+            last_output: None,
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<()> {
+        let before = self.jobs.len();
+        self.jobs.retain(|job| job.id != id);
+        if self.jobs.len() == before {
+            anyhow::bail!("No scheduled query with ID {}", id);
+        }
+        Ok(())
+    }
+
+    pub fn record_runs(&mut self, ran: Schedule) {
+        for ran in ran.jobs {
+            let stored = self.jobs.iter_mut().find(|job| job.id == ran.id && job.created_at == ran.created_at);
+            if let Some(job) = stored.filter(|job| ran.last_run > job.last_run) {
+                job.last_run = ran.last_run;
+                job.last_output = ran.last_output;
+            }
+        }
+    }
+}
+
+fn record_result(id: u32, changed: bool, output: &str) -> Result<()> {
+    let line = serde_json::to_string(&RunRecord {
+        timestamp: Local::now().timestamp(),
+        changed,
+        output,
+    })
+    .context("Failed to serialize schedule result")?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(results_path(id)?)
+        .context("Failed to open schedule results")?;
+
+    writeln!(file, "{}", line)
+        .context("Failed to write schedule result")
+}
+
+fn print_diff(previous: &str, current: &str) {
+    let diff = TextDiff::from_lines(previous, current);
+    for change in diff.iter_all_changes() {
+        let line = change.to_string_lossy();
+        let line = line.trim_end_matches('\n');
+        match change.tag() {
+            ChangeTag::Delete => println!("{}", format!("- {}", line).bright_red()),
+            ChangeTag::Insert => println!("{}", format!("+ {}", line).bright_green()),
+            ChangeTag::Equal => {}
+        }
+    }
+}
+
+pub async fn run(session: &mut Session, schedule: &mut Schedule, all: bool) -> Result<()> {
+    let now = Local::now();
+
+    for job in schedule.jobs.iter_mut() {
+        let cron = match Cron::parse(&job.cron) {
+            Ok(cron) => cron,
+            Err(e) => {
+                eprintln!("{} [{}] {}: {}", "Error:".paint(Role::Error).bold(), job.id, job.cron, e);
+                continue;
+            }
+        };
+        if !all && !cron.fired_between(job.last_run.unwrap_or(job.created_at), now.naive_local()) {
+            continue;
+        }
+
+        let response = match session.send_query(&job.query).await {
+            Ok(response) => response,
+            Err(e) => {
+                eprintln!("{} [{}] {}: {}", "Error:".paint(Role::Error).bold(), job.id, job.query, e);
+                continue;
+            }
+        };
+        let output = response.data.output.trim().to_string();
+        let changed = job.last_output.as_deref() != Some(output.as_str());
+
+        if changed {
+            println!("{} {}", format!("[{}]", job.id).bright_cyan(), job.query.paint(Role::Query).bold());
+            match &job.last_output {
+                Some(previous) => print_diff(previous, &output),
+                None => println!("{}", output),
+            }
+            println!();
+        }
+
+        record_result(job.id, changed, &output)?;
+        job.last_run = Some(now.timestamp());
+        job.last_output = Some(output);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn next(expression: &str, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        Cron::parse(expression).unwrap().next_after(after)
+    }
+
+    #[test]
+    fn parses_lists_ranges_and_steps() {
+        let field = parse_field("1,5-7,*/20", 0, 59).unwrap();
+        let allowed: Vec<u32> = (0..60).filter(|&value| field.matches(value)).collect();
+        assert_eq!(allowed, [0, 1, 5, 6, 7, 20, 40]);
+        assert!(!field.any);
+
+        let field = parse_field("10/15", 0, 59).unwrap();
+        assert!(field.matches(10) && field.matches(25) && field.matches(55) && !field.matches(0));
+    }
+
+    #[test]
+    fn treats_unrestricted_steps_as_any() {
+        assert!(parse_field("*", 1, 31).unwrap().any);
+        assert!(parse_field("*/1", 1, 31).unwrap().any);
+        assert!(parse_field("1-31", 1, 31).unwrap().any);
+        assert!(!parse_field("*/2", 1, 31).unwrap().any);
+        assert!(Cron::parse("* * * * 0-6").unwrap().weekday.any);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!(Cron::parse("* * * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("* * 0 * *").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+        assert!(Cron::parse("5-1 * * * *").is_err());
+        assert!(Cron::parse("a * * * *").is_err());
+    }
+
+    #[test]
+    fn finds_the_next_fire_time() {
+        assert_eq!(next("*/15 * * * *", at(2026, 3, 1, 10, 7)), Some(at(2026, 3, 1, 10, 15)));
+        assert_eq!(next("0 9 * * *", at(2026, 3, 1, 9, 0)), Some(at(2026, 3, 2, 9, 0)));
+        assert_eq!(next("30 8 1 * *", at(2026, 12, 15, 0, 0)), Some(at(2027, 1, 1, 8, 30)));
+        assert_eq!(next("0 0 29 2 *", at(2026, 3, 1, 0, 0)), Some(at(2028, 2, 29, 0, 0)));
+        assert_eq!(next("0 0 30 2 *", at(2026, 3, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn matches_day_or_weekday_when_both_are_restricted() {
+        // 2026-03-02 is a Monday.
+        assert_eq!(next("0 12 15 * 1", at(2026, 3, 1, 0, 0)), Some(at(2026, 3, 2, 12, 0)));
+        assert_eq!(next("0 12 */1 * 1", at(2026, 3, 3, 0, 0)), Some(at(2026, 3, 9, 12, 0)));
+        assert_eq!(next("0 12 * * 7", at(2026, 3, 2, 0, 0)), Some(at(2026, 3, 8, 12, 0)));
+    }
+
+    #[test]
+    fn reports_whether_a_job_fired() {
+        let cron = Cron::parse("0 9 * * *").unwrap();
+        let since = Local.from_local_datetime(&at(2026, 3, 1, 8, 0)).unwrap().timestamp();
+        assert!(!cron.fired_between(since, at(2026, 3, 1, 8, 59)));
+        assert!(cron.fired_between(since, at(2026, 3, 1, 9, 0)));
+    }
+
+    #[test]
+    fn keeps_the_query_intact() {
+        let (cron, query) = split_spec("  0  9 * * 1-5   Summarize  \"a   b\"  ").unwrap();
+        assert_eq!(cron, "0 9 * * 1-5");
+        assert_eq!(query, "Summarize  \"a   b\"");
+        assert!(split_spec("0 9 * * 1-5").is_err());
+    }
+
+    #[test]
+    fn records_runs_without_losing_concurrent_changes() {
+        let job = |id, created_at, last_run: Option<i64>| Job {
+            id,
+            cron: "* * * * *".to_string(),
+            query: format!("query {}", id),
+            created_at,
+            last_run,
+            last_output: last_run.map(|run| run.to_string()),
+        };
+        let ran = Schedule { jobs: vec![job(1, 10, Some(100)), job(2, 10, Some(100)), job(3, 10, Some(100))] };
+        let mut stored = Schedule { jobs: vec![job(1, 10, None), job(3, 50, None), job(4, 60, None)] };
+        stored.record_runs(ran);
+
+        let runs: Vec<_> = stored.jobs.iter().map(|job| (job.id, job.last_run)).collect();
+        assert_eq!(runs, [(1, Some(100)), (3, None), (4, None)]);
+        assert_eq!(stored.jobs[0].last_output.as_deref(), Some("100"));
+    }
+}
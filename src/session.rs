@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+use crate::api::{CallOptions, Client, FastGPTRequest, FastGPTResponse};
+use crate::error::{Error, Result};
+use crate::prompt;
+
+pub const DEFAULT_CONTEXT_BUDGET: usize = 16_000;
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub query: String,
+    pub response: FastGPTResponse,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Context {
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub cache: bool,
+    pub web_search: bool,
+    pub system_prompt: Option<String>,
+    pub context_budget: usize,
+    pub enforce_context_budget: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            cache: true,
+            web_search: true,
+            system_prompt: None,
+            context_budget: DEFAULT_CONTEXT_BUDGET,
+            enforce_context_budget: false,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    #[serde(default)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub contexts: Vec<Context>,
+    #[serde(default)]
+    pub history: Vec<Exchange>,
+}
+
+pub struct Session {
+    client: Client,
+    settings: Settings,
+    contexts: Vec<Context>,
+    history: Vec<Exchange>,
+}
+
+#[derive(Default)]
+pub struct SessionBuilder {
+    backend: Option<Client>,
+    api_key: Option<String>,
+    settings: Settings,
+}
+
+impl SessionBuilder {
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn backend(mut self, client: Client) -> Self {
+        self.backend = Some(client);
+        self
+    }
+
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.settings.cache = cache;
+        self
+    }
+
+    pub fn web_search(mut self, web_search: bool) -> Self {
+        self.settings.web_search = web_search;
+        self
+    }
+
+    pub fn system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        let prompt = prompt.into();
+        self.settings.system_prompt = if prompt.trim().is_empty() { None } else { Some(prompt) };
+        self
+    }
+
+    pub fn context_budget(mut self, tokens: usize) -> Self {
+        self.settings.context_budget = tokens;
+        self
+    }
+
+    pub fn enforce_context_budget(mut self, enforce: bool) -> Self {
+        self.settings.enforce_context_budget = enforce;
+        self
+    }
+
+    fn client(&mut self) -> Result<Client> {
+        match (self.backend.take(), self.api_key.take()) {
+            (Some(client), _) => Ok(client),
+            (None, Some(api_key)) => Ok(Client::new(api_key)),
+            (None, None) => Err(Error::Config("a session needs an API key or a backend client".to_string())),
+        }
+    }
+
+    pub fn build(mut self) -> Result<Session> {
+        Ok(Session {
+            client: self.client()?,
+            settings: self.settings,
+            contexts: Vec::new(),
+            history: Vec::new(),
+        })
+    }
+
+    pub fn restore(mut self, snapshot: Snapshot) -> Result<Session> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(Error::Snapshot(format!("unsupported version {} (expected {})", snapshot.version, SNAPSHOT_VERSION)));
+        }
+        Ok(Session {
+            client: self.client()?,
+            settings: snapshot.settings,
+            contexts: snapshot.contexts,
+            history: snapshot.history,
+        })
+    }
+}
+
+impl Session {
+    pub fn builder() -> SessionBuilder {
+        SessionBuilder::default()
+    }
+
+    pub fn history(&self) -> &[Exchange] {
+        &self.history
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn contexts(&self) -> &[Context] {
+        &self.contexts
+    }
+
+    pub fn add_context(&mut self, name: impl Into<String>, content: impl Into<String>) {
+        let name = name.into();
+        let content = content.into();
+        match self.contexts.iter_mut().find(|context| context.name == name) {
+            Some(context) => context.content = content,
+            None => self.contexts.push(Context { name, content }),
+        }
+    }
+
+    pub fn remove_context(&mut self, name: &str) -> bool {
+        let before = self.contexts.len();
+        self.contexts.retain(|context| context.name != name);
+        self.contexts.len() != before
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            settings: self.settings.clone(),
+            contexts: self.contexts.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    pub fn contextual_query(&self, query: &str) -> String {
+        let instructions: Vec<&str> = self.settings.system_prompt.iter().map(String::as_str).collect();
+        let files: Vec<prompt::File> = self
+            .contexts
+            .iter()
+            .map(|context| prompt::File { name: &context.name, label: None, content: &context.content })
+            .collect();
+        let history: Vec<prompt::Turn> = self
+            .history
+            .iter()
+            .map(|exchange| prompt::Turn { query: &exchange.query, answer: &exchange.response.data.output })
+            .collect();
+        let budget = prompt::Budget { tokens: self.settings.context_budget, count: &prompt::estimate_tokens };
+        let budget = self.settings.enforce_context_budget.then_some(&budget);
+        prompt::build(query, &instructions, &files, &history, budget).0
+    }
+
+    pub async fn ask(&mut self, query: &str) -> Result<FastGPTResponse> {
+        self.ask_with(query, &CallOptions::default()).await
+    }
+
+    pub async fn ask_with(&mut self, query: &str, options: &CallOptions) -> Result<FastGPTResponse> {
+        let request = FastGPTRequest {
+            query: self.contextual_query(query),
+            cache: self.settings.cache,
+            web_search: self.settings.web_search,
+        };
+        let response = self.client.query_with(&request, options).await?;
+        self.history.push(Exchange { query: query.to_string(), response: response.clone() });
+        Ok(response)
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::lock::{self, StoreLock};
+use crate::retention::{self, StoredFile};
+use crate::{get_data_dir, write_atomic, FastGPTResponse};
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedSession {
+    pub id: String,
+    pub created_at: i64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    pub exchanges: Vec<SavedExchange>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedExchange {
+    pub timestamp: i64,
+    pub query: String,
+    pub prompt: String,
+    pub response: FastGPTResponse,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<Rating>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Rating {
+    pub score: u8,
+    pub comment: Option<String>,
+    pub timestamp: i64,
+}
+
+pub fn get_sessions_dir() -> Result<PathBuf> {
+    let dir = get_data_dir()?.join("sessions");
+    fs::create_dir_all(&dir)
+        .context("Failed to create sessions directory")?;
+    Ok(dir)
+}
+
+pub fn session_path(id: &str) -> Result<PathBuf> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        anyhow::bail!("Invalid session ID '{}' (IDs contain only letters, digits, '-' and '_')", id);
+    }
+    Ok(get_sessions_dir()?.join(format!("{}.json", id)))
+}
+
+pub fn format_time(timestamp: i64) -> String {
+    match Local.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+pub fn lock_exclusive() -> Result<StoreLock> {
+    lock::exclusive(&get_sessions_dir()?.join(lock::LOCK_FILE))
+}
+
+fn lock_shared() -> Result<StoreLock> {
+    lock::shared(&get_sessions_dir()?.join(lock::LOCK_FILE))
+}
+
+pub fn load(id: &str) -> Result<SavedSession> {
+    let _lock = lock_shared()?;
+    read(id)
+}
+
+fn read(id: &str) -> Result<SavedSession> {
+    let path = session_path(id)?;
+    if !path.exists() {
+        anyhow::bail!("No saved session with ID {}", id);
+    }
+
+    let content = fs::read_to_string(&path)
+        .context(format!("Failed to read session file: {}", path.display()))?;
+
+    serde_json::from_str(&content)
+        .context(format!("Failed to parse session file: {}", path.display()))
+}
+
+fn save(session: &SavedSession) -> Result<()> {
+    let path = session_path(&session.id)?;
+    let content = serde_json::to_string_pretty(session)
+        .context("Failed to serialize session")?;
+
+    write_atomic(&path, content.as_bytes())
+        .context(format!("Failed to write session file: {}", path.display()))
+}
+
+fn read_or_create(id: &str) -> Result<SavedSession> {
+    if session_path(id)?.exists() {
+        read(id)
+    } else {
+        Ok(SavedSession {
+            id: id.to_string(),
+            created_at: Local::now().timestamp(),
+            metadata: BTreeMap::new(),
+            exchanges: Vec::new(),
+        })
+    }
+}
+
+pub fn append_exchange(id: &str, query: &str, prompt: &str, response: &FastGPTResponse) -> Result<()> {
+    let _lock = lock_exclusive()?;
+    let mut session = read_or_create(id)?;
+
+    session.exchanges.push(SavedExchange {
+        timestamp: Local::now().timestamp(),
+        query: query.to_string(),
+        prompt: prompt.to_string(),
+        response: response.clone(),
+        rating: None,
+    });
+
+    save(&session)
+}
+
+pub fn set_metadata(id: &str, metadata: &BTreeMap<String, String>) -> Result<()> {
+    let _lock = lock_exclusive()?;
+    let mut session = read_or_create(id)?;
+    session.metadata = metadata.clone();
+    save(&session)
+}
+
+pub fn parse_metadata(pair: &str) -> Result<(String, String)> {
+    let (key, value) = pair
+        .split_once('=')
+        .context(format!("Invalid metadata '{}' (expected key=value)", pair))?;
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        anyhow::bail!("Invalid metadata key in '{}' (keys cannot be empty or contain spaces)", pair);
+    }
+    Ok((key.to_string(), value.trim().to_string()))
+}
+
+pub fn load_all() -> Result<Vec<SavedSession>> {
+    let _lock = lock_shared()?;
+    let mut sessions = Vec::new();
+
+    for entry in fs::read_dir(get_sessions_dir()?).context("Failed to read sessions directory")? {
+        let path = entry.context("Failed to read sessions directory entry")?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        if let Ok(content) = fs::read_to_string(&path) {
+            if let Ok(session) = serde_json::from_str::<SavedSession>(&content) {
+                sessions.push(session);
+            }
+        }
+    }
+
+    sessions.sort_by_key(|session| session.created_at);
+    Ok(sessions)
+}
+
+pub fn stored_files() -> Result<Vec<StoredFile>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(get_sessions_dir()?).context("Failed to read sessions directory")? {
+        let path = entry.context("Failed to read sessions directory entry")?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let last_used = fs::read_to_string(&path).ok()
+            .and_then(|content| serde_json::from_str::<SavedSession>(&content).ok())
+            .map(|session| session.exchanges.last().map_or(session.created_at, |exchange| exchange.timestamp))
+            .unwrap_or_else(|| retention::modified_secs(&path));
+        files.push(StoredFile {
+            kind: retention::Kind::Session,
+            size: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            path,
+            last_used,
+        });
+    }
+
+    Ok(files)
+}
+
+pub fn rate_last_exchange(id: &str, score: u8, comment: Option<String>) -> Result<usize> {
+    let _lock = lock_exclusive()?;
+    if !session_path(id)?.exists() {
+        anyhow::bail!("Nothing to rate yet: ask a question first");
+    }
+
+    let mut session = read(id)?;
+    let exchange_count = session.exchanges.len();
+    let exchange = session.exchanges
+        .last_mut()
+        .context("Nothing to rate yet: ask a question first")?;
+
+    exchange.rating = Some(Rating {
+        score,
+        comment,
+        timestamp: Local::now().timestamp(),
+    });
+
+    save(&session)?;
+    Ok(exchange_count)
+}
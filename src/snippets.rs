@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::get_data_dir;
+
+pub type Snippets = BTreeMap<String, String>;
+
+fn snippets_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("snippets.json"))
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub fn load() -> Result<Snippets> {
+    let Ok(path) = snippets_path() else {
+        return Ok(Snippets::new());
+    };
+    if !path.exists() {
+        return Ok(Snippets::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .context(format!("Failed to read snippets file: {}", path.display()))?;
+
+    serde_json::from_str(&content)
+        .context(format!("Failed to parse snippets file: {}", path.display()))
+}
+
+pub fn save(snippets: &Snippets) -> Result<()> {
+    let path = snippets_path()?;
+    let content = serde_json::to_string_pretty(snippets)
+        .context("Failed to serialize snippets")?;
+
+    fs::write(&path, content)
+        .context(format!("Failed to write snippets file: {}", path.display()))
+}
+
+pub fn expand(text: &str, snippets: &Snippets) -> String {
+    if snippets.is_empty() {
+        return text.to_string();
+    }
+
+    let snippet_regex = Regex::new(r"(^|\s)#([A-Za-z][\w-]*)").unwrap();
+    snippet_regex.replace_all(text, |caps: &Captures| {
+        match snippets.get(&caps[2]) {
+            Some(snippet) => format!("{}{}", &caps[1], snippet),
+            None => caps[0].to_string(),
+        }
+    }).to_string()
+}
@@ -0,0 +1,127 @@
+use colored::*;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use textwrap::core::display_width;
+
+use crate::csv_field;
+use crate::output::remove_reference_numbers;
+use crate::theme::{Paint, Role};
+
+pub const TABLE_INSTRUCTION: &str = "Format the answer as a single Markdown table with a header row. Do not add any text before or after the table.";
+
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+pub fn table_query(query: &str) -> String {
+    format!("{}\n\n{}", query, TABLE_INSTRUCTION)
+}
+
+pub fn parse(markdown: &str) -> Option<Table> {
+    let mut header = Vec::new();
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut in_table = false;
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES) {
+        match event {
+            Event::Start(Tag::Table(_)) => in_table = true,
+            Event::End(TagEnd::Table) => break,
+            Event::Text(text) | Event::Code(text) if in_table => cell.push_str(&text),
+            Event::SoftBreak | Event::HardBreak if in_table => cell.push(' '),
+            Event::End(TagEnd::TableCell) => {
+                row.push(remove_reference_numbers(&cell).trim().to_string());
+                cell.clear();
+            }
+            Event::End(TagEnd::TableHead) => header = std::mem::take(&mut row),
+            Event::End(TagEnd::TableRow) => rows.push(std::mem::take(&mut row)),
+            _ => {}
+        }
+    }
+
+    if header.is_empty() {
+        return None;
+    }
+    Some(Table { header, rows })
+}
+
+impl Table {
+    fn columns(&self) -> usize {
+        self.rows.iter().map(Vec::len).chain([self.header.len()]).max().unwrap_or(0)
+    }
+
+    pub fn render(&self) -> String {
+        let columns = self.columns();
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                std::iter::once(&self.header)
+                    .chain(&self.rows)
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| display_width(cell))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let format_row = |row: &[String], header: bool| -> String {
+            (0..columns)
+                .map(|column| {
+                    let cell = row.get(column).map(String::as_str).unwrap_or("");
+                    let padded = format!("{}{}", cell, " ".repeat(widths[column].saturating_sub(display_width(cell))));
+                    if header { padded.paint(Role::Answer).bold().to_string() } else { padded }
+                })
+                .collect::<Vec<_>>()
+                .join(&" │ ".dimmed().to_string())
+        };
+
+        let mut lines = vec![format_row(&self.header, true)];
+        let separator: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+        lines.push(separator.join("─┼─").dimmed().to_string());
+        for row in &self.rows {
+            lines.push(format_row(row, false));
+        }
+        lines.join("\n")
+    }
+
+    pub fn to_csv(&self) -> String {
+        let columns = self.columns();
+        std::iter::once(&self.header)
+            .chain(&self.rows)
+            .map(|row| {
+                (0..columns)
+                    .map(|column| csv_field(row.get(column).map(String::as_str).unwrap_or("")))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .map(|line| line + "\n")
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_first_table() {
+        let markdown = "Intro\n\n| Name | Year |\n|---|---|\n| Dune【1】 | 1965 |\n| `Emma` | 1815 |\n\n| Other |\n|---|\n| x |\n";
+        let table = parse(markdown).unwrap();
+        assert_eq!(table.header, ["Name", "Year"]);
+        assert_eq!(table.rows, [["Dune", "1965"], ["Emma", "1815"]]);
+    }
+
+    #[test]
+    fn returns_none_without_a_table() {
+        assert!(parse("Just text\n\n- a list").is_none());
+    }
+
+    #[test]
+    fn writes_csv_with_padded_rows() {
+        let table = Table {
+            header: vec!["a".to_string(), "b".to_string()],
+            rows: vec![vec!["1, 2".to_string()], vec!["x".to_string(), "say \"hi\"".to_string()]],
+        };
+        assert_eq!(table.to_csv(), "a,b\n\"1, 2\",\nx,\"say \"\"hi\"\"\"\n");
+    }
+}
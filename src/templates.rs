@@ -0,0 +1,213 @@
+use anyhow::{Context, Result};
+use colored::*;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::assets;
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Source {
+    Builtin,
+    Customized,
+    Custom,
+}
+
+pub struct Template {
+    pub name: String,
+    pub description: String,
+    pub body: String,
+    pub source: Source,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TemplateFile {
+    description: String,
+    body: String,
+}
+
+fn placeholder_regex() -> Regex {
+    Regex::new(r"\{\{\s*([A-Za-z_][\w-]*)\s*(?:\|([^}]*))?\}\}").unwrap()
+}
+
+fn asset_path(name: &str) -> String {
+    format!("templates/{}.toml", name)
+}
+
+pub fn user_path(name: &str) -> Result<PathBuf> {
+    let dir = assets::override_dir().context("Could not determine config directory")?;
+    Ok(dir.join(asset_path(name)))
+}
+
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        anyhow::bail!("Invalid template name '{}': use letters, digits, '-' and '_'", name);
+    }
+    Ok(())
+}
+
+pub fn to_toml(description: &str, body: &str) -> Result<String> {
+    toml::to_string(&TemplateFile { description: description.to_string(), body: body.to_string() })
+        .context("Failed to serialize template")
+}
+
+pub fn parse(content: &str) -> Result<()> {
+    toml::from_str::<TemplateFile>(content).context("Invalid template (expected `description` and `body` strings)")?;
+    Ok(())
+}
+
+pub fn all() -> Vec<Template> {
+    assets::files("templates")
+        .iter()
+        .filter(|path| path.ends_with(".toml"))
+        .filter_map(|path| {
+            let name = Path::new(path).file_stem()?.to_string_lossy().to_string();
+            let content = assets::read(path)?;
+            let customized = user_path(&name).is_ok_and(|path| path.exists());
+            let source = match (assets::is_embedded(path), customized) {
+                (true, false) => Source::Builtin,
+                (true, true) => Source::Customized,
+                (false, _) => Source::Custom,
+            };
+            match toml::from_str::<TemplateFile>(&content) {
+                Ok(file) => Some(Template { name, description: file.description, body: file.body, source }),
+                Err(e) => {
+                    eprintln!("{} invalid template asset {}: {}", "Warning:".bright_yellow(), path, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub fn find(name: &str) -> Option<Template> {
+    all().into_iter().find(|template| template.name == name)
+}
+
+pub fn content(name: &str) -> Option<String> {
+    assets::read(&asset_path(name))
+}
+
+pub fn save(name: &str, content: &str) -> Result<PathBuf> {
+    let path = user_path(name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory: {}", parent.display()))?;
+    }
+    fs::write(&path, format!("{}\n", content.trim_end()))
+        .context(format!("Failed to write file: {}", path.display()))?;
+    Ok(path)
+}
+
+pub fn remove(name: &str) -> Result<Source> {
+    let template = find(name).context(format!("Unknown template '{}'", name))?;
+    if template.source == Source::Builtin {
+        anyhow::bail!("'{}' is a built-in template and can't be removed", name);
+    }
+    let path = user_path(name)?;
+    fs::remove_file(&path)
+        .context(format!("Failed to remove file: {}", path.display()))?;
+    Ok(template.source)
+}
+
+impl Template {
+    pub fn params(&self) -> Vec<String> {
+        let mut params: Vec<String> = Vec::new();
+        for caps in placeholder_regex().captures_iter(&self.body) {
+            if !params.iter().any(|param| param == &caps[1]) {
+                params.push(caps[1].to_string());
+            }
+        }
+        params
+    }
+
+    fn required_params(&self) -> Vec<String> {
+        placeholder_regex()
+            .captures_iter(&self.body)
+            .filter(|caps| caps.get(2).is_none())
+            .map(|caps| caps[1].to_string())
+            .collect()
+    }
+
+    pub fn usage(&self) -> String {
+        let required = self.required_params();
+        let params: Vec<String> = self.params()
+            .iter()
+            .map(|param| if required.contains(param) { format!("<{}>", param) } else { format!("[{}]", param) })
+            .collect();
+        format!("{} {}", self.name, params.join(" "))
+    }
+
+    pub fn render(&self, args: &[String]) -> Result<String> {
+        let params = self.params();
+        let required = self.required_params();
+
+        let mut values: Vec<Option<String>> = params.iter().map(|_| None).collect();
+        let fill = args.len().min(params.len());
+        for (i, value) in values.iter_mut().enumerate().take(fill) {
+            *value = Some(args[i].clone());
+        }
+        if args.len() > params.len() && !params.is_empty() {
+            let last = params.len() - 1;
+            values[last] = Some(args[last..].join(" "));
+        }
+
+        if let Some(missing) = params
+            .iter()
+            .zip(&values)
+            .find(|(param, value)| value.is_none() && required.contains(param))
+        {
+            anyhow::bail!("Missing <{}> for template '{}' (usage: {})", missing.0, self.name, self.usage());
+        }
+
+        Ok(placeholder_regex().replace_all(&self.body, |caps: &Captures| {
+            let index = params.iter().position(|param| param == &caps[1]).unwrap_or(0);
+            values[index]
+                .clone()
+                .or_else(|| caps.get(2).map(|default| default.as_str().trim().to_string()))
+                .unwrap_or_default()
+        }).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(body: &str) -> Template {
+        Template { name: "t".to_string(), description: String::new(), body: body.to_string(), source: Source::Custom }
+    }
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn lists_params_once_in_order() {
+        let t = template("{{lang}} {{ code }} {{lang}} {{style|terse}}");
+        assert_eq!(t.params(), ["lang", "code", "style"]);
+        assert_eq!(t.usage(), "t <lang> <code> [style]");
+    }
+
+    #[test]
+    fn renders_values_and_defaults() {
+        let t = template("Explain {{topic}} {{tone|simply}}.");
+        assert_eq!(t.render(&args(&["monads"])).unwrap(), "Explain monads simply.");
+        assert_eq!(t.render(&args(&["monads", "formally"])).unwrap(), "Explain monads formally.");
+    }
+
+    #[test]
+    fn joins_extra_args_into_the_last_param() {
+        let t = template("Translate to {{lang}}: {{text}}");
+        assert_eq!(t.render(&args(&["French", "good", "morning"])).unwrap(), "Translate to French: good morning");
+    }
+
+    #[test]
+    fn rejects_missing_required_params() {
+        let t = template("Compare {{a}} and {{b}}");
+        assert!(t.render(&args(&["x"])).is_err());
+        assert_eq!(template("No params").render(&args(&["ignored"])).unwrap(), "No params");
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use colored::{Color, ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+pub const PRESETS: &[&str] = &["dark", "light", "solarized-light"];
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+#[derive(Clone, Copy)]
+pub enum Role {
+    Query,
+    Answer,
+    References,
+    Error,
+    Banner,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ThemeConfig {
+    pub preset: Option<String>,
+    pub query: Option<String>,
+    pub answer: Option<String>,
+    pub references: Option<String>,
+    pub error: Option<String>,
+    pub banner: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    query: Color,
+    answer: Color,
+    references: Color,
+    error: Color,
+    banner: Color,
+    code: &'static str,
+}
+
+impl Theme {
+    fn preset(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme {
+                query: Color::BrightWhite,
+                answer: Color::BrightWhite,
+                references: Color::BrightYellow,
+                error: Color::BrightRed,
+                banner: Color::BrightBlue,
+                code: "base16-ocean.dark",
+            }),
+            "light" => Some(Theme {
+                query: Color::Black,
+                answer: Color::Black,
+                references: Color::Magenta,
+                error: Color::Red,
+                banner: Color::Blue,
+                code: "InspiredGitHub",
+            }),
+            "solarized-light" => Some(Theme {
+                query: Color::TrueColor { r: 7, g: 54, b: 66 },
+                answer: Color::TrueColor { r: 88, g: 110, b: 117 },
+                references: Color::TrueColor { r: 181, g: 137, b: 0 },
+                error: Color::TrueColor { r: 220, g: 50, b: 47 },
+                banner: Color::TrueColor { r: 38, g: 139, b: 210 },
+                code: "Solarized (light)",
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn from_config(config: &ThemeConfig) -> Result<Theme> {
+        let preset = config.preset.as_deref().unwrap_or("dark");
+        let mut theme = Theme::preset(preset)
+            .context(format!("Unknown theme preset '{}' (expected one of: {})", preset, PRESETS.join(", ")))?;
+
+        for (value, color) in [
+            (&config.query, &mut theme.query),
+            (&config.answer, &mut theme.answer),
+            (&config.references, &mut theme.references),
+            (&config.error, &mut theme.error),
+            (&config.banner, &mut theme.banner),
+        ] {
+            if let Some(value) = value {
+                *color = parse_color(value)?;
+            }
+        }
+        Ok(theme)
+    }
+
+    fn color(&self, role: Role) -> Color {
+        match role {
+            Role::Query => self.query,
+            Role::Answer => self.answer,
+            Role::References => self.references,
+            Role::Error => self.error,
+            Role::Banner => self.banner,
+        }
+    }
+}
+
+fn parse_color(value: &str) -> Result<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|part| u8::from_str_radix(part, 16).ok());
+        if let (6, Some(r), Some(g), Some(b)) = (hex.len(), channel(0), channel(2), channel(4)) {
+            return Ok(Color::TrueColor { r, g, b });
+        }
+        anyhow::bail!("Invalid theme color '{}' (expected #rrggbb)", value);
+    }
+    value
+        .replace(['_', '-'], " ")
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid theme color '{}' (expected a name like 'bright blue' or #rrggbb)", value))
+}
+
+pub fn init(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+fn current() -> Theme {
+    THEME.get().copied().unwrap_or_else(|| Theme::preset("dark").unwrap())
+}
+
+pub fn code_theme() -> &'static str {
+    current().code
+}
+
+pub trait Paint {
+    fn paint(self, role: Role) -> ColoredString;
+}
+
+impl<T: Colorize> Paint for T {
+    fn paint(self, role: Role) -> ColoredString {
+        self.color(current().color(role))
+    }
+}
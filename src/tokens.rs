@@ -0,0 +1,116 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, Once};
+use std::time::{Duration, Instant};
+
+use crate::{content_hash, COMMAND_TIMEOUT};
+
+pub const TOKENIZERS: &[&str] = &["heuristic", "bpe", "command"];
+const MAX_OUTPUT_BYTES: u64 = 4096;
+
+enum Backend {
+    Heuristic,
+    Bpe,
+    Command(String),
+}
+
+pub struct Tokenizer {
+    backend: Backend,
+    counts: Mutex<HashMap<u64, usize>>,
+    fallback_warning: Once,
+}
+
+fn heuristic(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+fn run_command(command: &str, text: &str) -> Result<usize> {
+    let parts = shell_words::split(command).context(format!("Invalid tokenizer_command: {}", command))?;
+    let (program, args) = parts.split_first().context("tokenizer_command is empty")?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context(format!("Failed to start tokenizer command: {}", command))?;
+
+    let mut stdin = child.stdin.take().context("Failed to open the tokenizer command's stdin")?;
+    let mut stdout = child.stdout.take().context("Failed to capture the tokenizer command's output")?;
+    let input = text.as_bytes().to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let read = (&mut stdout).take(MAX_OUTPUT_BYTES).read_to_end(&mut output);
+        let _ = std::io::copy(&mut stdout, &mut std::io::sink());
+        read.map(|_| output)
+    });
+
+    let deadline = Instant::now() + COMMAND_TIMEOUT;
+    let mut status = None;
+    while status.is_none() || !reader.is_finished() {
+        if status.is_none() {
+            status = child.try_wait().context("Failed to wait for tokenizer command")?;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("Tokenizer command did not finish within {}s", COMMAND_TIMEOUT.as_secs());
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let status = status.context("Failed to wait for tokenizer command")?;
+    if !status.success() {
+        anyhow::bail!("Tokenizer command exited with {}", status);
+    }
+    writer.join().expect("tokenizer stdin writer panicked").context("Failed to write to tokenizer command")?;
+    let output = reader.join().expect("tokenizer output reader panicked").context("Failed to read tokenizer output")?;
+
+    let stdout = String::from_utf8_lossy(&output);
+    let printed: String = stdout.trim().chars().take(40).collect();
+    let ellipsis = if stdout.trim().chars().count() > 40 { "..." } else { "" };
+    stdout.trim().parse()
+        .context(format!("Tokenizer command printed '{}{}' instead of a token count", printed, ellipsis))
+}
+
+impl Tokenizer {
+    pub fn new(name: Option<&str>, command: Option<&str>) -> Result<Tokenizer> {
+        let backend = match (name.unwrap_or("heuristic"), command) {
+            ("heuristic", _) => Backend::Heuristic,
+            ("bpe", _) => Backend::Bpe,
+            ("command", Some(command)) if !command.trim().is_empty() => Backend::Command(command.trim().to_string()),
+            ("command", _) => anyhow::bail!("tokenizer = \"command\" needs tokenizer_command in the config"),
+            (other, _) => anyhow::bail!("Invalid tokenizer '{}' in config (expected one of: {})", other, TOKENIZERS.join(", ")),
+        };
+        Ok(Tokenizer { backend, counts: Mutex::new(HashMap::new()), fallback_warning: Once::new() })
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        let command = match &self.backend {
+            Backend::Heuristic => return heuristic(text),
+            Backend::Bpe => return tiktoken_rs::cl100k_base_singleton().encode_ordinary(text).len(),
+            Backend::Command(command) => command,
+        };
+
+        let key = content_hash(text);
+        if let Some(&count) = self.counts.lock().unwrap().get(&key) {
+            return count;
+        }
+        if self.fallback_warning.is_completed() {
+            return heuristic(text);
+        }
+        let count = match run_command(command, text) {
+            Ok(count) => count,
+            Err(e) => {
+                self.fallback_warning.call_once(|| {
+                    eprintln!("{} {:#}; using the heuristic estimate", "Warning:".bright_yellow(), e);
+                });
+                heuristic(text)
+            }
+        };
+        self.counts.lock().unwrap().insert(key, count);
+        count
+    }
+}
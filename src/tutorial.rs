@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::fs;
+use std::path::Path;
+
+use crate::theme::{Paint, Role};
+
+const SAMPLE_NOTES: &str = "# Trip notes\n\n\
+- Flight to Lisbon departs Friday 07:40 from gate B12\n\
+- Hotel: Casa do Rio, check-in after 15:00, booking ref QX7-2291\n\
+- Must try: pastéis de nata at Manteigaria, tram 28 early in the morning\n\
+- Return flight Tuesday 18:05\n";
+
+enum Expect {
+    Question,
+    Command(&'static str),
+}
+
+struct Step {
+    title: &'static str,
+    lines: Vec<String>,
+    expect: Expect,
+}
+
+pub struct Tutorial {
+    steps: Vec<Step>,
+    current: usize,
+    shown: bool,
+}
+
+impl Tutorial {
+    pub fn new(dir: &Path) -> Result<Self> {
+        let notes = dir.join("trip-notes.md");
+        fs::write(&notes, SAMPLE_NOTES)
+            .context(format!("Failed to write file: {}", notes.display()))?;
+        let export = dir.join("conversation.html");
+
+        let steps = vec![
+            Step {
+                title: "Ask a question",
+                lines: vec![
+                    "Anything you type that doesn't start with / is sent to FastGPT.".to_string(),
+                    format!("Try: {}", "What is the tallest mountain in Europe?".bright_cyan()),
+                ],
+                expect: Expect::Question,
+            },
+            Step {
+                title: "Add a file as context",
+                lines: vec![
+                    "Files you attach are sent along with every question.".to_string(),
+                    "A sample notes file has been created for you. Attach it with:".to_string(),
+                    format!("/add-file {}", notes.display()).bright_cyan().to_string(),
+                ],
+                expect: Expect::Command("/add-file "),
+            },
+            Step {
+                title: "Ask about the file",
+                lines: vec![
+                    "Follow-up questions see both the attached file and the conversation so far.".to_string(),
+                    format!("Try: {}", "When does my return flight leave?".bright_cyan()),
+                ],
+                expect: Expect::Question,
+            },
+            Step {
+                title: "Review the conversation",
+                lines: vec![
+                    format!("{} lists every question and answer of this session.", "/history".bright_cyan()),
+                ],
+                expect: Expect::Command("/history"),
+            },
+            Step {
+                title: "Share it",
+                lines: vec![
+                    "Export the conversation as a standalone web page:".to_string(),
+                    format!("/export html {}", export.display()).bright_cyan().to_string(),
+                    "The sample files are deleted when you leave the tutorial, so copy the page elsewhere to keep it.".dimmed().to_string(),
+                ],
+                expect: Expect::Command("/export "),
+            },
+        ];
+
+        Ok(Tutorial { steps, current: 0, shown: false })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current >= self.steps.len()
+    }
+
+    pub fn print_intro(&self) {
+        println!("{}", "Welcome to the FastGPT tutorial!".bright_green().bold());
+        println!("This walks you through the basics in {} short steps, using a real session.", self.steps.len());
+        println!("Type {} at any time to see all commands, or {} to leave.", "/help".bright_cyan(), "/exit".bright_cyan());
+        println!();
+    }
+
+    pub fn print_step(&mut self) {
+        if self.shown || self.is_finished() {
+            return;
+        }
+        let step = &self.steps[self.current];
+        println!("{} {}", format!("Step {}/{}:", self.current + 1, self.steps.len()).bright_yellow().bold(), step.title.paint(Role::Query).bold());
+        for line in &step.lines {
+            println!("  {}", line);
+        }
+        println!();
+        self.shown = true;
+    }
+
+    pub fn observe(&mut self, input: &str) {
+        let Some(step) = self.steps.get(self.current) else {
+            return;
+        };
+        let matched = match step.expect {
+            Expect::Question => !input.starts_with('/'),
+            Expect::Command(command) => input == command.trim_end() || input.starts_with(command),
+        };
+        if matched {
+            self.current += 1;
+            self.shown = false;
+        }
+    }
+
+    pub fn print_outro(&self) {
+        println!("{}", "Tutorial complete!".bright_green().bold());
+        println!("Keep asking questions here, or type {} to leave. Run {} any time for a normal session.", "/exit".bright_cyan(), "fastgpt".bright_cyan());
+        println!();
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{get_data_dir, lock};
+
+pub const COST_PER_QUERY: f64 = 0.015;
+pub const BUDGET_WARNING_THRESHOLD: f64 = 0.8;
+
+#[derive(Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub timestamp: i64,
+    pub session_id: String,
+    pub tokens: u64,
+    pub ms: u64,
+}
+
+#[derive(Default)]
+pub struct Rollup {
+    pub queries: u64,
+    pub tokens: u64,
+    pub total_ms: u64,
+}
+
+impl Rollup {
+    fn add(&mut self, record: &UsageRecord) {
+        self.add_query(record.tokens, record.ms);
+    }
+
+    pub fn add_query(&mut self, tokens: u64, ms: u64) {
+        self.queries += 1;
+        self.tokens += tokens;
+        self.total_ms += ms;
+    }
+
+    pub fn average_ms(&self) -> u64 {
+        self.total_ms.checked_div(self.queries).unwrap_or(0)
+    }
+
+    pub fn estimated_cost(&self) -> f64 {
+        self.queries as f64 * COST_PER_QUERY
+    }
+}
+
+fn usage_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("usage.jsonl"))
+}
+
+pub fn record(session_id: &str, tokens: u64, ms: u64) -> Result<()> {
+    let record = UsageRecord {
+        timestamp: Local::now().timestamp(),
+        session_id: session_id.to_string(),
+        tokens,
+        ms,
+    };
+
+    let line = serde_json::to_string(&record)
+        .context("Failed to serialize usage record")?;
+
+    let path = usage_path()?;
+    let _lock = lock::exclusive(&lock_path(&path))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .context("Failed to open usage database")?;
+
+    writeln!(file, "{}", line)
+        .context("Failed to write usage record")?;
+
+    Ok(())
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    path.with_extension("jsonl.lock")
+}
+
+pub fn load_records() -> Result<Vec<UsageRecord>> {
+    let path = usage_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let _lock = lock::shared(&lock_path(&path))?;
+    let content = fs::read_to_string(&path)
+        .context("Failed to read usage database")?;
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+fn local_time(timestamp: i64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(timestamp, 0).single()
+}
+
+pub fn rollup_by(records: &[UsageRecord], format: &str) -> BTreeMap<String, Rollup> {
+    let mut rollups: BTreeMap<String, Rollup> = BTreeMap::new();
+
+    for record in records {
+        if let Some(time) = local_time(record.timestamp) {
+            rollups
+                .entry(time.format(format).to_string())
+                .or_default()
+                .add(record);
+        }
+    }
+
+    rollups
+}
+
+pub fn total(records: &[UsageRecord]) -> Rollup {
+    let mut rollup = Rollup::default();
+    for record in records {
+        rollup.add(record);
+    }
+    rollup
+}
+
+pub struct Budget {
+    pub tokens: Option<u64>,
+    pub cost: Option<f64>,
+    pub hard_stop: bool,
+}
+
+impl Budget {
+    pub fn used_fraction(&self, spent: &Rollup) -> f64 {
+        let token_fraction = self.tokens
+            .filter(|&limit| limit > 0)
+            .map(|limit| spent.tokens as f64 / limit as f64)
+            .unwrap_or(0.0);
+        let cost_fraction = self.cost
+            .filter(|&limit| limit > 0.0)
+            .map(|limit| spent.estimated_cost() / limit)
+            .unwrap_or(0.0);
+
+        token_fraction.max(cost_fraction)
+    }
+
+    pub fn describe(&self, spent: &Rollup) -> String {
+        let mut parts = Vec::new();
+        if let Some(limit) = self.tokens {
+            parts.push(format!("{} / {} tokens", spent.tokens, limit));
+        }
+        if let Some(limit) = self.cost {
+            parts.push(format!("${:.2} / ${:.2}", spent.estimated_cost(), limit));
+        }
+        parts.join(", ")
+    }
+}
+
+pub fn month_to_date() -> Result<Rollup> {
+    let month = Local::now().format("%Y-%m").to_string();
+    let records: Vec<UsageRecord> = load_records()?
+        .into_iter()
+        .filter(|record| {
+            local_time(record.timestamp)
+                .is_some_and(|time| time.format("%Y-%m").to_string() == month)
+        })
+        .collect();
+
+    Ok(total(&records))
+}
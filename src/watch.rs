@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use colored::*;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rustyline::ExternalPrinter;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct Watched {
+    files: HashMap<PathBuf, String>,
+    changed: HashSet<PathBuf>,
+    live: bool,
+}
+
+pub struct ContextWatcher {
+    watcher: RecommendedWatcher,
+    directories: HashSet<PathBuf>,
+    watched: Arc<Mutex<Watched>>,
+}
+
+impl ContextWatcher {
+    pub fn new(mut printer: Option<impl ExternalPrinter + Send + 'static>) -> Result<Self> {
+        let watched = Arc::new(Mutex::new(Watched::default()));
+        let shared = Arc::clone(&watched);
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                return;
+            }
+            let Ok(mut watched) = shared.lock() else {
+                return;
+            };
+            for path in event.paths {
+                let Some(name) = watched.files.get(&path).cloned() else {
+                    continue;
+                };
+                if !watched.changed.insert(path) {
+                    continue;
+                }
+                let next = if watched.live { "will be re-read" } else { "you'll be asked to refresh it" };
+                let message = format!("context file {} changed ({})", name, next).dimmed().to_string();
+                match &mut printer {
+                    Some(printer) => {
+                        printer.print(format!("{}\n", message)).ok();
+                    }
+                    None => eprintln!("{}", message),
+                }
+            }
+        })
+        .context("Failed to start watching files")?;
+
+        Ok(Self { watcher, directories: HashSet::new(), watched })
+    }
+
+    pub fn sync<'a>(&mut self, files: impl Iterator<Item = &'a Path>, live: bool) {
+        let files: HashMap<PathBuf, String> = files
+            .filter_map(|path| Some((path.canonicalize().ok()?, path.display().to_string())))
+            .collect();
+        let directories: HashSet<PathBuf> = files.keys().filter_map(|path| path.parent().map(Path::to_path_buf)).collect();
+
+        for directory in self.directories.difference(&directories) {
+            self.watcher.unwatch(directory).ok();
+        }
+        let mut watching: HashSet<PathBuf> = directories.intersection(&self.directories).cloned().collect();
+        for directory in directories.difference(&self.directories) {
+            match self.watcher.watch(directory, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    watching.insert(directory.clone());
+                }
+                Err(e) => eprintln!("{} Failed to watch {}: {}", "Warning:".bright_yellow(), directory.display(), e),
+            }
+        }
+        self.directories = watching;
+
+        if let Ok(mut watched) = self.watched.lock() {
+            watched.changed.retain(|path| files.contains_key(path));
+            watched.files = files;
+            watched.live = live;
+        }
+    }
+
+    pub fn clear_changes(&self) {
+        if let Ok(mut watched) = self.watched.lock() {
+            watched.changed.clear();
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::{Context, Result};
+use scraper::{ElementRef, Html, Node, Selector};
+use std::time::Duration;
+
+use crate::archives::MAX_ENTRY_BYTES;
+use crate::{documents, retention};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+const USER_AGENT: &str = concat!("fastgpt-cli/", env!("CARGO_PKG_VERSION"));
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "form", "button", "input",
+    "select", "textarea", "nav", "header", "footer", "aside", "figure",
+];
+const BLOCK_TAGS: &[&str] = &[
+    "p", "div", "section", "article", "main", "blockquote", "table", "tr", "ul", "ol", "dl", "dt", "dd",
+    "h1", "h2", "h3", "h4", "h5", "h6", "li", "pre", "br", "hr",
+];
+
+pub struct Page {
+    pub title: Option<String>,
+    pub text: String,
+}
+
+fn selector(css: &str) -> Selector {
+    Selector::parse(css).expect("valid selector")
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn is_boilerplate(element: &ElementRef) -> bool {
+    let value = element.value();
+    let hints = format!("{} {}", value.attr("class").unwrap_or_default(), value.attr("id").unwrap_or_default()).to_lowercase();
+    ["comment", "sidebar", "footer", "cookie", "newsletter", "share", "related", "advert", "promo", "breadcrumb"]
+        .iter()
+        .any(|hint| hints.contains(hint))
+        || value.attr("aria-hidden") == Some("true")
+        || value.attr("hidden").is_some()
+}
+
+fn paragraph_score(element: &ElementRef) -> usize {
+    element
+        .select(&selector("p, pre, li"))
+        .map(|paragraph| collapse(&paragraph.text().collect::<String>()).len())
+        .filter(|&chars| chars >= MIN_PARAGRAPH_CHARS)
+        .sum()
+}
+
+fn main_content(document: &Html) -> Option<ElementRef<'_>> {
+    for css in ["article", "main", "[role=main]"] {
+        let best = document.select(&selector(css)).max_by_key(paragraph_score);
+        if best.as_ref().is_some_and(|element| paragraph_score(element) > 0) {
+            return best;
+        }
+    }
+
+    document
+        .select(&selector("div, section, td"))
+        .filter(|element| !is_boilerplate(element))
+        .map(|element| {
+            let direct: usize = element
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(|child| matches!(child.value().name(), "p" | "pre" | "ul" | "ol" | "blockquote" | "h2" | "h3"))
+                .map(|child| collapse(&child.text().collect::<String>()).len())
+                .sum();
+            (direct, element)
+        })
+        .filter(|(score, _)| *score > 0)
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, element)| element)
+        .or_else(|| document.select(&selector("body")).next())
+}
+
+fn render(element: ElementRef, out: &mut String, preformatted: bool) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) if preformatted => out.push_str(text),
+            Node::Text(text) => {
+                let text = collapse(text);
+                if text.is_empty() {
+                    continue;
+                }
+                if !out.is_empty() && !out.ends_with(char::is_whitespace) && !text.starts_with(|c: char| ",.;:!?)".contains(c)) {
+                    out.push(' ');
+                }
+                out.push_str(&text);
+            }
+            Node::Element(_) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                let tag = child.value().name();
+                if SKIPPED_TAGS.contains(&tag) || is_boilerplate(&child) {
+                    continue;
+                }
+                let block = BLOCK_TAGS.contains(&tag);
+                if block && !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                match tag {
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        out.push('\n');
+                        out.push_str(&"#".repeat(tag[1..].parse().unwrap_or(1)));
+                        out.push(' ');
+                    }
+                    "li" => out.push_str("- "),
+                    "pre" => out.push_str("```\n"),
+                    _ => {}
+                }
+                render(child, out, preformatted || tag == "pre");
+                if tag == "pre" {
+                    out.push_str("\n```");
+                }
+                if block {
+                    out.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn extract(html: &str) -> Page {
+    let document = Html::parse_document(html);
+    let title = document
+        .select(&selector("meta[property='og:title']"))
+        .find_map(|meta| meta.value().attr("content").map(collapse))
+        .or_else(|| document.select(&selector("title")).next().map(|title| collapse(&title.text().collect::<String>())))
+        .filter(|title| !title.is_empty());
+
+    let mut text = String::new();
+    if let Some(content) = main_content(&document) {
+        render(content, &mut text, false);
+    }
+    Page { title, text: documents::tidy(&text) }
+}
+
+pub async fn fetch(client: &reqwest::Client, url: &str) -> Result<Page> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        anyhow::bail!("Only http:// and https:// URLs can be added: {}", url);
+    }
+    let mut response = client
+        .get(url)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .context(format!("Failed to fetch {}", url))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("Fetching {} failed with status {}", url, status);
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("text/html")
+        .to_lowercase();
+
+    let too_large = || anyhow::anyhow!("{} is larger than {}", url, retention::format_size(MAX_ENTRY_BYTES));
+    if response.content_length().is_some_and(|length| length > MAX_ENTRY_BYTES) {
+        return Err(too_large());
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.context(format!("Failed to read {}", url))? {
+        if (bytes.len() + chunk.len()) as u64 > MAX_ENTRY_BYTES {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    let body = String::from_utf8_lossy(&bytes).into_owned();
+
+    if content_type.contains("html") {
+        let page = extract(&body);
+        if page.text.is_empty() {
+            anyhow::bail!("No readable text found at {}", url);
+        }
+        Ok(page)
+    } else if content_type.starts_with("text/") || content_type.contains("json") || content_type.contains("xml") {
+        Ok(Page { title: None, text: body })
+    } else {
+        anyhow::bail!("{} is not a web page or text ({})", url, content_type);
+    }
+}